@@ -0,0 +1,53 @@
+//Reading huffman codes from one stream or four, `cargo bench --bench decode`
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use learning_huffman::{
+    compress_to_vec_with_options, decode_interleaved, decode_with_table, decompress_to_vec,
+    encode_interleaved, encode_with_table, get_byte_frequencies, Coder, CompressOptions, HuffCode,
+    HuffNode,
+};
+
+fn decode(c: &mut Criterion) {
+    let text = std::fs::read("./test_files/135-0.txt").unwrap();
+    //64 MiB of it, beyond the caches
+    let large: Vec<u8> = text.iter().copied().cycle().take(64 << 20).collect();
+
+    for (name, bytes) in [("gutenberg", &text), ("gutenberg 64 MiB", &large)] {
+        let table = HuffCode::from_tree(&HuffNode::from_frequencies(&get_byte_frequencies(bytes)));
+
+        let mut single = Vec::new();
+        let last_byte_size = encode_with_table(bytes, &table, &mut single).unwrap();
+        let mut interleaved = Vec::new();
+        encode_interleaved(bytes, &table, &mut interleaved).unwrap();
+
+        let compressed = compress_to_vec_with_options(bytes, &CompressOptions::new());
+        let compressed4 =
+            compress_to_vec_with_options(bytes, &CompressOptions::new().coder(Coder::HUFFMAN4));
+
+        let mut group = c.benchmark_group(format!("decode/{name}"));
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        if bytes.len() > text.len() {
+            group.sample_size(10);
+        }
+
+        group.bench_function("single stream", |b| {
+            b.iter(|| decode_with_table(black_box(&single), &table, last_byte_size).unwrap())
+        });
+        group.bench_function("4 streams", |b| {
+            b.iter(|| decode_interleaved(black_box(&interleaved), &table, bytes.len()).unwrap())
+        });
+        group.bench_function("decompress_to_vec huffman", |b| {
+            b.iter(|| decompress_to_vec(black_box(&compressed)).unwrap())
+        });
+        group.bench_function("decompress_to_vec huffman4", |b| {
+            b.iter(|| decompress_to_vec(black_box(&compressed4)).unwrap())
+        });
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
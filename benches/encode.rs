@@ -0,0 +1,69 @@
+//Writing huffman codes, `cargo bench --bench encode`
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use learning_huffman::{
+    compress_to_vec, encode_with_table, get_byte_frequencies, CodeTable, HuffCode, HuffNode,
+};
+
+///one bit at a time into a byte, as `encode_with_table` used to write codes
+fn bit_by_bit(bytes: &[u8], table: &CodeTable, output: &mut Vec<u8>) -> u8 {
+    let (mut current, mut used) = (0u8, 0);
+
+    for byte in bytes {
+        let code = &table[*byte];
+        for i in 0..code.get_length() {
+            current |= (((code.get_code() >> i) & 1) as u8) << used;
+            used += 1;
+
+            if used == 8 {
+                output.push(current);
+                (current, used) = (0, 0);
+            }
+        }
+    }
+    if used != 0 {
+        output.push(current);
+    }
+
+    used
+}
+
+fn encode(c: &mut Criterion) {
+    let text = std::fs::read("./test_files/135-0.txt").unwrap();
+    //64 MiB of it, beyond the caches
+    let large: Vec<u8> = text.iter().copied().cycle().take(64 << 20).collect();
+
+    for (name, bytes) in [("gutenberg", &text), ("gutenberg 64 MiB", &large)] {
+        let table = HuffCode::from_tree(&HuffNode::from_frequencies(&get_byte_frequencies(bytes)));
+        let mut output = Vec::new();
+
+        let mut group = c.benchmark_group(format!("encode/{name}"));
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        if bytes.len() > text.len() {
+            group.sample_size(10);
+        }
+
+        group.bench_function("bit by bit", |b| {
+            b.iter(|| {
+                output.clear();
+                bit_by_bit(black_box(bytes), &table, &mut output)
+            })
+        });
+        group.bench_function("accumulator", |b| {
+            b.iter(|| {
+                output.clear();
+                encode_with_table(black_box(bytes), &table, &mut output).unwrap()
+            })
+        });
+        group.bench_function("compress_to_vec", |b| {
+            b.iter(|| compress_to_vec(black_box(bytes)))
+        });
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, encode);
+criterion_main!(benches);
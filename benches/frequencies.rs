@@ -0,0 +1,90 @@
+//Counting byte frequencies, the first pass over every input
+//
+//`cargo bench --bench frequencies`, LH_BENCH_GB sets the size of the synthetic input (2 by default)
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use learning_huffman::{get_byte_frequencies, get_byte_frequencies_parallel};
+
+///one table, as `get_byte_frequencies` used to count
+fn single_table(bytes: &[u8]) -> [usize; 256] {
+    let mut frequencies = [0; 256];
+    for byte in bytes {
+        frequencies[*byte as usize] += 1;
+    }
+
+    frequencies
+}
+
+fn threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+fn gutenberg(c: &mut Criterion) {
+    let bytes = std::fs::read("./test_files/135-0.txt").unwrap();
+
+    let mut group = c.benchmark_group("frequencies/gutenberg");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+
+    group.bench_function("single table", |b| {
+        b.iter(|| single_table(black_box(&bytes)))
+    });
+    group.bench_function("interleaved tables", |b| {
+        b.iter(|| get_byte_frequencies(black_box(&bytes)))
+    });
+    group.bench_function(BenchmarkId::new("parallel", threads()), |b| {
+        b.iter(|| get_byte_frequencies_parallel(black_box(&bytes), threads()))
+    });
+
+    group.finish();
+}
+
+///`LH_BENCH_GB` of text and runs of the same byte, half each, counted a 64 MiB buffer at a time
+fn synthetic(c: &mut Criterion) {
+    const BUFFER_SIZE: usize = 64 << 20;
+
+    let gigabytes: u64 = std::env::var("LH_BENCH_GB")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(2);
+    let buffers = (gigabytes << 30) / BUFFER_SIZE as u64;
+
+    let text = std::fs::read("./test_files/135-0.txt").unwrap();
+    let mut buffer: Vec<u8> = text.iter().copied().cycle().take(BUFFER_SIZE / 2).collect();
+    buffer.extend((0..BUFFER_SIZE / 2).map(|i| (i >> 16) as u8));
+
+    let mut group = c.benchmark_group("frequencies/synthetic");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(buffers * BUFFER_SIZE as u64));
+
+    let mut bench = |name: BenchmarkId, count: &dyn Fn(&[u8]) -> [usize; 256]| {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut frequencies = [0; 256];
+                for _ in 0..buffers {
+                    let counts = count(black_box(&buffer));
+                    for (frequency, count) in frequencies.iter_mut().zip(counts) {
+                        *frequency += count;
+                    }
+                }
+                frequencies
+            })
+        });
+    };
+
+    bench(BenchmarkId::new("single table", gigabytes), &single_table);
+    bench(
+        BenchmarkId::new("interleaved tables", gigabytes),
+        &get_byte_frequencies,
+    );
+    bench(
+        BenchmarkId::new(format!("parallel {}", threads()), gigabytes),
+        &|bytes| get_byte_frequencies_parallel(bytes, threads()),
+    );
+
+    group.finish();
+}
+
+criterion_group!(benches, gutenberg, synthetic);
+criterion_main!(benches);
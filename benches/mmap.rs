@@ -0,0 +1,129 @@
+//Compressing a large file with `read_to_end` or through a memory map, `cargo bench --bench mmap --features mmap`
+//
+//Every method runs in a process of its own, which reports its time and peak RSS (VmHWM in /proc/self/status),
+//so this only works on Linux. The input is `LH_BENCH_GB` (1 by default) of `test_files/135-0.txt`
+
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, BufReader, Read, Write},
+    path::Path,
+    process::Command,
+    time::Instant,
+};
+
+use learning_huffman::{compress_file_mapped, CompressFile, CompressOptions};
+
+const METHODS: [&str; 2] = ["read_to_end", "mmap"];
+
+///peak resident memory of this process in KiB
+fn peak_rss() -> u64 {
+    let status = fs::read_to_string("/proc/self/status").expect("peak RSS is read from /proc");
+
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+        .expect("VmHWM in /proc/self/status")
+}
+
+///compress `input` to `output` with `method` and print the time and peak RSS
+fn child(method: &str, input: &str, output: &str) -> io::Result<()> {
+    let start = Instant::now();
+    match method {
+        "read_to_end" => CompressFile::new().compress(input)?.output_freq(output)?,
+        "mmap" => compress_file_mapped(input, output, &CompressOptions::new())?,
+        _ => panic!("unknown method {method}"),
+    }
+
+    println!("{} {}", start.elapsed().as_secs_f64(), peak_rss());
+    Ok(())
+}
+
+///`size` bytes of the text, written a piece at a time
+fn write_input(path: &Path, size: u64) -> io::Result<()> {
+    if fs::metadata(path).is_ok_and(|metadata| metadata.len() == size) {
+        return Ok(());
+    }
+
+    let text = fs::read("./test_files/135-0.txt")?;
+    let piece: Vec<u8> = text.iter().copied().cycle().take(64 << 20).collect();
+
+    let mut file = File::create(path)?;
+    let mut written = 0;
+    while written < size {
+        let count = piece.len().min((size - written) as usize);
+        file.write_all(&piece[..count])?;
+        written += count as u64;
+    }
+
+    Ok(())
+}
+
+fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (
+        BufReader::new(File::open(a)?),
+        BufReader::new(File::open(b)?),
+    );
+    let (mut buffer_a, mut buffer_b) = (vec![0; 1 << 20], vec![0; 1 << 20]);
+
+    loop {
+        let read = a.read(&mut buffer_a)?;
+        b.read_exact(&mut buffer_b[..read])?;
+        if buffer_a[..read] != buffer_b[..read] {
+            return Ok(false);
+        }
+        if read == 0 {
+            return Ok(b.read(&mut buffer_b)? == 0);
+        }
+    }
+}
+
+fn main() -> io::Result<()> {
+    if let Ok(method) = env::var("LH_BENCH_METHOD") {
+        let args: Vec<String> = env::args().collect();
+        return child(&method, &args[1], &args[2]);
+    }
+
+    let gigabytes: u64 = env::var("LH_BENCH_GB")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(1);
+
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let input = dir.join("mmap_bench_input");
+    write_input(&input, gigabytes << 30)?;
+
+    println!("compressing {gigabytes} GiB");
+    let mut outputs = Vec::new();
+    for method in METHODS {
+        let output = dir.join(format!("mmap_bench_{method}.huf"));
+        let _ = fs::remove_file(&output);
+
+        let result = Command::new(env::current_exe()?)
+            .env("LH_BENCH_METHOD", method)
+            .arg(&input)
+            .arg(&output)
+            .output()?;
+        assert!(result.status.success(), "{method} failed: {result:?}");
+
+        let result = String::from_utf8_lossy(&result.stdout);
+        let (seconds, rss) = result.trim().split_once(' ').expect("time and peak RSS");
+        let seconds: f64 = seconds.parse().unwrap();
+        let rss: u64 = rss.parse().unwrap();
+        println!(
+            "{method:>12}: {seconds:6.2} s, {:7.1} MiB/s, peak RSS {:7.1} MiB",
+            (gigabytes << 10) as f64 / seconds,
+            rss as f64 / 1024.0
+        );
+
+        outputs.push(output);
+    }
+
+    assert!(same_contents(&outputs[0], &outputs[1])?, "outputs differ");
+    for output in outputs {
+        fs::remove_file(output)?;
+    }
+
+    Ok(())
+}
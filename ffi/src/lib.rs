@@ -0,0 +1,583 @@
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CStr, CString},
+    io,
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use learning_huffman::{
+    compress_to_vec_with_options, decompress_bzip2_with_options, decompress_to_vec_with_options,
+    Coder, CompressOptions, DecompressOptions, LimitError, TreeMode,
+};
+
+//The C interface of learning_huffman, declared in include/learning_huffman.h, and the exports
+//for wasm32-unknown-unknown in `wasm`. Cargo.toml builds it as a static and a shared library
+
+pub mod wasm;
+
+///`flags` of `lh_compress`, see include/learning_huffman.h
+pub const LH_FLAG_BWT: u32 = 1;
+pub const LH_FLAG_LEGACY_TREE: u32 = 4;
+
+///Result of every call that can fail, `LhStatus::Ok` is 0
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LhStatus {
+    Ok = 0,
+    ///a null pointer, unknown coder or flag
+    InvalidArgument = 1,
+    ///the input isn't a valid compressed file
+    InvalidData = 2,
+    ///decompressing would go over `max_output_size`
+    Limit = 3,
+    ///a context was written to after `lh_finish`, or read from before it
+    InvalidState = 4,
+    ///a bug in the library, the call panicked
+    Panic = 5,
+}
+
+impl LhStatus {
+    const ALL: [LhStatus; 6] = [
+        LhStatus::Ok,
+        LhStatus::InvalidArgument,
+        LhStatus::InvalidData,
+        LhStatus::Limit,
+        LhStatus::InvalidState,
+        LhStatus::Panic,
+    ];
+
+    fn message(self) -> &'static CStr {
+        match self {
+            LhStatus::Ok => c"ok",
+            LhStatus::InvalidArgument => c"invalid argument",
+            LhStatus::InvalidData => c"invalid data",
+            LhStatus::Limit => c"limit exceeded",
+            LhStatus::InvalidState => c"invalid context state",
+            LhStatus::Panic => c"internal error",
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+///keep `message` for `lh_last_error` and return `status`
+pub(crate) fn fail(status: LhStatus, message: impl ToString) -> LhStatus {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+    status
+}
+
+pub(crate) fn fail_io(err: io::Error) -> LhStatus {
+    match LimitError::from_io(&err) {
+        Some(_) => fail(LhStatus::Limit, err),
+        None => fail(LhStatus::InvalidData, err),
+    }
+}
+
+///run `body`, a panic fails with `LhStatus::Panic` and returns `panicked` instead of unwinding into C
+pub(crate) fn catch_panic<T>(panicked: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(message) => message,
+            None => payload
+                .downcast_ref::<String>()
+                .map_or("unknown panic", String::as_str),
+        };
+        fail(LhStatus::Panic, format!("panicked: {message}"));
+        panicked
+    })
+}
+
+///`len` bytes at `data`, which may be null when `len` is 0
+unsafe fn input<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (data.is_null(), len) {
+        (true, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(slice::from_raw_parts(data, len)),
+    }
+}
+
+///hand `bytes` over to C, to be given back to `lh_free`
+unsafe fn output(bytes: Vec<u8>, data: *mut *mut u8, len: *mut usize) {
+    let bytes = Box::into_raw(bytes.into_boxed_slice());
+    *len = bytes.len();
+    *data = bytes as *mut u8;
+}
+
+unsafe fn compress_options(coder: *const c_char, flags: u32) -> Result<CompressOptions, LhStatus> {
+    let mut options = CompressOptions::new();
+
+    if !coder.is_null() {
+        let name = CStr::from_ptr(coder).to_string_lossy();
+        let coder = Coder::from_name(&name)
+            .ok_or_else(|| fail(LhStatus::InvalidArgument, format!("unknown coder {name}")))?;
+        options = options.coder(coder);
+    }
+
+    with_flags(options, flags)
+}
+
+///`options` with the tree mode and bwt set in `flags`
+pub(crate) fn with_flags(
+    options: CompressOptions,
+    flags: u32,
+) -> Result<CompressOptions, LhStatus> {
+    let options = match flags & !LH_FLAG_BWT {
+        0 => options.tree_mode(TreeMode::Standard),
+        LH_FLAG_LEGACY_TREE => options.tree_mode(TreeMode::Legacy),
+        _ => {
+            return Err(fail(
+                LhStatus::InvalidArgument,
+                format!("invalid flags {flags}"),
+            ))
+        }
+    };
+
+    Ok(options.bwt(flags & LH_FLAG_BWT != 0))
+}
+
+pub(crate) fn decompress_options(max_output_size: usize) -> DecompressOptions {
+    match max_output_size {
+        0 => DecompressOptions::new(),
+        bytes => DecompressOptions::new().max_output_size(bytes),
+    }
+}
+
+///files from `--bzip2` as well, like the command line
+pub(crate) fn decompress(bytes: &[u8], options: &DecompressOptions) -> io::Result<Vec<u8>> {
+    match bytes.starts_with(b"BZh") {
+        true => decompress_bzip2_with_options(bytes, options),
+        false => decompress_to_vec_with_options(bytes, options),
+    }
+}
+
+///static description of `status`, "unknown status" if it isn't one of `LhStatus`
+#[no_mangle]
+pub extern "C" fn lh_status_message(status: c_int) -> *const c_char {
+    catch_panic(ptr::null(), || {
+        LhStatus::ALL
+            .into_iter()
+            .find(|known| *known as c_int == status)
+            .map_or(c"unknown status", LhStatus::message)
+            .as_ptr()
+    })
+}
+
+///what went wrong in the last call on this thread that didn't return `LhStatus::Ok`,
+///valid until the next call fails
+#[no_mangle]
+pub extern "C" fn lh_last_error() -> *const c_char {
+    catch_panic(ptr::null(), || {
+        LAST_ERROR.with(|last| last.borrow().as_ptr())
+    })
+}
+
+///compress `input` into a new buffer like `learning_huffman -c` does
+///
+/// # Safety
+///`input` must point to `input_len` bytes, `coder` be null or a C string and
+///`output` and `output_len` be valid to write to
+#[no_mangle]
+pub unsafe extern "C" fn lh_compress(
+    input: *const u8,
+    input_len: usize,
+    coder: *const c_char,
+    flags: u32,
+    output: *mut *mut u8,
+    output_len: *mut usize,
+) -> LhStatus {
+    catch_panic(LhStatus::Panic, || {
+        let Some(bytes) = self::input(input, input_len) else {
+            return fail(LhStatus::InvalidArgument, "input is null");
+        };
+        if output.is_null() || output_len.is_null() {
+            return fail(LhStatus::InvalidArgument, "output is null");
+        }
+
+        match compress_options(coder, flags) {
+            Ok(options) => {
+                self::output(
+                    compress_to_vec_with_options(bytes, &options),
+                    output,
+                    output_len,
+                );
+                LhStatus::Ok
+            }
+            Err(status) => status,
+        }
+    })
+}
+
+///decompress `input` from `lh_compress` or the command line into a new buffer,
+///failing with `LhStatus::Limit` before writing more than `max_output_size` bytes unless it is 0
+///
+/// # Safety
+///`input` must point to `input_len` bytes and `output` and `output_len` be valid to write to
+#[no_mangle]
+pub unsafe extern "C" fn lh_decompress(
+    input: *const u8,
+    input_len: usize,
+    max_output_size: usize,
+    output: *mut *mut u8,
+    output_len: *mut usize,
+) -> LhStatus {
+    catch_panic(LhStatus::Panic, || {
+        let Some(bytes) = self::input(input, input_len) else {
+            return fail(LhStatus::InvalidArgument, "input is null");
+        };
+        if output.is_null() || output_len.is_null() {
+            return fail(LhStatus::InvalidArgument, "output is null");
+        }
+
+        match decompress(bytes, &decompress_options(max_output_size)) {
+            Ok(decompressed) => {
+                self::output(decompressed, output, output_len);
+                LhStatus::Ok
+            }
+            Err(err) => fail_io(err),
+        }
+    })
+}
+
+///free a buffer from `lh_compress` or `lh_decompress`
+///
+/// # Safety
+///`buffer` and `len` must be from one of them and not freed yet, or `buffer` null
+#[no_mangle]
+pub unsafe extern "C" fn lh_free(buffer: *mut u8, len: usize) {
+    catch_panic((), || {
+        if !buffer.is_null() {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer, len)));
+        }
+    })
+}
+
+enum Direction {
+    Compress(CompressOptions),
+    Decompress(DecompressOptions),
+}
+
+///A streaming context, input is written to it in pieces and after `lh_finish`
+///the output read from it in pieces
+///
+///The frequency header needs all of the input, so nothing can be read before it is finished
+pub struct LhContext {
+    direction: Direction,
+    input: Vec<u8>,
+    ///`None` until finished
+    output: Option<Vec<u8>>,
+    position: usize,
+}
+
+impl LhContext {
+    fn new(direction: Direction) -> *mut LhContext {
+        Box::into_raw(Box::new(LhContext {
+            direction,
+            input: Vec::new(),
+            output: None,
+            position: 0,
+        }))
+    }
+}
+
+///new context compressing like `lh_compress`, null if `coder` or `flags` are invalid
+///
+/// # Safety
+///`coder` must be null or a C string
+#[no_mangle]
+pub unsafe extern "C" fn lh_compress_new(coder: *const c_char, flags: u32) -> *mut LhContext {
+    catch_panic(ptr::null_mut(), || match compress_options(coder, flags) {
+        Ok(options) => LhContext::new(Direction::Compress(options)),
+        Err(_) => ptr::null_mut(),
+    })
+}
+
+///new context decompressing like `lh_decompress`
+#[no_mangle]
+pub extern "C" fn lh_decompress_new(max_output_size: usize) -> *mut LhContext {
+    catch_panic(ptr::null_mut(), || {
+        LhContext::new(Direction::Decompress(decompress_options(max_output_size)))
+    })
+}
+
+///add `len` bytes at `data` to the input
+///
+/// # Safety
+///`context` must be from `lh_compress_new` or `lh_decompress_new` and `data` point to `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn lh_write(
+    context: *mut LhContext,
+    data: *const u8,
+    len: usize,
+) -> LhStatus {
+    catch_panic(LhStatus::Panic, || {
+        let (Some(context), Some(bytes)) = (context.as_mut(), input(data, len)) else {
+            return fail(LhStatus::InvalidArgument, "context or data is null");
+        };
+        if context.output.is_some() {
+            return fail(LhStatus::InvalidState, "context is already finished");
+        }
+
+        context.input.extend_from_slice(bytes);
+        LhStatus::Ok
+    })
+}
+
+///end the input and compress or decompress it
+///
+/// # Safety
+///`context` must be from `lh_compress_new` or `lh_decompress_new`
+#[no_mangle]
+pub unsafe extern "C" fn lh_finish(context: *mut LhContext) -> LhStatus {
+    catch_panic(LhStatus::Panic, || {
+        let Some(context) = context.as_mut() else {
+            return fail(LhStatus::InvalidArgument, "context is null");
+        };
+        if context.output.is_some() {
+            return fail(LhStatus::InvalidState, "context is already finished");
+        }
+
+        let input = std::mem::take(&mut context.input);
+        let output = match &context.direction {
+            Direction::Compress(options) => compress_to_vec_with_options(&input, options),
+            Direction::Decompress(options) => match decompress(&input, options) {
+                Ok(decompressed) => decompressed,
+                Err(err) => return fail_io(err),
+            },
+        };
+
+        context.output = Some(output);
+        LhStatus::Ok
+    })
+}
+
+///copy up to `capacity` bytes of the output to `buffer`, `*read` is 0 once all of it was read
+///
+/// # Safety
+///`context` must be from `lh_compress_new` or `lh_decompress_new`, `buffer` have room
+///for `capacity` bytes and `read` be valid to write to
+#[no_mangle]
+pub unsafe extern "C" fn lh_read(
+    context: *mut LhContext,
+    buffer: *mut u8,
+    capacity: usize,
+    read: *mut usize,
+) -> LhStatus {
+    catch_panic(LhStatus::Panic, || {
+        let Some(context) = context.as_mut() else {
+            return fail(LhStatus::InvalidArgument, "context is null");
+        };
+        if (buffer.is_null() && capacity > 0) || read.is_null() {
+            return fail(LhStatus::InvalidArgument, "buffer or read is null");
+        }
+        let Some(output) = &context.output else {
+            return fail(LhStatus::InvalidState, "context isn't finished");
+        };
+
+        let count = capacity.min(output.len() - context.position);
+        if count > 0 {
+            ptr::copy_nonoverlapping(output[context.position..].as_ptr(), buffer, count);
+        }
+        context.position += count;
+        *read = count;
+
+        LhStatus::Ok
+    })
+}
+
+///free a context, finished or not
+///
+/// # Safety
+///`context` must be from `lh_compress_new` or `lh_decompress_new` and not freed yet, or null
+#[no_mangle]
+pub unsafe extern "C" fn lh_context_free(context: *mut LhContext) {
+    catch_panic((), || {
+        if !context.is_null() {
+            drop(Box::from_raw(context));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compress(bytes: &[u8], coder: &CStr, flags: u32) -> Result<Vec<u8>, LhStatus> {
+        let (mut data, mut len) = (ptr::null_mut(), 0);
+
+        unsafe {
+            let status = lh_compress(
+                bytes.as_ptr(),
+                bytes.len(),
+                coder.as_ptr(),
+                flags,
+                &mut data,
+                &mut len,
+            );
+            if status != LhStatus::Ok {
+                return Err(status);
+            }
+
+            let compressed = slice::from_raw_parts(data, len).to_vec();
+            lh_free(data, len);
+            Ok(compressed)
+        }
+    }
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(lh_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn compress_decompress_test() {
+        let bytes = std::fs::read("../README.md").unwrap();
+
+        let compressed = compress(&bytes, c"ans", LH_FLAG_BWT).unwrap();
+        assert_eq!(
+            learning_huffman::decompress_to_vec(&compressed).unwrap(),
+            bytes
+        );
+
+        let (mut data, mut len) = (ptr::null_mut(), 0);
+        unsafe {
+            let status = lh_decompress(
+                compressed.as_ptr(),
+                compressed.len(),
+                0,
+                &mut data,
+                &mut len,
+            );
+            assert_eq!(status, LhStatus::Ok);
+            assert_eq!(slice::from_raw_parts(data, len), bytes);
+            lh_free(data, len);
+
+            let status = lh_decompress(
+                compressed.as_ptr(),
+                compressed.len(),
+                10,
+                &mut data,
+                &mut len,
+            );
+            assert_eq!(status, LhStatus::Limit);
+            assert!(last_error().contains("10"));
+
+            let status = lh_decompress(compressed.as_ptr(), 5, 0, &mut data, &mut len);
+            assert_eq!(status, LhStatus::InvalidData);
+        }
+
+        //the same as without the C interface
+        let options = CompressOptions::new().tree_mode(TreeMode::Legacy);
+        assert_eq!(
+            compress(&bytes, c"huffman", LH_FLAG_LEGACY_TREE).unwrap(),
+            compress_to_vec_with_options(&bytes, &options)
+        );
+    }
+
+    #[test]
+    fn invalid_arguments_test() {
+        assert_eq!(compress(b"abc", c"zip", 0), Err(LhStatus::InvalidArgument));
+        assert_eq!(last_error(), "unknown coder zip");
+
+        //2 was a tree mode that built the same trees as the standard one
+        let flags = 2 | LH_FLAG_LEGACY_TREE;
+        assert_eq!(
+            compress(b"abc", c"ans", flags),
+            Err(LhStatus::InvalidArgument)
+        );
+        assert_eq!(compress(b"abc", c"ans", 8), Err(LhStatus::InvalidArgument));
+
+        let (mut data, mut len) = (ptr::null_mut(), 0);
+        unsafe {
+            let status = lh_compress(ptr::null(), 3, ptr::null(), 0, &mut data, &mut len);
+            assert_eq!(status, LhStatus::InvalidArgument);
+
+            //empty input can be null
+            let status = lh_compress(ptr::null(), 0, ptr::null(), 0, &mut data, &mut len);
+            assert_eq!(status, LhStatus::Ok);
+            lh_free(data, len);
+
+            assert!(lh_compress_new(c"zip".as_ptr(), 0).is_null());
+        }
+
+        let message = |status| unsafe { CStr::from_ptr(lh_status_message(status)) };
+        assert_eq!(message(LhStatus::Limit as c_int), c"limit exceeded");
+        assert_eq!(message(LhStatus::Panic as c_int), c"internal error");
+        assert_eq!(message(6), c"unknown status");
+        assert_eq!(message(-1), c"unknown status");
+    }
+
+    #[test]
+    fn catch_panic_test() {
+        let status = catch_panic(LhStatus::Panic, || panic!("at the disco"));
+        assert_eq!(status, LhStatus::Panic);
+        assert_eq!(last_error(), "panicked: at the disco");
+
+        let context = catch_panic(ptr::null_mut(), || -> *mut LhContext { panic!("{}", 1) });
+        assert!(context.is_null());
+        assert_eq!(last_error(), "panicked: 1");
+
+        assert_eq!(catch_panic(LhStatus::Panic, || LhStatus::Ok), LhStatus::Ok);
+    }
+
+    #[test]
+    fn context_test() {
+        let bytes = std::fs::read("../README.md").unwrap();
+
+        unsafe {
+            let context = lh_compress_new(c"range".as_ptr(), 0);
+            for chunk in bytes.chunks(1000) {
+                assert_eq!(lh_write(context, chunk.as_ptr(), chunk.len()), LhStatus::Ok);
+            }
+
+            let mut buffer = [0; 100];
+            let mut read = 0;
+            let status = lh_read(context, buffer.as_mut_ptr(), buffer.len(), &mut read);
+            assert_eq!(status, LhStatus::InvalidState);
+
+            assert_eq!(lh_finish(context), LhStatus::Ok);
+            assert_eq!(lh_write(context, bytes.as_ptr(), 1), LhStatus::InvalidState);
+            assert_eq!(lh_finish(context), LhStatus::InvalidState);
+
+            let mut compressed = Vec::new();
+            loop {
+                let status = lh_read(context, buffer.as_mut_ptr(), buffer.len(), &mut read);
+                assert_eq!(status, LhStatus::Ok);
+                if read == 0 {
+                    break;
+                }
+                compressed.extend_from_slice(&buffer[..read]);
+            }
+            lh_context_free(context);
+
+            let options = CompressOptions::new().coder(Coder::from_name("range").unwrap());
+            assert_eq!(compressed, compress_to_vec_with_options(&bytes, &options));
+
+            let context = lh_decompress_new(0);
+            for chunk in compressed.chunks(7) {
+                assert_eq!(lh_write(context, chunk.as_ptr(), chunk.len()), LhStatus::Ok);
+            }
+            assert_eq!(lh_finish(context), LhStatus::Ok);
+
+            let mut decompressed = vec![0; bytes.len() + 1];
+            let status = lh_read(
+                context,
+                decompressed.as_mut_ptr(),
+                decompressed.len(),
+                &mut read,
+            );
+            assert_eq!(status, LhStatus::Ok);
+            assert_eq!(&decompressed[..read], bytes);
+            lh_context_free(context);
+
+            let context = lh_decompress_new(0);
+            assert_eq!(lh_write(context, compressed.as_ptr(), 3), LhStatus::Ok);
+            assert_eq!(lh_finish(context), LhStatus::InvalidData);
+            lh_context_free(context);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::{cell::RefCell, ffi::CStr, ptr, slice};
+
+use learning_huffman::{compress_to_vec_with_options, Coder, CompressOptions};
+
+use crate::{
+    catch_panic, decompress, decompress_options, fail, fail_io, lh_last_error, with_flags, LhStatus,
+};
+
+//Exports for `wasm32-unknown-unknown`, used through wasm/learning_huffman.mjs.
+//JS copies its input into memory from `lh_wasm_alloc`, calls one of the functions and copies
+//the output out of `lh_wasm_output`. Only byte arrays and numbers cross the boundary, so the
+//module needs no bindings or glue code. They are plain functions on other targets.
+//Panics abort on `wasm32-unknown-unknown`, so `LhStatus::Panic` only comes up on the others
+
+thread_local! {
+    ///output of the last call, or its error message if it failed
+    static OUTPUT: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+fn finish(result: Result<Vec<u8>, LhStatus>) -> LhStatus {
+    let (status, output) = match result {
+        Ok(output) => (LhStatus::Ok, output),
+        Err(status) => {
+            let message = unsafe { CStr::from_ptr(lh_last_error()) };
+            (status, message.to_bytes().to_vec())
+        }
+    };
+
+    OUTPUT.with(|last| *last.borrow_mut() = output);
+    status
+}
+
+///room for `len` bytes of input, to be given back to `lh_wasm_free`
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
+pub extern "C" fn lh_wasm_alloc(len: usize) -> *mut u8 {
+    catch_panic(ptr::null_mut(), || {
+        let mut buffer = std::mem::ManuallyDrop::new(Vec::<u8>::with_capacity(len));
+        buffer.as_mut_ptr()
+    })
+}
+
+///free memory from `lh_wasm_alloc`
+///
+/// # Safety
+///`buffer` and `len` must be from `lh_wasm_alloc` and not freed yet
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
+pub unsafe extern "C" fn lh_wasm_free(buffer: *mut u8, len: usize) {
+    catch_panic((), || drop(Vec::from_raw_parts(buffer, 0, len)))
+}
+
+///compress like `lh_compress`, with `coder` the id of a `Coder` instead of its name
+///
+/// # Safety
+///`input` must point to `input_len` bytes
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
+pub unsafe extern "C" fn lh_wasm_compress(
+    input: *const u8,
+    input_len: usize,
+    coder: u32,
+    flags: u32,
+) -> LhStatus {
+    finish(catch_panic(Err(LhStatus::Panic), || {
+        let bytes = slice::from_raw_parts(input, input_len);
+
+        let coder = u8::try_from(coder)
+            .ok()
+            .and_then(Coder::from_id)
+            .ok_or_else(|| {
+                fail(
+                    LhStatus::InvalidArgument,
+                    format!("unknown coder id {coder}"),
+                )
+            });
+
+        coder
+            .and_then(|coder| with_flags(CompressOptions::new().coder(coder), flags))
+            .map(|options| compress_to_vec_with_options(bytes, &options))
+    }))
+}
+
+///decompress like `lh_decompress`
+///
+/// # Safety
+///`input` must point to `input_len` bytes
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
+pub unsafe extern "C" fn lh_wasm_decompress(
+    input: *const u8,
+    input_len: usize,
+    max_output_size: usize,
+) -> LhStatus {
+    finish(catch_panic(Err(LhStatus::Panic), || {
+        let bytes = slice::from_raw_parts(input, input_len);
+        decompress(bytes, &decompress_options(max_output_size)).map_err(fail_io)
+    }))
+}
+
+///start of the output of the last call, valid until the next one
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
+pub extern "C" fn lh_wasm_output() -> *const u8 {
+    catch_panic(ptr::null(), || {
+        OUTPUT.with(|output| output.borrow().as_ptr())
+    })
+}
+
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
+pub extern "C" fn lh_wasm_output_len() -> usize {
+    catch_panic(0, || OUTPUT.with(|output| output.borrow().len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LH_FLAG_BWT;
+
+    ///what the JS side does
+    fn call(bytes: &[u8], call: impl FnOnce(*const u8, usize) -> LhStatus) -> (LhStatus, Vec<u8>) {
+        let input = lh_wasm_alloc(bytes.len());
+        unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), input, bytes.len());
+            let status = call(input, bytes.len());
+            lh_wasm_free(input, bytes.len());
+
+            let output = slice::from_raw_parts(lh_wasm_output(), lh_wasm_output_len());
+            (status, output.to_vec())
+        }
+    }
+
+    #[test]
+    fn compress_decompress_test() {
+        let bytes = std::fs::read("../README.md").unwrap();
+
+        for coder in learning_huffman::CODERS {
+            let (status, compressed) = call(&bytes, |input, len| unsafe {
+                lh_wasm_compress(input, len, coder.id() as u32, LH_FLAG_BWT)
+            });
+            assert_eq!(status, LhStatus::Ok);
+
+            let options = CompressOptions::new().coder(coder).bwt(true);
+            assert_eq!(compressed, compress_to_vec_with_options(&bytes, &options));
+
+            let (status, decompressed) = call(&compressed, |input, len| unsafe {
+                lh_wasm_decompress(input, len, 0)
+            });
+            assert_eq!(status, LhStatus::Ok);
+            assert_eq!(decompressed, bytes);
+        }
+    }
+
+    #[test]
+    fn errors_test() {
+        let (status, message) = call(b"abc", |input, len| unsafe {
+            lh_wasm_compress(input, len, 7, 0)
+        });
+        assert_eq!(status, LhStatus::InvalidArgument);
+        assert_eq!(message, b"unknown coder id 7");
+
+        let (status, _) = call(b"abc", |input, len| unsafe {
+            lh_wasm_decompress(input, len, 0)
+        });
+        assert_eq!(status, LhStatus::InvalidData);
+
+        let compressed = learning_huffman::compress_to_vec(b"abcabc");
+        let (status, message) = call(&compressed, |input, len| unsafe {
+            lh_wasm_decompress(input, len, 5)
+        });
+        assert_eq!(status, LhStatus::Limit);
+        assert!(!message.is_empty());
+
+        //nothing to compress still gives a header
+        let (status, compressed) = call(b"", |input, len| unsafe {
+            lh_wasm_compress(input, len, 0, 0)
+        });
+        assert_eq!(status, LhStatus::Ok);
+        assert_eq!(compressed, learning_huffman::compress_to_vec(b""));
+        //a panic comes back as null instead of unwinding out of the call
+        assert!(lh_wasm_alloc(usize::MAX).is_null());
+        let message = unsafe { CStr::from_ptr(lh_last_error()) };
+        assert!(message.to_str().unwrap().contains("capacity overflow"));
+    }
+}
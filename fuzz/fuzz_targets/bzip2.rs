@@ -0,0 +1,16 @@
+#![no_main]
+
+use learning_huffman::{compress_bzip2, decompress_bzip2_with_options, DecompressOptions};
+use libfuzzer_sys::fuzz_target;
+
+//arbitrary bytes after a valid stream magic, then a round trip of the input
+fuzz_target!(|data: &[u8]| {
+    let mut stream = b"BZh1".to_vec();
+    stream.extend_from_slice(data);
+
+    let options = DecompressOptions::new().max_output_size(1 << 24);
+    let _ = decompress_bzip2_with_options(&stream, &options);
+
+    let compressed = compress_bzip2(data, 1);
+    assert_eq!(decompress_bzip2_with_options(&compressed, &options).unwrap(), data);
+});
@@ -0,0 +1,22 @@
+#![no_main]
+
+use learning_huffman::{DecompressOptions, FreqHeader};
+use libfuzzer_sys::fuzz_target;
+
+//whole compressed files, header first
+fuzz_target!(|data: &[u8]| {
+    let options = DecompressOptions::new()
+        .max_memory(1 << 26)
+        .max_output_size(1 << 24);
+    let mut reader = data;
+
+    if let Ok(header) = FreqHeader::read(&mut reader, &options) {
+        assert_eq!(data.len() - reader.len(), header.size());
+
+        if header.total() <= 1 << 24 {
+            if let Ok(decoded) = header.decode_with_options(reader, &options) {
+                assert_eq!(decoded.len(), header.total());
+            }
+        }
+    }
+});
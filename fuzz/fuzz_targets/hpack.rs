@@ -0,0 +1,18 @@
+#![no_main]
+
+use learning_huffman::{hpack_decode, hpack_encode, hpack_encoded_len};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(decoded) = hpack_decode(data) {
+        //valid padding is unique, so there is no other way to code it
+        let mut encoded = Vec::new();
+        hpack_encode(&decoded, &mut encoded);
+        assert_eq!(encoded, data);
+    }
+
+    let mut encoded = Vec::new();
+    hpack_encode(data, &mut encoded);
+    assert_eq!(encoded.len(), hpack_encoded_len(data));
+    assert_eq!(hpack_decode(&encoded).unwrap(), data);
+});
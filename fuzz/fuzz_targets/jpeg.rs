@@ -0,0 +1,15 @@
+#![no_main]
+
+use learning_huffman::{decode_jpeg_with_options, parse_dht, DecompressOptions};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = parse_dht(data);
+
+    //arbitrary segments and scan data after the start of image marker
+    let mut bytes = vec![0xff, 0xd8];
+    bytes.extend_from_slice(data);
+
+    let options = DecompressOptions::new().max_output_size(1 << 24);
+    let _ = decode_jpeg_with_options(&bytes, &options);
+});
@@ -0,0 +1,42 @@
+#![no_main]
+
+use learning_huffman::{DecompressOptions, FreqHeader};
+use libfuzzer_sys::fuzz_target;
+
+//a valid header built from the first bytes, followed by an arbitrary payload
+fuzz_target!(|data: &[u8]| {
+    let Some((&symbols, rest)) = data.split_first() else {
+        return;
+    };
+    let Some((&last_byte_size, rest)) = rest.split_first() else {
+        return;
+    };
+    let (table, payload) = rest.split_at((symbols as usize * 2).min(rest.len()));
+
+    let mut frequencies = [0u64; 256];
+    for pair in table.chunks_exact(2) {
+        frequencies[pair[0] as usize] = frequencies[pair[0] as usize].max(pair[1] as u64 + 1);
+    }
+
+    let entries = frequencies.iter().filter(|freq| **freq != 0).count() as u64;
+    let mut header = Vec::new();
+    header.extend_from_slice(&(1 + entries * 9).to_be_bytes());
+    //the high bit picks huffman4 with a standard tree, whose streams end on whole bytes
+    header.push(match last_byte_size & 0x80 {
+        0 => last_byte_size % 8,
+        _ => 0x68,
+    });
+    for (byte, freq) in frequencies.iter().enumerate() {
+        if *freq != 0 {
+            header.push(byte as u8);
+            header.extend_from_slice(&freq.to_be_bytes());
+        }
+    }
+
+    let header = FreqHeader::read(&mut &header[..], &DecompressOptions::new())
+        .expect("valid header rejected");
+
+    if let Ok(decoded) = header.decode(payload) {
+        assert_eq!(decoded.len(), header.total());
+    }
+});
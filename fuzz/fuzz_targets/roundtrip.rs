@@ -0,0 +1,15 @@
+#![no_main]
+
+use learning_huffman::{compress_to_vec_with_options, decompress_to_vec, Coder, CompressOptions};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for coder in [Coder::HUFFMAN, Coder::RANGE, Coder::ANS, Coder::HUFFMAN4] {
+        for bwt in [false, true] {
+            let options = CompressOptions::new().coder(coder).bwt(bwt);
+            let compressed = compress_to_vec_with_options(data, &options);
+
+            assert_eq!(decompress_to_vec(&compressed).unwrap(), data);
+        }
+    }
+});
@@ -0,0 +1,25 @@
+#![no_main]
+
+use learning_huffman::{decompress_to_vec, Decoder};
+use libfuzzer_sys::fuzz_target;
+
+//`Decoder` fed in pieces has to give the same result as decoding all of the input at once
+fuzz_target!(|data: &[u8]| {
+    let Some((&size, input)) = data.split_first() else {
+        return;
+    };
+
+    let mut decoder = Decoder::default();
+    let mut output = Vec::new();
+    let pieces = input
+        .chunks(size.max(1) as usize)
+        .try_for_each(|piece| decoder.feed(piece, &mut output).map(|_| ()))
+        .and_then(|_| decoder.finish(&mut output))
+        .map(|_| output);
+
+    match (decompress_to_vec(input), pieces) {
+        (Ok(whole), Ok(pieces)) => assert_eq!(whole, pieces),
+        (Err(whole), Err(pieces)) => assert_eq!(whole.kind(), pieces.kind()),
+        (whole, pieces) => panic!("{whole:?} {pieces:?}"),
+    }
+});
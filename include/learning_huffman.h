@@ -0,0 +1,84 @@
+/*
+ * C interface of learning_huffman, implemented in ffi/src/lib.rs.
+ *
+ * Link against liblearning_huffman_ffi.a (with -lpthread -ldl -lm) or liblearning_huffman_ffi.so
+ * from `cargo build --release -p learning_huffman-ffi`. Compressed buffers are the same as the files
+ * `learning_huffman -c` writes, and `lh_decompress` reads those and its `--bzip2` files.
+ */
+
+#ifndef LEARNING_HUFFMAN_H
+#define LEARNING_HUFFMAN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* result of every call that can fail, see lh_last_error for details */
+typedef enum lh_status {
+    LH_OK = 0,
+    /* a null pointer, unknown coder or flag */
+    LH_INVALID_ARGUMENT = 1,
+    /* the input isn't a valid compressed file */
+    LH_INVALID_DATA = 2,
+    /* decompressing would go over max_output_size */
+    LH_LIMIT = 3,
+    /* a context was written to after lh_finish, or read from before it */
+    LH_INVALID_STATE = 4,
+    /* a bug in the library, the call panicked */
+    LH_PANIC = 5,
+} lh_status;
+
+/* flags of lh_compress and lh_compress_new */
+#define LH_FLAG_BWT 1u
+#define LH_FLAG_LEGACY_TREE 4u
+
+/* streaming context from lh_compress_new or lh_decompress_new */
+typedef struct LhContext lh_context;
+
+/* static description of status, "unknown status" if it isn't an lh_status */
+const char *lh_status_message(int status);
+
+/* what went wrong in the last call on this thread that failed, valid until the next one fails */
+const char *lh_last_error(void);
+
+/*
+ * compress input_len bytes at input into a new buffer, freed with lh_free.
+ * coder is "huffman", "range", "ans" or "huffman4", NULL for huffman
+ */
+lh_status lh_compress(const uint8_t *input, size_t input_len, const char *coder, uint32_t flags,
+                      uint8_t **output, size_t *output_len);
+
+/* decompress into a new buffer, failing with LH_LIMIT over max_output_size bytes unless it is 0 */
+lh_status lh_decompress(const uint8_t *input, size_t input_len, size_t max_output_size,
+                        uint8_t **output, size_t *output_len);
+
+/* free a buffer from lh_compress or lh_decompress, NULL is ignored */
+void lh_free(uint8_t *buffer, size_t len);
+
+/*
+ * contexts take their input in pieces with lh_write, then after lh_finish give their output
+ * in pieces with lh_read. The frequency header needs all of the input, so nothing can be read
+ * before lh_finish
+ */
+
+/* NULL if coder or flags are invalid */
+lh_context *lh_compress_new(const char *coder, uint32_t flags);
+lh_context *lh_decompress_new(size_t max_output_size);
+
+lh_status lh_write(lh_context *context, const uint8_t *data, size_t len);
+lh_status lh_finish(lh_context *context);
+
+/* copy up to capacity bytes of the output to buffer, *read is 0 once all of it was read */
+lh_status lh_read(lh_context *context, uint8_t *buffer, size_t capacity, size_t *read);
+
+/* free a context, finished or not. NULL is ignored */
+void lh_context_free(lh_context *context);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,536 @@
+use alloc::{format, vec, vec::Vec};
+
+use crate::{fileops::repeat_byte, header::invalid_data, io};
+
+///the table has 2^log states, more follow the frequencies closer but take longer to build
+const MIN_TABLE_LOG: u32 = 5;
+const MAX_TABLE_LOG: u32 = 12;
+const DEFAULT_TABLE_LOG: u32 = 12;
+
+///size of `table_log` and `total` in front of the entries of a compact table
+const TABLE_HEADER_SIZE: usize = 1 + 8;
+
+///byte + normalized count
+const TABLE_ENTRY_SIZE: usize = 1 + 2;
+
+///scale `frequencies` to add up to a power of two, every used byte keeps at least 1
+///
+///the table log is picked from the input size and number of used bytes, at most 12
+pub fn normalize_frequencies(frequencies: &[usize; 256]) -> [usize; 256] {
+    let total: u128 = frequencies.iter().map(|freq| *freq as u128).sum();
+    let used = frequencies.iter().filter(|freq| **freq != 0).count();
+
+    let mut normalized = [0; 256];
+    if used == 0 {
+        return normalized;
+    }
+
+    let log = ceil_log2(total)
+        .clamp(MIN_TABLE_LOG, DEFAULT_TABLE_LOG)
+        .max(ceil_log2(used as u128) + 1);
+    let size = 1usize << log;
+
+    for (normalized, freq) in normalized.iter_mut().zip(frequencies) {
+        if *freq != 0 {
+            let scaled = (*freq as u128 * size as u128 + total / 2) / total;
+            *normalized = (scaled as usize).max(1);
+        }
+    }
+
+    //rounding is off by at most one per byte, move each step to the byte it costs the fewest bits
+    let mut sum: usize = normalized.iter().sum();
+    while sum != size {
+        let cost = |byte: usize, count: usize| {
+            frequencies[byte] as f64 * ln(normalized[byte] as f64 / count as f64)
+        };
+
+        if sum > size {
+            let byte = (0..256)
+                .filter(|byte| normalized[*byte] > 1)
+                .min_by(|a, b| {
+                    cost(*a, normalized[*a] - 1).total_cmp(&cost(*b, normalized[*b] - 1))
+                })
+                .unwrap();
+            normalized[byte] -= 1;
+            sum -= 1;
+        } else {
+            let byte = (0..256)
+                .filter(|byte| normalized[*byte] != 0)
+                .min_by(|a, b| {
+                    cost(*a, normalized[*a] + 1).total_cmp(&cost(*b, normalized[*b] + 1))
+                })
+                .unwrap();
+            normalized[byte] += 1;
+            sum += 1;
+        }
+    }
+
+    normalized
+}
+
+#[cfg(feature = "std")]
+fn ln(x: f64) -> f64 {
+    x.ln()
+}
+
+///`f64::ln` needs `std`, for positive normal `x`: ln(m * 2^e) = ln(m) + e * ln(2),
+///with ln(m) = 2 * atanh((m - 1) / (m + 1)) as a series for m in [1, 2)
+#[cfg(not(feature = "std"))]
+fn ln(x: f64) -> f64 {
+    let bits = x.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i64 - 1023;
+    let mantissa = f64::from_bits((bits & ((1 << 52) - 1)) | (1023 << 52));
+
+    let s = (mantissa - 1.0) / (mantissa + 1.0);
+    let mut power = s;
+    let mut sum = 0.0;
+    //s is at most 1/3, the terms are below f64 precision long before this
+    for k in (1..40).step_by(2) {
+        sum += power / k as f64;
+        power *= s * s;
+    }
+
+    2.0 * sum + exponent as f64 * core::f64::consts::LN_2
+}
+
+fn ceil_log2(value: u128) -> u32 {
+    match value {
+        0 | 1 => 0,
+        _ => u128::BITS - (value - 1).leading_zeros(),
+    }
+}
+
+fn floor_log2(value: u32) -> u32 {
+    u32::BITS - 1 - value.leading_zeros()
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct DecodeEntry {
+    byte: u8,
+    bits: u8,
+    base: u16,
+}
+
+///Encoding and decoding tables for normalized frequencies
+struct Table {
+    log: u32,
+    counts: [u32; 256],
+    ///first entry in `states` for every byte
+    starts: [u32; 256],
+    ///states in 2^log..2^(log + 1) to move to, grouped by byte
+    states: Vec<u16>,
+    decode: Vec<DecodeEntry>,
+}
+
+impl Table {
+    fn new(normalized: &[usize; 256]) -> io::Result<Self> {
+        let sum = normalized
+            .iter()
+            .try_fold(0usize, |sum, count| sum.checked_add(*count))
+            .filter(|sum| sum.is_power_of_two())
+            .ok_or_else(|| invalid_data("normalized frequencies don't add up to a power of two"))?;
+        let log = sum.trailing_zeros();
+
+        if !(MIN_TABLE_LOG..=MAX_TABLE_LOG).contains(&log) {
+            return Err(invalid_data(format!("invalid table log {log}")));
+        }
+
+        let size = 1 << log;
+        let mut counts = [0; 256];
+        let mut starts = [0; 256];
+        let mut start = 0;
+
+        for byte in 0..256 {
+            counts[byte] = normalized[byte] as u32;
+            starts[byte] = start;
+            start += counts[byte];
+        }
+
+        //spread every byte over the table, the step is odd so every position is hit once
+        let step = (size >> 1) + (size >> 3) + 3;
+        let mut spread = vec![0u8; size];
+        let mut position = 0;
+
+        for (byte, count) in counts.iter().enumerate() {
+            for _ in 0..*count {
+                spread[position] = byte as u8;
+                position = (position + step) & (size - 1);
+            }
+        }
+
+        let mut next = counts;
+        let mut states = vec![0; size];
+        let mut decode = vec![DecodeEntry::default(); size];
+
+        for (state, byte) in spread.iter().enumerate() {
+            let byte = *byte as usize;
+            let occurrence = next[byte];
+            next[byte] += 1;
+
+            let bits = log - floor_log2(occurrence);
+            states[(starts[byte] + occurrence - counts[byte]) as usize] = (size + state) as u16;
+            decode[state] = DecodeEntry {
+                byte: byte as u8,
+                bits: bits as u8,
+                base: ((occurrence << bits) - size as u32) as u16,
+            };
+        }
+
+        Ok(Table {
+            log,
+            counts,
+            starts,
+            states,
+            decode,
+        })
+    }
+}
+
+///Appends bits LSB first, the decoder reads them back from the end
+struct BitWriter<'a> {
+    bits: u64,
+    length: u32,
+    compressed_bytes: &'a mut Vec<u8>,
+}
+
+impl BitWriter<'_> {
+    fn write(&mut self, value: u32, length: u32) {
+        self.bits |= (value as u64) << self.length;
+        self.length += length;
+
+        while self.length >= 8 {
+            self.compressed_bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.length -= 8;
+        }
+    }
+
+    ///end with a set bit, so the decoder knows where the last byte ends
+    fn finish(mut self) {
+        self.write(1, 1);
+
+        if self.length != 0 {
+            self.compressed_bytes.push(self.bits as u8);
+        }
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    ///bits in front of this are still unread
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> io::Result<Self> {
+        match bytes.last() {
+            Some(last) if *last != 0 => Ok(BitReader {
+                bytes,
+                position: (bytes.len() - 1) * 8 + floor_log2(*last as u32) as usize,
+            }),
+            _ => Err(invalid_data("ans payload doesn't end with a set bit")),
+        }
+    }
+
+    fn read(&mut self, length: u32) -> io::Result<u32> {
+        self.position = self
+            .position
+            .checked_sub(length as usize)
+            .ok_or_else(|| invalid_data("ans payload ended"))?;
+
+        //at most 12 bits starting anywhere in a byte fit in 3 bytes
+        let index = self.position / 8;
+        let mut word = 0;
+        for i in 0..3 {
+            word |= (*self.bytes.get(index + i).unwrap_or(&0) as u32) << (8 * i);
+        }
+
+        Ok((word >> (self.position % 8)) & ((1 << length) - 1))
+    }
+}
+
+///append `uncompressed_bytes` tANS coded with `normalized` frequencies to `compressed_bytes`
+///
+///`normalized` comes from `normalize_frequencies` and has to contain every byte of
+///`uncompressed_bytes`. Nothing is written for fewer than 2 different bytes
+pub fn encode_ans(
+    uncompressed_bytes: &[u8],
+    normalized: &[usize; 256],
+    compressed_bytes: &mut Vec<u8>,
+) -> io::Result<()> {
+    if normalized.iter().filter(|count| **count != 0).count() < 2 {
+        return match uncompressed_bytes
+            .iter()
+            .find(|byte| normalized[**byte as usize] == 0)
+        {
+            Some(byte) => Err(invalid_data(format!("no count for byte {byte}"))),
+            None => Ok(()),
+        };
+    }
+
+    let table = Table::new(normalized)?;
+    let size = 1 << table.log;
+    let mut writer = BitWriter {
+        bits: 0,
+        length: 0,
+        compressed_bytes,
+    };
+
+    //the decoder goes forward, so encode backwards
+    let mut state: u32 = size;
+    for byte in uncompressed_bytes.iter().rev() {
+        let byte = *byte as usize;
+        let count = table.counts[byte];
+
+        if count == 0 {
+            return Err(invalid_data(format!("no count for byte {byte}")));
+        }
+
+        let mut bits = table.log - floor_log2(count);
+        if state >> bits < count {
+            bits -= 1;
+        }
+
+        writer.write(state & ((1 << bits) - 1), bits);
+        state = table.states[(table.starts[byte] + (state >> bits) - count) as usize] as u32;
+    }
+
+    writer.write(state - size, table.log);
+    writer.finish();
+
+    Ok(())
+}
+
+///decode `total` bytes from a payload written by `encode_ans` with the same `normalized` frequencies
+///
+///never panics or outputs more than `total` bytes, whatever `bytes` contains
+pub fn decode_ans(bytes: &[u8], normalized: &[usize; 256], total: usize) -> io::Result<Vec<u8>> {
+    let mut used = normalized
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count != 0);
+
+    match (used.next(), used.next()) {
+        (None, _) if total == 0 => return Ok(Vec::new()),
+        (None, _) => return Err(invalid_data("no counts for a non-empty payload")),
+        (Some((byte, _)), None) => return repeat_byte(byte as u8, total),
+        _ => {}
+    }
+
+    let table = Table::new(normalized)?;
+    let mut reader = BitReader::new(bytes)?;
+    let mut state = reader.read(table.log)? as usize;
+
+    //likely bytes can take less than a bit, don't trust `total` for more than the first few
+    let mut decompressed_bytes = Vec::with_capacity(total.min(bytes.len().saturating_mul(8)));
+
+    for _ in 0..total {
+        let entry = table.decode[state];
+        decompressed_bytes.push(entry.byte);
+        state = entry.base as usize + reader.read(entry.bits as u32)? as usize;
+    }
+
+    //the encoder started in the first state and used every bit
+    if state != 0 || reader.position != 0 {
+        return Err(invalid_data("ans payload doesn't end where expected"));
+    }
+
+    Ok(decompressed_bytes)
+}
+
+///append the compact table for `normalized` frequencies of `total` bytes
+///
+///`table_log` (u8) | `total` (u64) | (byte (u8) | count (u16)) for every used byte
+pub(crate) fn write_ans_table(normalized: &[usize; 256], total: usize, table: &mut Vec<u8>) {
+    let sum: usize = normalized.iter().sum();
+    let log = if sum == 0 {
+        MIN_TABLE_LOG
+    } else {
+        sum.trailing_zeros()
+    };
+
+    table.push(log as u8);
+    table.extend_from_slice(&(total as u64).to_be_bytes());
+
+    for (byte, count) in normalized.iter().enumerate() {
+        if *count != 0 {
+            table.push(byte as u8);
+            table.extend_from_slice(&(*count as u16).to_be_bytes());
+        }
+    }
+}
+
+///read a table written by `write_ans_table`, returns the normalized frequencies and the total
+///
+///only tables `write_ans_table` could have written are accepted
+pub(crate) fn read_ans_table(table: &[u8]) -> io::Result<([usize; 256], usize)> {
+    let entries = match table.len().checked_sub(TABLE_HEADER_SIZE) {
+        Some(size) if size % TABLE_ENTRY_SIZE == 0 && size / TABLE_ENTRY_SIZE <= 256 => {
+            size / TABLE_ENTRY_SIZE
+        }
+        _ => {
+            return Err(invalid_data(format!(
+                "invalid ans table size {}",
+                table.len()
+            )))
+        }
+    };
+
+    let log = table[0] as u32;
+    let mut total = [0; 8];
+    total.copy_from_slice(&table[1..TABLE_HEADER_SIZE]);
+    let total =
+        usize::try_from(u64::from_be_bytes(total)).map_err(|_| invalid_data("total too large"))?;
+
+    if !(MIN_TABLE_LOG..=MAX_TABLE_LOG).contains(&log) {
+        return Err(invalid_data(format!("invalid table log {log}")));
+    }
+
+    let mut normalized = [0; 256];
+    let mut previous = None;
+
+    for entry in table[TABLE_HEADER_SIZE..].chunks_exact(TABLE_ENTRY_SIZE) {
+        let byte = entry[0];
+        let count = u16::from_be_bytes([entry[1], entry[2]]) as usize;
+
+        //written in order, which also rules out repeated bytes
+        if count == 0 || previous.is_some_and(|previous| previous >= byte) {
+            return Err(invalid_data(format!(
+                "empty or out of order table entry for byte {byte}"
+            )));
+        }
+
+        normalized[byte as usize] = count;
+        previous = Some(byte);
+    }
+
+    let sum: usize = normalized.iter().sum();
+    let valid = match entries {
+        0 => total == 0 && log == MIN_TABLE_LOG,
+        //every used byte is there at least once
+        _ => sum == 1 << log && total >= entries,
+    };
+
+    if !valid {
+        return Err(invalid_data("ans table doesn't match its total"));
+    }
+
+    Ok((normalized, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_byte_frequencies;
+
+    fn round_trip(bytes: &[u8]) -> Vec<u8> {
+        let normalized = normalize_frequencies(&get_byte_frequencies(bytes));
+        let mut compressed = Vec::new();
+        encode_ans(bytes, &normalized, &mut compressed).unwrap();
+
+        let decompressed = decode_ans(&compressed, &normalized, bytes.len()).unwrap();
+        assert!(decompressed == bytes);
+
+        compressed
+    }
+
+    #[test]
+    fn normalize_frequencies_test() {
+        let normalized = normalize_frequencies(&get_byte_frequencies(b"AACD"));
+        assert_eq!(normalized[b'A' as usize], 16);
+        assert_eq!(normalized[b'C' as usize], 8);
+        assert_eq!(normalized.iter().sum::<usize>(), 1 << MIN_TABLE_LOG);
+
+        let mut frequencies = [1; 256];
+        frequencies[0] = usize::MAX / 2;
+        let normalized = normalize_frequencies(&frequencies);
+        assert_eq!(normalized.iter().sum::<usize>(), 1 << DEFAULT_TABLE_LOG);
+        assert!(normalized.iter().all(|count| *count != 0));
+
+        assert_eq!(normalize_frequencies(&[0; 256]), [0; 256]);
+    }
+
+    #[test]
+    fn round_trip_test() {
+        round_trip(b"AACD");
+        assert!(round_trip(&[]).is_empty());
+        assert!(round_trip(b"ZZZZ").is_empty());
+
+        let all: Vec<u8> = (0..=255).cycle().take(5000).collect();
+        round_trip(&all);
+
+        let bytes = std::fs::read("./test_files/135-0.txt").unwrap();
+        let compressed = round_trip(&bytes);
+        assert!(compressed.len() < bytes.len() * 6 / 10);
+    }
+
+    #[test]
+    fn skewed_test() {
+        let mut bytes = vec![b'a'; 10_000];
+        bytes.extend_from_slice(b"bc");
+
+        assert!(round_trip(&bytes).len() < 10_000 / 8 / 10);
+    }
+
+    #[test]
+    fn table_test() {
+        let normalized = normalize_frequencies(&get_byte_frequencies(b"AACD"));
+        let mut table = Vec::new();
+        write_ans_table(&normalized, 4, &mut table);
+
+        assert_eq!(table.len(), TABLE_HEADER_SIZE + 3 * TABLE_ENTRY_SIZE);
+        assert_eq!(read_ans_table(&table).unwrap(), (normalized, 4));
+
+        let mut empty = Vec::new();
+        write_ans_table(&[0; 256], 0, &mut empty);
+        assert_eq!(read_ans_table(&empty).unwrap(), ([0; 256], 0));
+    }
+
+    #[test]
+    fn read_invalid_table_test() {
+        let normalized = normalize_frequencies(&get_byte_frequencies(b"AACD"));
+        let mut valid = Vec::new();
+        write_ans_table(&normalized, 4, &mut valid);
+
+        let invalid = |change: fn(&mut Vec<u8>)| {
+            let mut table = valid.clone();
+            change(&mut table);
+            read_ans_table(&table).is_err()
+        };
+
+        assert!(invalid(|table| table[0] = 13));
+        assert!(invalid(|table| table[8] = 2));
+        assert!(invalid(|table| table[9] = b'D'));
+        assert!(invalid(|table| table[11] += 1));
+        assert!(invalid(|table| table.truncate(11)));
+        assert!(invalid(|table| table.clear()));
+    }
+
+    #[test]
+    fn decode_invalid_test() {
+        let bytes = b"ABRACADABRA";
+        let normalized = normalize_frequencies(&get_byte_frequencies(bytes));
+        let mut compressed = Vec::new();
+        encode_ans(bytes, &normalized, &mut compressed).unwrap();
+
+        assert!(decode_ans(&compressed, &normalized, bytes.len() + 1).is_err());
+        assert!(decode_ans(&compressed[1..], &normalized, bytes.len()).is_err());
+        assert!(decode_ans(&[], &normalized, bytes.len()).is_err());
+        assert!(decode_ans(&[0], &normalized, bytes.len()).is_err());
+
+        for byte in 0..=255 {
+            let _ = decode_ans(&[byte, 0x80], &normalized, 1000);
+        }
+
+        let err = encode_ans(b"Z", &normalized, &mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    #[cfg(not(feature = "std"))]
+    fn ln_test() {
+        for x in [0.25, 0.5, 1.0, 1.5, 2.0, 3.0, 1000.0, 4096.0 / 3.0] {
+            assert!((ln(x) - x.ln()).abs() < 1e-15, "{x}");
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::{CompressOptions, Decoder, DecompressOptions, Encoder, StreamCodec};
+
+//Adapters that move bytes between tokio readers or writers and an `Encoder` or `Decoder`,
+//so async code can compress without `spawn_blocking`. All of the work is done by the codec,
+//these only poll the inner reader or writer
+
+///how much is read from the inner reader at a time
+const READ_SIZE: usize = 8 * 1024;
+
+///Reads from `reader` and gives the output of the codec for it,
+///e.g. the compressed bytes of a file with `AsyncReader::encoder`
+#[derive(Debug)]
+pub struct AsyncReader<C, R> {
+    codec: C,
+    reader: R,
+    buffer: Box<[u8]>,
+    ///output of the codec, read up to `position`
+    output: Vec<u8>,
+    position: usize,
+    ///`reader` ended and the codec is finished
+    done: bool,
+}
+
+impl<R> AsyncReader<Encoder, R> {
+    pub fn encoder(reader: R, options: CompressOptions) -> Self {
+        Self::new(Encoder::new(options), reader)
+    }
+}
+
+impl<R> AsyncReader<Decoder, R> {
+    pub fn decoder(reader: R, options: DecompressOptions) -> Self {
+        Self::new(Decoder::new(options), reader)
+    }
+}
+
+impl<C, R> AsyncReader<C, R> {
+    pub fn new(codec: C, reader: R) -> Self {
+        AsyncReader {
+            codec,
+            reader,
+            buffer: vec![0; READ_SIZE].into_boxed_slice(),
+            output: Vec::new(),
+            position: 0,
+            done: false,
+        }
+    }
+
+    pub fn get_codec(&self) -> &C {
+        &self.codec
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<C: StreamCodec + Unpin, R: AsyncRead + Unpin> AsyncRead for AsyncReader<C, R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            if this.position < this.output.len() {
+                let count = buf.remaining().min(this.output.len() - this.position);
+                buf.put_slice(&this.output[this.position..this.position + count]);
+                this.position += count;
+
+                if this.position == this.output.len() {
+                    this.output.clear();
+                    this.position = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+
+            if this.done || buf.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut input = ReadBuf::new(&mut this.buffer);
+            ready!(Pin::new(&mut this.reader).poll_read(cx, &mut input))?;
+
+            match input.filled() {
+                [] => {
+                    this.done = true;
+                    this.codec.finish(&mut this.output)?;
+                }
+                bytes => this.codec.feed(bytes, &mut this.output)?,
+            }
+        }
+    }
+}
+
+///Takes input for the codec and writes its output to `writer`,
+///e.g. decompressed bytes to a file with `AsyncWriter::decoder`
+///
+///The codec is finished by `shutdown`, which has to be called for the output to be complete
+#[derive(Debug)]
+pub struct AsyncWriter<C, W> {
+    codec: C,
+    writer: W,
+    ///output of the codec, written up to `position`
+    output: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl<W> AsyncWriter<Encoder, W> {
+    pub fn encoder(writer: W, options: CompressOptions) -> Self {
+        Self::new(Encoder::new(options), writer)
+    }
+}
+
+impl<W> AsyncWriter<Decoder, W> {
+    pub fn decoder(writer: W, options: DecompressOptions) -> Self {
+        Self::new(Decoder::new(options), writer)
+    }
+}
+
+impl<C, W> AsyncWriter<C, W> {
+    pub fn new(codec: C, writer: W) -> Self {
+        AsyncWriter {
+            codec,
+            writer,
+            output: Vec::new(),
+            position: 0,
+            finished: false,
+        }
+    }
+
+    pub fn get_codec(&self) -> &C {
+        &self.codec
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<C, W: AsyncWrite + Unpin> AsyncWriter<C, W> {
+    ///write all of the pending output to `writer`
+    fn poll_output(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.position < self.output.len() {
+            let written =
+                ready!(Pin::new(&mut self.writer).poll_write(cx, &self.output[self.position..]))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.position += written;
+        }
+
+        self.output.clear();
+        self.position = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<C: StreamCodec + Unpin, W: AsyncWrite + Unpin> AsyncWrite for AsyncWriter<C, W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        //don't take more input while output is waiting
+        ready!(this.poll_output(cx))?;
+        this.codec.feed(buf, &mut this.output)?;
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_output(cx))?;
+        Pin::new(&mut this.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if !this.finished {
+            this.finished = true;
+            this.codec.finish(&mut this.output)?;
+        }
+
+        ready!(this.poll_output(cx))?;
+        Pin::new(&mut this.writer).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress_to_vec_with_options, Coder, LimitError};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    ///gives a few bytes at a time and is pending every other poll, like a socket
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        pending: bool,
+    }
+
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            self.pending = !self.pending;
+            if self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            let count = buf.remaining().min(self.bytes.len()).min(13);
+            buf.put_slice(&self.bytes[..count]);
+            self.bytes = &self.bytes[count..];
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn trickle(bytes: &[u8]) -> Trickle<'_> {
+        Trickle {
+            bytes,
+            pending: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn reader_test() {
+        let bytes = std::fs::read("./README.md").unwrap();
+        let options = CompressOptions::new().coder(Coder::RANGE).bwt(true);
+
+        let mut compressed = Vec::new();
+        AsyncReader::encoder(trickle(&bytes), options)
+            .read_to_end(&mut compressed)
+            .await
+            .unwrap();
+        assert!(compressed == compress_to_vec_with_options(&bytes, &options));
+
+        //small reads, the output is given out in pieces
+        let mut reader = AsyncReader::decoder(trickle(&compressed), DecompressOptions::new());
+        let mut decompressed = Vec::new();
+        let mut buffer = [0; 100];
+        loop {
+            let read = reader.read(&mut buffer).await.unwrap();
+            if read == 0 {
+                break;
+            }
+            decompressed.extend_from_slice(&buffer[..read]);
+        }
+        assert!(decompressed == bytes);
+        assert!(reader.get_codec().is_finished());
+    }
+
+    #[tokio::test]
+    async fn writer_test() {
+        let bytes = std::fs::read("./README.md").unwrap();
+
+        let mut writer = AsyncWriter::encoder(Vec::new(), CompressOptions::new());
+        for piece in bytes.chunks(1000) {
+            writer.write_all(piece).await.unwrap();
+        }
+        writer.shutdown().await.unwrap();
+        let compressed = writer.into_inner();
+        assert!(compressed == crate::compress_to_vec(&bytes));
+
+        let mut writer = AsyncWriter::decoder(Vec::new(), DecompressOptions::new());
+        tokio::io::copy(&mut trickle(&compressed), &mut writer)
+            .await
+            .unwrap();
+        writer.shutdown().await.unwrap();
+        assert!(writer.get_ref() == &bytes);
+
+        assert!(writer.write_all(b"more").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn errors_test() {
+        let compressed = crate::compress_to_vec(b"abcabcabc");
+        let options = DecompressOptions::new().max_output_size(5);
+
+        let err = AsyncReader::decoder(&compressed[..], options)
+            .read_to_end(&mut Vec::new())
+            .await
+            .unwrap_err();
+        assert!(LimitError::from_io(&err).is_some());
+
+        let mut writer = AsyncWriter::decoder(Vec::new(), DecompressOptions::new());
+        writer.write_all(&compressed[..5]).await.unwrap();
+        let err = writer.shutdown().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
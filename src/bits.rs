@@ -0,0 +1,122 @@
+use std::io;
+
+///Writes bits most significant first, like bzip2 and HPACK
+pub(crate) struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    pub(crate) fn new() -> Self {
+        BitWriter {
+            bytes: Vec::new(),
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    ///the low `count` bits of `value`, up to 32
+    pub(crate) fn write(&mut self, count: u32, value: u32) {
+        self.buffer = self.buffer << count | (value as u64 & ((1 << count) - 1));
+        self.count += count;
+
+        while self.count >= 8 {
+            self.count -= 8;
+            self.bytes.push((self.buffer >> self.count) as u8);
+        }
+    }
+
+    ///48 bits, like bzip2's magic numbers
+    pub(crate) fn write_magic(&mut self, magic: u64) {
+        self.write(24, (magic >> 24) as u32);
+        self.write(24, magic as u32);
+    }
+
+    ///bits left until the next whole byte
+    pub(crate) fn padding(&self) -> u32 {
+        (8 - self.count) % 8
+    }
+
+    ///pads the last byte with 0s
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.write(8 - self.count, 0);
+        }
+
+        self.bytes
+    }
+}
+
+///Reads bits most significant first
+pub(crate) struct BitReader<'a> {
+    pub(crate) bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, position: 0 }
+    }
+
+    pub(crate) fn bit(&mut self) -> io::Result<u32> {
+        let byte = self
+            .bytes
+            .get(self.position / 8)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "bit stream ended"))?;
+        let bit = byte >> (7 - self.position % 8) & 1;
+        self.position += 1;
+
+        Ok(bit as u32)
+    }
+
+    ///`count` bits, up to 32
+    pub(crate) fn read(&mut self, count: u32) -> io::Result<u32> {
+        (0..count).try_fold(0, |value, _| Ok(value << 1 | self.bit()?))
+    }
+
+    ///48 bits, like bzip2's magic numbers
+    pub(crate) fn read_magic(&mut self) -> io::Result<u64> {
+        Ok((self.read(24)? as u64) << 24 | self.read(24)? as u64)
+    }
+
+    ///streams end on whole bytes
+    pub(crate) fn align(&mut self) {
+        self.position = self.position.next_multiple_of(8);
+    }
+
+    pub(crate) fn is_at_end(&self) -> bool {
+        self.position >= self.bytes.len() * 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_test() {
+        let mut writer = BitWriter::new();
+        writer.write(3, 0b101);
+        writer.write(32, 0xdead_beef);
+        writer.write_magic(0x3141_5926_5359);
+        assert_eq!(writer.padding(), 5);
+
+        let bytes = writer.finish();
+        assert_eq!(bytes.len(), 11);
+        assert_eq!(bytes[0], 0b1011_1011);
+
+        let mut reader = BitReader::new(&bytes);
+        assert_eq!(reader.read(3).unwrap(), 0b101);
+        assert_eq!(reader.read(32).unwrap(), 0xdead_beef);
+        assert_eq!(reader.read_magic().unwrap(), 0x3141_5926_5359);
+        assert!(!reader.is_at_end());
+
+        reader.align();
+        assert!(reader.is_at_end());
+        assert_eq!(
+            reader.bit().unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
@@ -0,0 +1,477 @@
+use alloc::{format, vec, vec::Vec};
+
+use crate::{header::invalid_data, io};
+
+///bytes transformed at once, like bzip2 -9
+pub const BWT_BLOCK_SIZE: usize = 900_000;
+
+///zero runs are written in bijective base 2 with these two digits
+const RUN_A: u8 = 0;
+const RUN_B: u8 = 1;
+
+///MTF indices are shifted by one to make room for the run digits,
+///the two that don't fit follow this escape
+const ESCAPE: u8 = 255;
+
+///sizes in front of the stream and every block
+const SIZE_LENGTH: usize = 8;
+const BLOCK_HEADER_LENGTH: usize = 4 + 4;
+
+///suffix array of `bytes` with an implicit sentinel at the end that sorts first,
+///the sentinel's own suffix is the first entry
+///
+///built with SA-IS in O(n)
+pub fn suffix_array(bytes: &[u8]) -> Vec<usize> {
+    let symbols: Vec<usize> = bytes
+        .iter()
+        .map(|byte| *byte as usize + 1)
+        .chain([0])
+        .collect();
+
+    induced_sort(&symbols, 257)
+}
+
+///marks a slot of the suffix array that wasn't filled yet
+const EMPTY: usize = usize::MAX;
+
+///suffix array of `symbols`, which are below `alphabet_size` and end with a single 0
+fn induced_sort(symbols: &[usize], alphabet_size: usize) -> Vec<usize> {
+    let n = symbols.len();
+    if n == 1 {
+        return vec![0];
+    }
+
+    //a suffix is S type if it is smaller than the one after it, L type otherwise
+    let mut s_type = vec![true; n];
+    for i in (0..n - 1).rev() {
+        s_type[i] = symbols[i] < symbols[i + 1] || (symbols[i] == symbols[i + 1] && s_type[i + 1]);
+    }
+    //leftmost S type suffixes, the sentinel is always one
+    let is_lms = |i: usize| i > 0 && s_type[i] && !s_type[i - 1];
+
+    let mut bucket_sizes = vec![0; alphabet_size];
+    for symbol in symbols {
+        bucket_sizes[*symbol] += 1;
+    }
+    let bucket_tails = || {
+        let mut tails = bucket_sizes.clone();
+        for i in 1..alphabet_size {
+            tails[i] += tails[i - 1];
+        }
+        tails
+    };
+
+    //sort the LMS suffixes by their prefixes up to the next LMS suffix
+    let mut suffixes = vec![EMPTY; n];
+    let mut tails = bucket_tails();
+    for i in (0..n).filter(|i| is_lms(*i)) {
+        tails[symbols[i]] -= 1;
+        suffixes[tails[symbols[i]]] = i;
+    }
+    induce(symbols, &s_type, &bucket_sizes, &mut suffixes);
+
+    //name the LMS substrings by their rank, equal ones share a name
+    let mut names = vec![EMPTY; n];
+    let mut name_count = 0;
+    let mut previous: Option<usize> = None;
+
+    for i in suffixes.iter().copied().filter(|i| is_lms(*i)) {
+        let same = previous.is_some_and(|previous| {
+            (0..).find_map(|k| {
+                let (a, b) = (previous + k, i + k);
+                if symbols[a] != symbols[b] || s_type[a] != s_type[b] {
+                    Some(false)
+                } else if k > 0 && (is_lms(a) || is_lms(b)) {
+                    Some(is_lms(a) && is_lms(b))
+                } else {
+                    None
+                }
+            }) == Some(true)
+        });
+
+        if !same {
+            name_count += 1;
+        }
+        names[i] = name_count - 1;
+        previous = Some(i);
+    }
+
+    //sorting the string of names sorts the LMS suffixes
+    let lms: Vec<usize> = (0..n).filter(|i| is_lms(*i)).collect();
+    let reduced: Vec<usize> = lms.iter().map(|i| names[*i]).collect();
+
+    let reduced_suffixes = if name_count == reduced.len() {
+        let mut reduced_suffixes = vec![0; reduced.len()];
+        for (i, name) in reduced.iter().enumerate() {
+            reduced_suffixes[*name] = i;
+        }
+        reduced_suffixes
+    } else {
+        induced_sort(&reduced, name_count)
+    };
+
+    //every other suffix follows from the sorted LMS suffixes
+    suffixes.fill(EMPTY);
+    let mut tails = bucket_tails();
+    for i in reduced_suffixes.iter().rev() {
+        let suffix = lms[*i];
+        tails[symbols[suffix]] -= 1;
+        suffixes[tails[symbols[suffix]]] = suffix;
+    }
+    induce(symbols, &s_type, &bucket_sizes, &mut suffixes);
+
+    suffixes
+}
+
+///sort the L type suffixes from the LMS ones in `suffixes`, then the S type ones from those
+fn induce(symbols: &[usize], s_type: &[bool], bucket_sizes: &[usize], suffixes: &mut [usize]) {
+    let mut heads = vec![0; bucket_sizes.len()];
+    for i in 1..bucket_sizes.len() {
+        heads[i] = heads[i - 1] + bucket_sizes[i - 1];
+    }
+    let mut tails: Vec<usize> = heads
+        .iter()
+        .zip(bucket_sizes)
+        .map(|(head, size)| head + size)
+        .collect();
+
+    for i in 0..suffixes.len() {
+        let suffix = suffixes[i];
+        if suffix != EMPTY && suffix > 0 && !s_type[suffix - 1] {
+            let symbol = symbols[suffix - 1];
+            suffixes[heads[symbol]] = suffix - 1;
+            heads[symbol] += 1;
+        }
+    }
+
+    for i in (0..suffixes.len()).rev() {
+        let suffix = suffixes[i];
+        if suffix != EMPTY && suffix > 0 && s_type[suffix - 1] {
+            let symbol = symbols[suffix - 1];
+            tails[symbol] -= 1;
+            suffixes[tails[symbol]] = suffix - 1;
+        }
+    }
+}
+
+///last column of the sorted rotations of `block` + sentinel, without the sentinel,
+///and the row the sentinel was in
+pub fn burrows_wheeler(block: &[u8]) -> (Vec<u8>, usize) {
+    let mut primary = 0;
+    let mut last_column = Vec::with_capacity(block.len());
+
+    for (row, suffix) in suffix_array(block).into_iter().enumerate() {
+        match suffix {
+            0 => primary = row,
+            _ => last_column.push(block[suffix - 1]),
+        }
+    }
+
+    (last_column, primary)
+}
+
+///undo `burrows_wheeler`
+pub fn inverse_burrows_wheeler(last_column: &[u8], primary: usize) -> io::Result<Vec<u8>> {
+    let n = last_column.len() + 1;
+
+    //the first row starts with the sentinel, so it can't end with it unless the block is empty
+    if primary >= n || (primary == 0 && n > 1) {
+        return Err(invalid_data(format!("invalid primary index {primary}")));
+    }
+
+    //row of every symbol in the first column, the sentinel sorts first
+    let mut starts = [0; 256];
+    let mut start = 1;
+    let mut counts = [0; 256];
+    for byte in last_column {
+        counts[*byte as usize] += 1;
+    }
+    for byte in 0..256 {
+        starts[byte] = start;
+        start += counts[byte];
+    }
+
+    //row each row's rotation continues in, one to the left
+    let mut next = vec![0; n];
+    for row in 0..n {
+        if row == primary {
+            continue;
+        }
+
+        let byte = last_column[if row < primary { row } else { row - 1 }] as usize;
+        next[row] = starts[byte];
+        starts[byte] += 1;
+    }
+
+    let mut block = vec![0; n - 1];
+    let mut row = 0;
+
+    for i in (0..n - 1).rev() {
+        if row == primary {
+            return Err(invalid_data("rotation ended early"));
+        }
+
+        block[i] = last_column[if row < primary { row } else { row - 1 }];
+        row = next[row];
+    }
+
+    Ok(block)
+}
+
+///index of every byte in a list of recently seen bytes, which moves it to the front
+pub fn move_to_front(bytes: &[u8]) -> Vec<u8> {
+    let mut list: [u8; 256] = core::array::from_fn(|i| i as u8);
+
+    bytes
+        .iter()
+        .map(|byte| {
+            let index = list.iter().position(|item| item == byte).unwrap();
+            list.copy_within(0..index, 1);
+            list[0] = *byte;
+            index as u8
+        })
+        .collect()
+}
+
+///undo `move_to_front`
+pub fn inverse_move_to_front(indices: &[u8]) -> Vec<u8> {
+    let mut list: [u8; 256] = core::array::from_fn(|i| i as u8);
+
+    indices
+        .iter()
+        .map(|index| {
+            let byte = list[*index as usize];
+            list.copy_within(0..*index as usize, 1);
+            list[0] = byte;
+            byte
+        })
+        .collect()
+}
+
+///append `indices` to `encoded` with runs of 0 written as digits `RUN_A` (1) and `RUN_B` (2)
+///of their length in bijective base 2, least significant first, like bzip2
+pub fn encode_zero_runs(indices: &[u8], encoded: &mut Vec<u8>) {
+    let mut run = 0usize;
+
+    for index in indices.iter().chain([&1]) {
+        if *index == 0 {
+            run += 1;
+            continue;
+        }
+
+        encoded.extend(run_digits(run));
+        run = 0;
+
+        encoded.push(match *index {
+            index if index < ESCAPE - 1 => index + 1,
+            _ => ESCAPE,
+        });
+        if *index >= ESCAPE - 1 {
+            encoded.push(*index - (ESCAPE - 1));
+        }
+    }
+
+    //the chained 1 only flushed the last run
+    encoded.pop();
+}
+
+///digits `RUN_A` (1) and `RUN_B` (2) of `run` in bijective base 2, least significant first
+pub(crate) fn run_digits(mut run: usize) -> impl Iterator<Item = u8> {
+    core::iter::from_fn(move || {
+        (run > 0).then(|| {
+            let digit = if run % 2 == 1 { RUN_A } else { RUN_B };
+            run = (run - 1 - digit as usize) / 2;
+            digit
+        })
+    })
+}
+
+///decode `length` indices written by `encode_zero_runs` from the start of `encoded`,
+///returns them and how many bytes were read
+pub fn decode_zero_runs(encoded: &[u8], length: usize) -> io::Result<(Vec<u8>, usize)> {
+    let mut indices = Vec::with_capacity(length.min(encoded.len().saturating_mul(8)));
+    let mut bytes = encoded.iter();
+    let mut run = 0usize;
+    let mut weight = 1usize;
+
+    let ended = || io::Error::new(io::ErrorKind::UnexpectedEof, "zero runs ended");
+
+    while indices.len() + run < length {
+        let symbol = *bytes.next().ok_or_else(ended)?;
+
+        if symbol == RUN_A || symbol == RUN_B {
+            run = weight
+                .checked_mul(symbol as usize + 1)
+                .and_then(|digit| run.checked_add(digit))
+                .filter(|run| indices.len() + run <= length)
+                .ok_or_else(|| invalid_data("zero run too long"))?;
+            weight = weight.saturating_mul(2);
+            continue;
+        }
+
+        indices.resize(indices.len() + run, 0);
+        run = 0;
+        weight = 1;
+
+        indices.push(match symbol {
+            ESCAPE => match bytes.next().ok_or_else(ended)? {
+                escaped @ (0 | 1) => ESCAPE - 1 + escaped,
+                escaped => return Err(invalid_data(format!("invalid escaped index {escaped}"))),
+            },
+            symbol => symbol - 1,
+        });
+    }
+
+    indices.resize(length, 0);
+
+    Ok((indices, encoded.len() - bytes.as_slice().len()))
+}
+
+///bzip2 style preprocessing: every block goes through BWT, MTF and zero-run coding
+///
+///`size` (u64) | `size / BWT_BLOCK_SIZE` blocks of `length` (u32) | `primary` (u32) | zero-run coded MTF indices.
+///The result compresses much better than `bytes` with any of the `CODERS`
+pub fn bwt_transform(bytes: &[u8]) -> Vec<u8> {
+    let mut transformed = Vec::with_capacity(bytes.len() / 2);
+    transformed.extend_from_slice(&(bytes.len() as u64).to_be_bytes());
+
+    for block in bytes.chunks(BWT_BLOCK_SIZE) {
+        let (last_column, primary) = burrows_wheeler(block);
+
+        transformed.extend_from_slice(&(block.len() as u32).to_be_bytes());
+        transformed.extend_from_slice(&(primary as u32).to_be_bytes());
+        encode_zero_runs(&move_to_front(&last_column), &mut transformed);
+    }
+
+    transformed
+}
+
+///size of the bytes `transformed` stands for, from its first bytes
+pub fn bwt_untransformed_size(transformed: &[u8]) -> io::Result<usize> {
+    let size = transformed
+        .get(..SIZE_LENGTH)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "bwt size missing"))?;
+
+    usize::try_from(u64::from_be_bytes(size.try_into().unwrap()))
+        .map_err(|_| invalid_data("bwt size too large"))
+}
+
+///undo `bwt_transform`
+///
+///never panics or outputs more than the size in front of `transformed`, whatever it contains
+pub fn bwt_untransform(transformed: &[u8]) -> io::Result<Vec<u8>> {
+    let size = bwt_untransformed_size(transformed)?;
+    let mut rest = &transformed[SIZE_LENGTH..];
+    let mut bytes = Vec::with_capacity(size.min(BWT_BLOCK_SIZE));
+
+    while bytes.len() < size {
+        let block_header = rest
+            .get(..BLOCK_HEADER_LENGTH)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "bwt block missing"))?;
+        let length = u32::from_be_bytes(block_header[..4].try_into().unwrap()) as usize;
+        let primary = u32::from_be_bytes(block_header[4..].try_into().unwrap()) as usize;
+
+        //every block but the last is full
+        if length != BWT_BLOCK_SIZE.min(size - bytes.len()) {
+            return Err(invalid_data(format!("invalid bwt block length {length}")));
+        }
+
+        let (indices, read) = decode_zero_runs(&rest[BLOCK_HEADER_LENGTH..], length)?;
+        rest = &rest[BLOCK_HEADER_LENGTH + read..];
+
+        let last_column = inverse_move_to_front(&indices);
+        bytes.extend(inverse_burrows_wheeler(&last_column, primary)?);
+    }
+
+    if !rest.is_empty() {
+        return Err(invalid_data("bytes after the last bwt block"));
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suffix_array_test() {
+        //sentinel, a, ana, anana, banana, na, nana
+        assert_eq!(suffix_array(b"banana"), [6, 5, 3, 1, 0, 4, 2]);
+        assert_eq!(suffix_array(b""), [0]);
+        assert_eq!(suffix_array(b"aaaa"), [4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn burrows_wheeler_test() {
+        let (last_column, primary) = burrows_wheeler(b"banana");
+
+        assert_eq!(last_column, b"annbaa");
+        assert_eq!(primary, 4);
+        assert_eq!(
+            inverse_burrows_wheeler(&last_column, primary).unwrap(),
+            b"banana"
+        );
+
+        assert_eq!(burrows_wheeler(b""), (vec![], 0));
+        assert!(inverse_burrows_wheeler(b"", 0).unwrap().is_empty());
+        assert!(inverse_burrows_wheeler(b"annbaa", 0).is_err());
+        assert!(inverse_burrows_wheeler(b"annbaa", 7).is_err());
+    }
+
+    #[test]
+    fn move_to_front_test() {
+        let indices = move_to_front(b"bananaaa");
+
+        assert_eq!(indices, [98, 98, 110, 1, 1, 1, 0, 0]);
+        assert_eq!(inverse_move_to_front(&indices), b"bananaaa");
+    }
+
+    #[test]
+    fn zero_runs_test() {
+        let indices = [0, 0, 0, 5, 0, 0, 254, 255, 0, 0, 0, 0, 0, 0, 0];
+        let mut encoded = vec![];
+        encode_zero_runs(&indices, &mut encoded);
+
+        //3 = 1 + 2 * 1, 2 = 2, 7 = 1 + 2 * 1 + 4 * 1
+        assert_eq!(
+            encoded,
+            [RUN_A, RUN_A, 6, RUN_B, ESCAPE, 0, ESCAPE, 1, RUN_A, RUN_A, RUN_A]
+        );
+        assert_eq!(
+            decode_zero_runs(&encoded, indices.len()).unwrap(),
+            (indices.to_vec(), encoded.len())
+        );
+
+        assert!(decode_zero_runs(&encoded, indices.len() - 1).is_err());
+        assert!(decode_zero_runs(&encoded[..5], indices.len()).is_err());
+        assert!(decode_zero_runs(&[ESCAPE, 2], 1).is_err());
+    }
+
+    #[test]
+    fn bwt_transform_test() {
+        for bytes in [&b""[..], b"a", b"banana", &[0; 1000], &[255; 1000]] {
+            let transformed = bwt_transform(bytes);
+
+            assert_eq!(bwt_untransformed_size(&transformed).unwrap(), bytes.len());
+            assert_eq!(bwt_untransform(&transformed).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn bwt_transform_blocks_test() {
+        let bytes = std::fs::read("./test_files/135-0.txt").unwrap();
+        let transformed = bwt_transform(&bytes);
+
+        assert!(bytes.len() > 3 * BWT_BLOCK_SIZE);
+        assert!(bwt_untransform(&transformed).unwrap() == bytes);
+
+        let mut truncated = transformed.clone();
+        truncated.pop();
+        assert!(bwt_untransform(&truncated).is_err());
+
+        let mut longer = transformed;
+        longer.push(0);
+        assert!(bwt_untransform(&longer).is_err());
+    }
+}
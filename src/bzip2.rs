@@ -0,0 +1,798 @@
+use std::io;
+
+use crate::{
+    bits::{BitReader, BitWriter},
+    bwt::{move_to_front, run_digits, suffix_array},
+    header::invalid_data,
+    huff::code_lengths,
+    DecompressOptions,
+};
+
+///every stream starts with this and the block size level, '1' to '9'
+const STREAM_MAGIC: &[u8; 3] = b"BZh";
+
+///48 bit magic numbers in front of every block and the end of a stream, digits of pi and sqrt(pi)
+const BLOCK_MAGIC: u64 = 0x3141_5926_5359;
+const END_MAGIC: u64 = 0x1772_4538_5090;
+
+///symbols coded with the same table before a new one can be selected
+const GROUP_SIZE: usize = 50;
+
+const MIN_TABLES: usize = 2;
+const MAX_TABLES: usize = 6;
+
+///longest code bzip2 decoders accept, and the longest it writes
+const MAX_CODE_LENGTH: u32 = 20;
+const MAX_WRITTEN_CODE_LENGTH: u16 = 17;
+
+///zero runs are written like `encode_zero_runs` does, MTF indices above them
+const RUN_B: u16 = 1;
+
+///rounds of choosing tables for groups and rebuilding the tables from their groups
+const TABLE_ITERATIONS: usize = 4;
+
+///CRC-32 with the polynomial bzip2 uses, most significant bit first
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = match crc & 0x8000_0000 {
+                0 => crc << 1,
+                _ => crc << 1 ^ 0x04c1_1db7,
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+///checksum of the uncompressed bytes of a block, stored in front of it
+pub fn bzip2_crc(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(u32::MAX, |crc, byte| {
+        crc << 8 ^ CRC_TABLE[((crc >> 24) as u8 ^ byte) as usize]
+    })
+}
+
+///Canonical code of a table, shorter codes first and on equal lengths lower symbols first
+struct Table {
+    ///code length of every symbol
+    lengths: Vec<u16>,
+    ///symbols in code order
+    symbols: Vec<u16>,
+    ///number of codes of every length
+    counts: [u32; MAX_CODE_LENGTH as usize + 1],
+}
+
+impl Table {
+    fn new(lengths: Vec<u16>) -> Self {
+        let mut counts = [0; MAX_CODE_LENGTH as usize + 1];
+        for length in &lengths {
+            counts[*length as usize] += 1;
+        }
+
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16).collect();
+        symbols.sort_by_key(|symbol| lengths[*symbol as usize]);
+
+        Table {
+            lengths,
+            symbols,
+            counts,
+        }
+    }
+
+    ///code of every symbol
+    fn codes(&self) -> Vec<u32> {
+        let mut codes = vec![0; self.lengths.len()];
+        let mut code = 0;
+
+        for length in 1..=MAX_CODE_LENGTH as u16 {
+            for symbol in self.symbols.iter() {
+                if self.lengths[*symbol as usize] == length {
+                    codes[*symbol as usize] = code;
+                    code += 1;
+                }
+            }
+            code <<= 1;
+        }
+
+        codes
+    }
+
+    ///never panics, also for lengths that aren't a valid code
+    fn decode(&self, reader: &mut BitReader) -> io::Result<u16> {
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+
+        for count in &self.counts[1..] {
+            code = code << 1 | reader.bit()?;
+
+            if code >= first && code - first < *count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+        }
+
+        Err(invalid_data("invalid bzip2 huffman code"))
+    }
+}
+
+///compress `bytes` into a bzip2 stream that `bzip2 -d` can read,
+///in blocks of `level` * 100000 bytes like `bzip2 -<level>`
+///
+///panics if `level` isn't 1 to 9
+pub fn compress_bzip2(bytes: &[u8], level: u8) -> Vec<u8> {
+    assert!((1..=9).contains(&level), "bzip2 level {level} isn't 1 to 9");
+
+    let mut writer = BitWriter::new();
+    for byte in STREAM_MAGIC.iter().chain([&(b'0' + level)]) {
+        writer.write(8, *byte as u32);
+    }
+
+    //bzip2 keeps some room for runs in the last bytes
+    let block_size = level as usize * 100_000 - 19;
+    let mut combined_crc = 0u32;
+    let mut rest = bytes;
+
+    while !rest.is_empty() {
+        let (block, used) = run_length_encode(rest, block_size);
+        let crc = bzip2_crc(&rest[..used]);
+
+        combined_crc = combined_crc.rotate_left(1) ^ crc;
+        write_block(&mut writer, &block, crc);
+        rest = &rest[used..];
+    }
+
+    writer.write_magic(END_MAGIC);
+    writer.write(32, combined_crc);
+
+    writer.finish()
+}
+
+///runs of 4 to 255 equal bytes from the start of `bytes` as 4 bytes and the number of
+///the rest, until the block is full. Returns the block and how many bytes are in it
+fn run_length_encode(bytes: &[u8], block_size: usize) -> (Vec<u8>, usize) {
+    let mut block = Vec::with_capacity(block_size.min(bytes.len()));
+    let mut used = 0;
+
+    while used < bytes.len() {
+        let byte = bytes[used];
+        let run = bytes[used..]
+            .iter()
+            .take(255)
+            .take_while(|other| **other == byte)
+            .count();
+
+        if block.len() + run.min(5) > block_size {
+            break;
+        }
+
+        block.resize(block.len() + run.min(4), byte);
+        if run >= 4 {
+            block.push((run - 4) as u8);
+        }
+        used += run;
+    }
+
+    (block, used)
+}
+
+///append `block` after BWT, MTF, zero-run coding and huffman coding with up to 6 tables
+fn write_block(writer: &mut BitWriter, block: &[u8], crc: u32) {
+    let n = block.len();
+
+    //rotations of `block` are sorted like the suffixes of it twice
+    let mut origin = 0;
+    let mut last_column = Vec::with_capacity(n);
+    for start in suffix_array(&[block, block].concat()) {
+        if start < n {
+            if start == 0 {
+                origin = last_column.len();
+            }
+            last_column.push(block[(start + n - 1) % n]);
+        }
+    }
+
+    //MTF over the bytes that are used only
+    let mut in_use = [false; 256];
+    for byte in block {
+        in_use[*byte as usize] = true;
+    }
+    let mut ranks = [0; 256];
+    let mut used_count = 0;
+    for byte in 0..256 {
+        if in_use[byte] {
+            ranks[byte] = used_count as u8;
+            used_count += 1;
+        }
+    }
+    let ranked: Vec<u8> = last_column
+        .iter()
+        .map(|byte| ranks[*byte as usize])
+        .collect();
+
+    let end_of_block = used_count as u16 + 1;
+    let mut symbols = Vec::with_capacity(n + 1);
+    let mut run = 0;
+    for index in move_to_front(&ranked) {
+        if index == 0 {
+            run += 1;
+            continue;
+        }
+
+        symbols.extend(run_digits(run).map(u16::from));
+        run = 0;
+        symbols.push(index as u16 + 1);
+    }
+    symbols.extend(run_digits(run).map(u16::from));
+    symbols.push(end_of_block);
+
+    let (tables, selectors) = choose_tables(&symbols, used_count + 2);
+
+    writer.write_magic(BLOCK_MAGIC);
+    writer.write(32, crc);
+    //not randomised
+    writer.write(1, 0);
+    writer.write(24, origin as u32);
+
+    //which of the 16 ranges of 16 bytes are used, then the used bytes in those
+    let used_ranges = (0..16).filter(|range| in_use[range * 16..range * 16 + 16].contains(&true));
+    writer.write(
+        16,
+        used_ranges
+            .clone()
+            .fold(0, |bits, range| bits | 0x8000 >> range),
+    );
+    for range in used_ranges {
+        let bits = (0..16)
+            .filter(|byte| in_use[range * 16 + byte])
+            .fold(0, |bits, byte| bits | 0x8000 >> byte);
+        writer.write(16, bits);
+    }
+
+    writer.write(3, tables.len() as u32);
+    writer.write(15, selectors.len() as u32);
+
+    //selectors are MTF coded, every index in unary
+    let mut order: Vec<usize> = (0..tables.len()).collect();
+    for selector in &selectors {
+        let index = order.iter().position(|table| table == selector).unwrap();
+        order[..=index].rotate_right(1);
+
+        for _ in 0..index {
+            writer.write(1, 1);
+        }
+        writer.write(1, 0);
+    }
+
+    //code lengths as changes of the previous one, 10 is +1 and 11 is -1
+    for table in &tables {
+        let mut current = table.lengths[0];
+        writer.write(5, current as u32);
+
+        for length in &table.lengths {
+            while current < *length {
+                writer.write(2, 0b10);
+                current += 1;
+            }
+            while current > *length {
+                writer.write(2, 0b11);
+                current -= 1;
+            }
+            writer.write(1, 0);
+        }
+    }
+
+    let codes: Vec<Vec<u32>> = tables.iter().map(Table::codes).collect();
+    for (group, selector) in symbols.chunks(GROUP_SIZE).zip(selectors) {
+        for symbol in group {
+            let symbol = *symbol as usize;
+            writer.write(
+                tables[selector].lengths[symbol] as u32,
+                codes[selector][symbol],
+            );
+        }
+    }
+}
+
+///tables and the table for every group of `symbols`, like bzip2 chooses them:
+///start from tables for ranges of symbols of about equal frequency, then alternate between
+///taking the cheapest table for every group and rebuilding every table from its groups
+fn choose_tables(symbols: &[u16], alphabet_size: usize) -> (Vec<Table>, Vec<usize>) {
+    let table_count = match symbols.len() {
+        0..200 => MIN_TABLES,
+        200..600 => 3,
+        600..1200 => 4,
+        1200..2400 => 5,
+        _ => MAX_TABLES,
+    };
+
+    let mut frequencies = vec![0; alphabet_size];
+    for symbol in symbols {
+        frequencies[*symbol as usize] += 1;
+    }
+
+    //the symbols in a range are cheap in its table, all others expensive
+    let mut lengths = vec![vec![15u16; alphabet_size]; table_count];
+    let mut remaining = symbols.len();
+    let mut start = 0;
+    for part in (1..=table_count).rev() {
+        let target = remaining / part;
+        let mut end = start;
+        let mut sum = 0;
+
+        while sum < target && end < alphabet_size {
+            sum += frequencies[end];
+            end += 1;
+        }
+        //alternate between rounding down and up
+        if end > start + 1 && part != table_count && part != 1 && (table_count - part) % 2 == 1 {
+            end -= 1;
+            sum -= frequencies[end];
+        }
+
+        lengths[part - 1][start..end].fill(0);
+        start = end;
+        remaining -= sum;
+    }
+
+    let mut selectors = Vec::with_capacity(symbols.len().div_ceil(GROUP_SIZE));
+
+    for _ in 0..TABLE_ITERATIONS {
+        let mut table_frequencies = vec![vec![0; alphabet_size]; table_count];
+        selectors.clear();
+
+        for group in symbols.chunks(GROUP_SIZE) {
+            let cost = |table: &usize| -> usize {
+                group
+                    .iter()
+                    .map(|symbol| lengths[*table][*symbol as usize] as usize)
+                    .sum()
+            };
+            let best = (0..table_count).min_by_key(cost).unwrap();
+
+            for symbol in group {
+                table_frequencies[best][*symbol as usize] += 1;
+            }
+            selectors.push(best);
+        }
+
+        lengths = table_frequencies
+            .iter()
+            .map(|frequencies| limited_code_lengths(frequencies))
+            .collect();
+    }
+
+    (lengths.into_iter().map(Table::new).collect(), selectors)
+}
+
+///code lengths where every symbol has a code and none is longer than `MAX_WRITTEN_CODE_LENGTH`,
+///flattening the frequencies until the tree is shallow enough
+fn limited_code_lengths(frequencies: &[usize]) -> Vec<u16> {
+    let mut weights: Vec<usize> = frequencies.iter().map(|freq| (*freq).max(1)).collect();
+
+    loop {
+        let lengths = code_lengths(&weights);
+        if lengths
+            .iter()
+            .all(|length| *length <= MAX_WRITTEN_CODE_LENGTH)
+        {
+            return lengths;
+        }
+
+        for weight in &mut weights {
+            *weight = 1 + *weight / 2;
+        }
+    }
+}
+
+///decompress every bzip2 stream in `bytes`, one after another
+pub fn decompress_bzip2(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    decompress_bzip2_with_options(bytes, &DecompressOptions::new())
+}
+
+///like `decompress_bzip2`, enforcing the limits in `options`
+///
+///bzip2 doesn't store the output size, so they are checked before every block is written out
+pub fn decompress_bzip2_with_options(
+    bytes: &[u8],
+    options: &DecompressOptions,
+) -> io::Result<Vec<u8>> {
+    let mut reader = BitReader::new(bytes);
+    let mut decompressed_bytes = Vec::new();
+
+    //files can hold several streams, e.g. from `cat` or pbzip2
+    loop {
+        read_stream(&mut reader, options, &mut decompressed_bytes)?;
+
+        if reader.is_at_end() {
+            return Ok(decompressed_bytes);
+        }
+    }
+}
+
+fn read_stream(
+    reader: &mut BitReader,
+    options: &DecompressOptions,
+    decompressed_bytes: &mut Vec<u8>,
+) -> io::Result<()> {
+    for byte in STREAM_MAGIC {
+        if reader.read(8)? != *byte as u32 {
+            return Err(invalid_data("not a bzip2 stream"));
+        }
+    }
+
+    let level = reader.read(8)?;
+    if !(b'1' as u32..=b'9' as u32).contains(&level) {
+        return Err(invalid_data(format!(
+            "invalid bzip2 block size level {level}"
+        )));
+    }
+    let block_size = (level - b'0' as u32) as usize * 100_000;
+
+    let mut combined_crc = 0u32;
+
+    loop {
+        match reader.read_magic()? {
+            BLOCK_MAGIC => {
+                let crc = reader.read(32)?;
+                let block = read_block(reader, block_size)?;
+
+                let start = decompressed_bytes.len();
+                let size = run_length_decode(&block, |_, _| {});
+                options.check(start.saturating_add(size), reader.bytes.len())?;
+
+                run_length_decode(&block, |byte, count| {
+                    decompressed_bytes.resize(decompressed_bytes.len() + count, byte)
+                });
+
+                if bzip2_crc(&decompressed_bytes[start..]) != crc {
+                    return Err(invalid_data("bzip2 block crc doesn't match"));
+                }
+                combined_crc = combined_crc.rotate_left(1) ^ crc;
+            }
+            END_MAGIC => {
+                if reader.read(32)? != combined_crc {
+                    return Err(invalid_data("bzip2 stream crc doesn't match"));
+                }
+                reader.align();
+
+                return Ok(());
+            }
+            magic => return Err(invalid_data(format!("invalid bzip2 magic {magic:#014x}"))),
+        }
+    }
+}
+
+///read a block written by `write_block` up to the run-length decoding, which is left to the caller
+fn read_block(reader: &mut BitReader, block_size: usize) -> io::Result<Vec<u8>> {
+    if reader.read(1)? != 0 {
+        return Err(invalid_data("randomised bzip2 blocks aren't supported"));
+    }
+    let origin = reader.read(24)? as usize;
+
+    let used_ranges = reader.read(16)?;
+    let mut used = Vec::new();
+    for range in 0..16 {
+        if used_ranges & 0x8000 >> range != 0 {
+            let bits = reader.read(16)?;
+            used.extend(
+                (0..16)
+                    .filter(|byte| bits & 0x8000 >> byte != 0)
+                    .map(|byte| (range * 16 + byte) as u8),
+            );
+        }
+    }
+    if used.is_empty() {
+        return Err(invalid_data("bzip2 block uses no bytes"));
+    }
+    let alphabet_size = used.len() + 2;
+
+    let table_count = reader.read(3)? as usize;
+    if !(MIN_TABLES..=MAX_TABLES).contains(&table_count) {
+        return Err(invalid_data(format!(
+            "invalid bzip2 table count {table_count}"
+        )));
+    }
+    let selector_count = reader.read(15)? as usize;
+    if selector_count == 0 {
+        return Err(invalid_data("bzip2 block has no selectors"));
+    }
+
+    let mut order: Vec<usize> = (0..table_count).collect();
+    let mut selectors = Vec::with_capacity(selector_count);
+    for _ in 0..selector_count {
+        let mut index = 0;
+        while reader.bit()? == 1 {
+            index += 1;
+            if index == table_count {
+                return Err(invalid_data("invalid bzip2 selector"));
+            }
+        }
+
+        order[..=index].rotate_right(1);
+        selectors.push(order[0]);
+    }
+
+    let mut tables = Vec::with_capacity(table_count);
+    for _ in 0..table_count {
+        let mut lengths = Vec::with_capacity(alphabet_size);
+        let mut current = reader.read(5)?;
+
+        for _ in 0..alphabet_size {
+            loop {
+                if !(1..=MAX_CODE_LENGTH).contains(&current) {
+                    return Err(invalid_data(format!("invalid bzip2 code length {current}")));
+                }
+                if reader.bit()? == 0 {
+                    break;
+                }
+                match reader.bit()? {
+                    0 => current += 1,
+                    _ => current -= 1,
+                }
+            }
+            lengths.push(current as u16);
+        }
+
+        tables.push(Table::new(lengths));
+    }
+
+    //huffman, zero runs and MTF undone at once
+    let end_of_block = alphabet_size as u16 - 1;
+    let too_large = || invalid_data("bzip2 block larger than its level allows");
+    let mut list = used;
+    let mut last_column = Vec::new();
+    let mut run = 0;
+    let mut weight = 1;
+
+    for symbol_count in 0.. {
+        let selector = selectors
+            .get(symbol_count / GROUP_SIZE)
+            .ok_or_else(|| invalid_data("bzip2 block ran out of selectors"))?;
+        let symbol = tables[*selector].decode(reader)?;
+
+        if symbol <= RUN_B {
+            run += weight * (symbol as usize + 1);
+            weight *= 2;
+            if last_column.len() + run > block_size {
+                return Err(too_large());
+            }
+            continue;
+        }
+
+        last_column.resize(last_column.len() + run, list[0]);
+        run = 0;
+        weight = 1;
+
+        if symbol == end_of_block {
+            break;
+        }
+        if last_column.len() == block_size {
+            return Err(too_large());
+        }
+
+        let index = symbol as usize - 1;
+        let byte = list[index];
+        list.copy_within(0..index, 1);
+        list[0] = byte;
+        last_column.push(byte);
+    }
+
+    if origin >= last_column.len() {
+        return Err(invalid_data(format!("invalid bzip2 origin {origin}")));
+    }
+
+    //the rotation that continues every row, rows of the same byte keep their order
+    let mut starts = [0; 256];
+    for byte in &last_column {
+        starts[*byte as usize] += 1;
+    }
+    let mut start = 0;
+    for count in &mut starts {
+        (*count, start) = (start, start + *count);
+    }
+
+    let mut next = vec![0; last_column.len()];
+    for (row, byte) in last_column.iter().enumerate() {
+        next[starts[*byte as usize]] = row;
+        starts[*byte as usize] += 1;
+    }
+
+    let mut row = next[origin];
+    let mut block = Vec::with_capacity(last_column.len());
+    for _ in 0..last_column.len() {
+        block.push(last_column[row]);
+        row = next[row];
+    }
+
+    Ok(block)
+}
+
+///undo `run_length_encode`, calls `output` with every byte and how often it repeats,
+///returns the number of bytes
+fn run_length_decode(block: &[u8], mut output: impl FnMut(u8, usize)) -> usize {
+    let mut size = 0;
+    let mut previous = None;
+    let mut same = 0;
+
+    for byte in block {
+        //the byte after 4 equal ones is the number of further repeats
+        if same == 4 {
+            output(previous.unwrap(), *byte as usize);
+            size += *byte as usize;
+            same = 0;
+            continue;
+        }
+
+        if previous == Some(*byte) {
+            same += 1;
+        } else {
+            previous = Some(*byte);
+            same = 1;
+        }
+
+        output(*byte, 1);
+        size += 1;
+    }
+
+    size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(name: &str) -> Vec<u8> {
+        std::fs::read(format!("./test_files/bzip2/{name}")).unwrap()
+    }
+
+    fn text(size: usize) -> Vec<u8> {
+        let mut bytes = std::fs::read("./test_files/135-0.txt").unwrap();
+        bytes.truncate(size);
+        bytes
+    }
+
+    #[test]
+    fn crc_test() {
+        //CRC-32/BZIP2 check value
+        assert_eq!(bzip2_crc(b"123456789"), 0xfc89_1918);
+        assert_eq!(bzip2_crc(b""), 0);
+    }
+
+    #[test]
+    fn table_test() {
+        let table = Table::new(vec![2, 1, 3, 3]);
+
+        assert_eq!(table.codes(), [0b10, 0b0, 0b110, 0b111]);
+
+        let mut writer = BitWriter::new();
+        for symbol in [2, 0, 1, 3] {
+            writer.write(table.lengths[symbol] as u32, table.codes()[symbol]);
+        }
+        let bytes = writer.finish();
+        let mut reader = BitReader::new(&bytes);
+        let decoded: Vec<u16> = (0..4).map(|_| table.decode(&mut reader).unwrap()).collect();
+
+        assert_eq!(decoded, [2, 0, 1, 3]);
+    }
+
+    #[test]
+    fn run_length_test() {
+        let mut bytes = vec![b'a'; 3];
+        bytes.extend_from_slice(&[b'b'; 4]);
+        bytes.extend_from_slice(&[b'c'; 260]);
+
+        let (block, used) = run_length_encode(&bytes, 100);
+        assert_eq!(used, bytes.len());
+        assert_eq!(block, b"aaabbbb\0cccc\xfbcccc\x01");
+
+        let mut decoded = Vec::new();
+        let size = run_length_decode(&block, |byte, count| {
+            decoded.resize(decoded.len() + count, byte)
+        });
+        assert_eq!(decoded, bytes);
+        assert_eq!(size, bytes.len());
+
+        //runs aren't split between blocks
+        assert_eq!(run_length_encode(&bytes, 10), (b"aaabbbb\0".to_vec(), 7));
+    }
+
+    #[test]
+    fn decompress_samples_test() {
+        let runs = sample("runs.bin");
+
+        assert_eq!(decompress_bzip2(&sample("empty.bz2")).unwrap(), b"");
+        assert_eq!(decompress_bzip2(&sample("runs.bin.bz2")).unwrap(), runs);
+        //two blocks at level 1
+        assert!(decompress_bzip2(&sample("135-0_head.txt.bz2")).unwrap() == text(200_000));
+
+        let mut both = text(5000);
+        both.extend_from_slice(&runs);
+        assert!(decompress_bzip2(&sample("multi_stream.bz2")).unwrap() == both);
+    }
+
+    #[test]
+    fn compress_test() {
+        let runs = sample("runs.bin");
+        let text = text(250_000);
+
+        for bytes in [
+            &b""[..],
+            b"a",
+            b"banana",
+            b"abababab",
+            &[7; 1000],
+            &runs,
+            &text,
+        ] {
+            let compressed = compress_bzip2(bytes, 1);
+            assert!(decompress_bzip2(&compressed).unwrap() == bytes);
+        }
+
+        assert_eq!(compress_bzip2(b"", 9), sample("empty.bz2"));
+        //about as good as bzip2 itself
+        assert!(
+            compress_bzip2(&text[..200_000], 1).len()
+                < sample("135-0_head.txt.bz2").len() * 21 / 20
+        );
+    }
+
+    #[test]
+    fn compress_tables_test() {
+        let compressed = compress_bzip2(&text(10_000), 9);
+        let mut reader = BitReader::new(&compressed);
+        reader.read(32).unwrap();
+        assert_eq!(reader.read_magic().unwrap(), BLOCK_MAGIC);
+
+        //crc, randomised, origin
+        reader.read(32).unwrap();
+        reader.read(25).unwrap();
+        let used_ranges = reader.read(16).unwrap();
+        reader.read(16 * used_ranges.count_ones()).unwrap();
+
+        assert_eq!(reader.read(3).unwrap() as usize, MAX_TABLES);
+    }
+
+    #[test]
+    fn decompress_invalid_test() {
+        let compressed = compress_bzip2(&sample("runs.bin"), 9);
+
+        for size in [0, 3, 10, 100, compressed.len() - 1] {
+            assert!(decompress_bzip2(&compressed[..size]).is_err());
+        }
+
+        //payload bits, crcs and the level
+        for position in [4, 10, 14, 500, compressed.len() - 3] {
+            let mut bytes = compressed.clone();
+            bytes[position] ^= 0x10;
+            assert!(decompress_bzip2(&bytes).is_err());
+        }
+
+        let mut bytes = compressed.clone();
+        bytes.push(0);
+        assert!(decompress_bzip2(&bytes).is_err());
+    }
+
+    #[test]
+    fn decompress_limits_test() {
+        let compressed = compress_bzip2(&[0; 100_000], 1);
+        let options = DecompressOptions::new().max_output_size(99_999);
+        let err = decompress_bzip2_with_options(&compressed, &options).unwrap_err();
+
+        assert!(crate::LimitError::from_io(&err).is_some());
+    }
+}
@@ -0,0 +1,436 @@
+use alloc::{format, vec::Vec};
+use core::fmt;
+
+use crate::io;
+
+use crate::{
+    ans::{decode_ans, encode_ans, normalize_frequencies, read_ans_table, write_ans_table},
+    fileops::{decode_payload, encode_huffman},
+    header::invalid_data,
+    interleaved::{decode_huffman4, encode_huffman4},
+    range::{decode_range, encode_range},
+    CompressOptions, FreqHeader,
+};
+
+///size of a default table entry, byte + frequency weight
+const ENTRY_SIZE: usize = 1 + 8;
+
+///Frequencies a coder works with and the number of bytes they stand for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FreqModel {
+    frequencies: [usize; 256],
+    total: usize,
+}
+
+impl FreqModel {
+    pub fn new(frequencies: [usize; 256], total: usize) -> Self {
+        FreqModel { frequencies, total }
+    }
+
+    pub fn frequencies(&self) -> &[usize; 256] {
+        &self.frequencies
+    }
+
+    ///number of bytes coded with this model
+    pub fn total(&self) -> usize {
+        self.total
+    }
+}
+
+///A coder for the payload that follows a `FreqHeader`
+///
+///Add it to `CODERS` to make it available by name and readable from headers.
+///The default model is the frequencies themselves, stored as (byte (u8) | frequency (u64)) entries
+pub trait EntropyCoder: Sync {
+    ///lowercase name to select the coder by
+    fn name(&self) -> &'static str;
+
+    ///stored in the header flags, 0 to 7. Never change it once files were written
+    fn id(&self) -> u8;
+
+    ///whether the payload is coded with a tree built with the options' `TreeMode`,
+    ///which is then stored in the header flags
+    fn uses_tree(&self) -> bool {
+        false
+    }
+
+    ///what the decoder needs to know about `frequencies` from `get_byte_frequencies`
+    fn build_model(&self, frequencies: &[usize; 256]) -> FreqModel {
+        FreqModel::new(*frequencies, frequencies.iter().sum())
+    }
+
+    ///append `model` to the header
+    fn write_model(&self, model: &FreqModel, table: &mut Vec<u8>) {
+        for (byte, freq) in model.frequencies.iter().enumerate() {
+            if *freq != 0 {
+                table.extend_from_slice(&(byte as u8).to_be_bytes());
+                table.extend_from_slice(&(*freq as u64).to_be_bytes());
+            }
+        }
+    }
+
+    ///read a model written by `write_model`, anything it couldn't have written is an error
+    fn read_model(&self, table: &[u8]) -> io::Result<FreqModel> {
+        if !table.len().is_multiple_of(ENTRY_SIZE) || table.len() / ENTRY_SIZE > 256 {
+            return Err(invalid_data(format!(
+                "invalid table size {}",
+                table.len() + 1
+            )));
+        }
+
+        let mut frequencies = [0; 256];
+        let mut total: usize = 0;
+
+        for entry in table.chunks_exact(ENTRY_SIZE) {
+            let byte = entry[0] as usize;
+            let mut frequency = [0; 8];
+            frequency.copy_from_slice(&entry[1..]);
+
+            let frequency = usize::try_from(u64::from_be_bytes(frequency))
+                .map_err(|_| invalid_data("frequency too large"))?;
+
+            if frequency == 0 || frequencies[byte] != 0 {
+                return Err(invalid_data(format!(
+                    "empty or repeated table entry for byte {byte}"
+                )));
+            }
+
+            total = total
+                .checked_add(frequency)
+                .ok_or_else(|| invalid_data("sum of frequencies overflows"))?;
+            frequencies[byte] = frequency;
+        }
+
+        Ok(FreqModel::new(frequencies, total))
+    }
+
+    ///append `uncompressed_bytes` coded with `model` to `compressed_bytes`,
+    ///returns the size of the last byte, 0 if it is full
+    fn encode(
+        &self,
+        uncompressed_bytes: &[u8],
+        model: &FreqModel,
+        options: &CompressOptions,
+        compressed_bytes: &mut Vec<u8>,
+    ) -> io::Result<u8>;
+
+    ///decode a payload written by `encode`, `header` is the one in front of it
+    fn decode(&self, payload: &[u8], header: &FreqHeader) -> io::Result<Vec<u8>>;
+}
+
+///every coder that can be selected by name or read from a header
+pub const CODERS: [Coder; 4] = [Coder::HUFFMAN, Coder::RANGE, Coder::ANS, Coder::HUFFMAN4];
+
+///Handle to one of the `CODERS`, stored in the header so every block can use a different one
+#[derive(Clone, Copy)]
+pub struct Coder(&'static dyn EntropyCoder);
+
+impl Coder {
+    pub const HUFFMAN: Coder = Coder(&HuffmanCoder);
+    ///range coder on the same frequencies, see `encode_range`
+    pub const RANGE: Coder = Coder(&RangeCoder);
+    ///tANS on normalized frequencies, see `encode_ans`
+    pub const ANS: Coder = Coder(&AnsCoder);
+    ///huffman codes in 4 interleaved streams, see `encode_interleaved`
+    pub const HUFFMAN4: Coder = Coder(&Huffman4Coder);
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        CODERS
+            .into_iter()
+            .find(|coder| coder.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        CODERS.into_iter().find(|coder| coder.id() == id)
+    }
+
+    pub fn name(self) -> &'static str {
+        self.0.name()
+    }
+
+    pub fn id(self) -> u8 {
+        self.0.id()
+    }
+
+    pub fn uses_tree(self) -> bool {
+        self.0.uses_tree()
+    }
+
+    pub fn entropy_coder(self) -> &'static dyn EntropyCoder {
+        self.0
+    }
+}
+
+impl Default for Coder {
+    fn default() -> Self {
+        Coder::HUFFMAN
+    }
+}
+
+impl PartialEq for Coder {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for Coder {}
+
+impl fmt::Debug for Coder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Coder({})", self.name())
+    }
+}
+
+impl fmt::Display for Coder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl core::str::FromStr for Coder {
+    type Err = io::Error;
+
+    fn from_str(name: &str) -> io::Result<Self> {
+        Coder::from_name(name).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("unknown coder {name}"))
+        })
+    }
+}
+
+///codes from a tree built with the options' `TreeMode`
+pub struct HuffmanCoder;
+
+impl EntropyCoder for HuffmanCoder {
+    fn name(&self) -> &'static str {
+        "huffman"
+    }
+
+    fn id(&self) -> u8 {
+        0
+    }
+
+    fn uses_tree(&self) -> bool {
+        true
+    }
+
+    fn encode(
+        &self,
+        uncompressed_bytes: &[u8],
+        model: &FreqModel,
+        options: &CompressOptions,
+        compressed_bytes: &mut Vec<u8>,
+    ) -> io::Result<u8> {
+        encode_huffman(
+            uncompressed_bytes,
+            model.frequencies(),
+            options.get_tree_mode(),
+            compressed_bytes,
+        )
+    }
+
+    fn decode(&self, payload: &[u8], header: &FreqHeader) -> io::Result<Vec<u8>> {
+        decode_payload(
+            payload,
+            header.frequencies(),
+            header.last_byte_size(),
+            header.tree_mode(),
+        )
+    }
+}
+
+///the same codes as `HuffmanCoder`, split between 4 streams the decoder reads at the same time
+pub struct Huffman4Coder;
+
+impl EntropyCoder for Huffman4Coder {
+    fn name(&self) -> &'static str {
+        "huffman4"
+    }
+
+    fn id(&self) -> u8 {
+        3
+    }
+
+    fn uses_tree(&self) -> bool {
+        true
+    }
+
+    fn encode(
+        &self,
+        uncompressed_bytes: &[u8],
+        model: &FreqModel,
+        options: &CompressOptions,
+        compressed_bytes: &mut Vec<u8>,
+    ) -> io::Result<u8> {
+        encode_huffman4(
+            uncompressed_bytes,
+            model.frequencies(),
+            options.get_tree_mode(),
+            compressed_bytes,
+        )?;
+
+        //every stream ends on a whole byte
+        Ok(0)
+    }
+
+    fn decode(&self, payload: &[u8], header: &FreqHeader) -> io::Result<Vec<u8>> {
+        decode_huffman4(
+            payload,
+            header.frequencies(),
+            header.total(),
+            header.tree_mode(),
+        )
+    }
+}
+
+pub struct RangeCoder;
+
+impl EntropyCoder for RangeCoder {
+    fn name(&self) -> &'static str {
+        "range"
+    }
+
+    fn id(&self) -> u8 {
+        1
+    }
+
+    fn encode(
+        &self,
+        uncompressed_bytes: &[u8],
+        model: &FreqModel,
+        _options: &CompressOptions,
+        compressed_bytes: &mut Vec<u8>,
+    ) -> io::Result<u8> {
+        encode_range(uncompressed_bytes, model.frequencies(), compressed_bytes);
+
+        //ends on whole bytes
+        Ok(0)
+    }
+
+    fn decode(&self, payload: &[u8], header: &FreqHeader) -> io::Result<Vec<u8>> {
+        decode_range(payload, header.frequencies())
+    }
+}
+
+///the model holds normalized frequencies in a compact table, see `write_ans_table`
+pub struct AnsCoder;
+
+impl EntropyCoder for AnsCoder {
+    fn name(&self) -> &'static str {
+        "ans"
+    }
+
+    fn id(&self) -> u8 {
+        2
+    }
+
+    fn build_model(&self, frequencies: &[usize; 256]) -> FreqModel {
+        FreqModel::new(normalize_frequencies(frequencies), frequencies.iter().sum())
+    }
+
+    fn write_model(&self, model: &FreqModel, table: &mut Vec<u8>) {
+        write_ans_table(model.frequencies(), model.total(), table);
+    }
+
+    fn read_model(&self, table: &[u8]) -> io::Result<FreqModel> {
+        let (normalized, total) = read_ans_table(table)?;
+
+        Ok(FreqModel::new(normalized, total))
+    }
+
+    fn encode(
+        &self,
+        uncompressed_bytes: &[u8],
+        model: &FreqModel,
+        _options: &CompressOptions,
+        compressed_bytes: &mut Vec<u8>,
+    ) -> io::Result<u8> {
+        encode_ans(uncompressed_bytes, model.frequencies(), compressed_bytes)?;
+
+        Ok(0)
+    }
+
+    fn decode(&self, payload: &[u8], header: &FreqHeader) -> io::Result<Vec<u8>> {
+        decode_ans(payload, header.frequencies(), header.total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress_to_vec_with_options, decompress_to_vec, get_byte_frequencies};
+
+    #[test]
+    fn from_name_test() {
+        assert_eq!(Coder::from_name("Range"), Some(Coder::RANGE));
+        assert_eq!("ans".parse::<Coder>().unwrap(), Coder::ANS);
+        assert_eq!(Coder::from_name("lzma"), None);
+        assert_eq!(Coder::default().to_string(), "huffman");
+    }
+
+    #[test]
+    fn ids_test() {
+        for (i, coder) in CODERS.iter().enumerate() {
+            assert!(coder.id() < 8);
+            assert_eq!(Coder::from_id(coder.id()), Some(*coder));
+            assert!(CODERS[..i].iter().all(|other| other.id() != coder.id()));
+        }
+    }
+
+    #[test]
+    fn model_test() {
+        let frequencies = get_byte_frequencies(b"AACD");
+
+        for coder in CODERS {
+            let coder = coder.entropy_coder();
+            let model = coder.build_model(&frequencies);
+            let mut table = Vec::new();
+            coder.write_model(&model, &mut table);
+
+            assert_eq!(model.total(), 4);
+            assert_eq!(coder.read_model(&table).unwrap(), model);
+            assert!(coder.read_model(&table[1..]).is_err());
+        }
+    }
+
+    ///stores the bytes as they are, to show a coder only needs to be added to `CODERS`
+    struct StoreCoder;
+
+    impl EntropyCoder for StoreCoder {
+        fn name(&self) -> &'static str {
+            "store"
+        }
+
+        fn id(&self) -> u8 {
+            7
+        }
+
+        fn encode(
+            &self,
+            uncompressed_bytes: &[u8],
+            _model: &FreqModel,
+            _options: &CompressOptions,
+            compressed_bytes: &mut Vec<u8>,
+        ) -> io::Result<u8> {
+            compressed_bytes.extend_from_slice(uncompressed_bytes);
+            Ok(0)
+        }
+
+        fn decode(&self, payload: &[u8], header: &FreqHeader) -> io::Result<Vec<u8>> {
+            match payload.len() == header.total() {
+                true => Ok(payload.to_vec()),
+                false => Err(invalid_data("stored payload has the wrong size")),
+            }
+        }
+    }
+
+    #[test]
+    fn unregistered_coder_test() {
+        let options = CompressOptions::new().coder(Coder(&StoreCoder));
+        let compressed = compress_to_vec_with_options(b"AACD", &options);
+
+        assert_eq!(compressed[compressed.len() - 4..], *b"AACD");
+        //id 7 isn't in `CODERS`, so it can't be read back
+        let error = decompress_to_vec(&compressed).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,158 @@
+use std::fmt::Write;
+
+use crate::{stats::escape_byte, HuffNode};
+
+///Options for `tree_to_dot`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DotOptions {
+    collapse_below: usize,
+}
+
+impl DotOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///draw subtrees lighter than `weight` as a single box,
+    ///keeps the graph readable for large alphabets
+    pub fn collapse_below(mut self, weight: usize) -> Self {
+        self.collapse_below = weight;
+        self
+    }
+}
+
+///render a tree from `HuffNode::from_frequencies` as a Graphviz DOT graph
+///
+///leaves show their byte and weight, internal nodes their weight and
+///edges the bit they stand for
+pub fn tree_to_dot(node: &Option<Box<HuffNode>>, options: &DotOptions) -> String {
+    let mut dot = String::from("digraph huffman {\n    node [fontname=\"monospace\"];\n");
+
+    if let Some(node) = node {
+        let mut next_id = 0;
+        write_node(node, options, &mut next_id, &mut dot);
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+///writes `node` and everything below it, returns the id it was given
+fn write_node(
+    node: &HuffNode,
+    options: &DotOptions,
+    next_id: &mut usize,
+    dot: &mut String,
+) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+
+    if let Some(byte) = node.get_byte() {
+        writeln!(
+            dot,
+            "    n{id} [shape=box, label=\"{}\\n{}\"];",
+            dot_escape(&escape_byte(byte)),
+            node.get_weight()
+        )
+        .unwrap();
+
+        return id;
+    }
+
+    if node.get_weight() < options.collapse_below {
+        writeln!(
+            dot,
+            "    n{id} [shape=box, style=dashed, label=\"{} symbols\\n{}\"];",
+            count_leaves(node),
+            node.get_weight()
+        )
+        .unwrap();
+
+        return id;
+    }
+
+    writeln!(
+        dot,
+        "    n{id} [shape=circle, label=\"{}\"];",
+        node.get_weight()
+    )
+    .unwrap();
+
+    for (bit, child) in [(0, node.get_left()), (1, node.get_right())] {
+        if let Some(child) = child {
+            let child_id = write_node(child, options, next_id, dot);
+            writeln!(dot, "    n{id} -> n{child_id} [label=\"{bit}\"];").unwrap();
+        }
+    }
+
+    id
+}
+
+fn count_leaves(node: &HuffNode) -> usize {
+    if node.get_byte().is_some() {
+        return 1;
+    }
+
+    node.get_left().map_or(0, count_leaves) + node.get_right().map_or(0, count_leaves)
+}
+
+fn dot_escape(string: &str) -> String {
+    string.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_byte_frequencies;
+
+    #[test]
+    fn tree_to_dot_test() {
+        let node = HuffNode::from_frequencies(&get_byte_frequencies(b"AACD"));
+        let dot = tree_to_dot(&node, &DotOptions::new());
+
+        assert_eq!(
+            dot,
+            "digraph huffman {
+    node [fontname=\"monospace\"];
+    n0 [shape=circle, label=\"4\"];
+    n1 [shape=box, label=\"A\\n2\"];
+    n0 -> n1 [label=\"0\"];
+    n2 [shape=circle, label=\"2\"];
+    n3 [shape=box, label=\"D\\n1\"];
+    n2 -> n3 [label=\"0\"];
+    n4 [shape=box, label=\"C\\n1\"];
+    n2 -> n4 [label=\"1\"];
+    n0 -> n2 [label=\"1\"];
+}
+"
+        );
+    }
+
+    #[test]
+    fn tree_to_dot_collapse_test() {
+        let node = HuffNode::from_frequencies(&get_byte_frequencies(b"AACD"));
+        let dot = tree_to_dot(&node, &DotOptions::new().collapse_below(3));
+
+        assert!(dot.contains("n2 [shape=box, style=dashed, label=\"2 symbols\\n2\"];"));
+        assert!(!dot.contains("n3"));
+    }
+
+    #[test]
+    fn tree_to_dot_escape_test() {
+        let node = HuffNode::from_frequencies(&get_byte_frequencies(b"\"\n"));
+        let dot = tree_to_dot(&node, &DotOptions::new());
+
+        assert!(dot.contains("label=\"\\\\\\\"\\n1\""));
+        assert!(dot.contains("label=\"\\\\n\\n1\""));
+    }
+
+    #[test]
+    fn tree_to_dot_empty_test() {
+        let dot = tree_to_dot(&None, &DotOptions::new());
+
+        assert_eq!(
+            dot,
+            "digraph huffman {\n    node [fontname=\"monospace\"];\n}\n"
+        );
+    }
+}
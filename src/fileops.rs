@@ -1,18 +1,68 @@
+use alloc::{boxed::Box, format, vec, vec::Vec};
+use core::marker::PhantomData;
+#[cfg(feature = "fs")]
 use std::{
-    collections::HashMap,
     fs::File,
-    io::{self, Read, Write},
-    marker::PhantomData,
+    io::{Read, Write},
 };
 
-use crate::{huff::get_byte_frequencies, HuffCode, HuffNode};
+use crate::{
+    bwt::bwt_transform,
+    header::{header_flags, invalid_data, FLAG_BWT},
+    huff::get_byte_frequencies,
+    io, CodeTable, Coder, DecompressOptions, FreqHeader, HuffCode, HuffNode, TreeMode,
+};
+
+///Options for `CompressFile` and `compress_to_vec_with_options`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressOptions {
+    tree_mode: TreeMode,
+    coder: Coder,
+    bwt: bool,
+}
+
+impl CompressOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///how ties are broken while building the tree, stored in the header
+    pub fn tree_mode(mut self, mode: TreeMode) -> Self {
+        self.tree_mode = mode;
+        self
+    }
+
+    ///how the payload is coded, the frequency header is the same for all of them
+    pub fn coder(mut self, coder: Coder) -> Self {
+        self.coder = coder;
+        self
+    }
+
+    ///run the input through `bwt_transform` before coding it, much smaller for text
+    pub fn bwt(mut self, bwt: bool) -> Self {
+        self.bwt = bwt;
+        self
+    }
+
+    pub fn get_tree_mode(&self) -> TreeMode {
+        self.tree_mode
+    }
+
+    pub fn get_coder(&self) -> Coder {
+        self.coder
+    }
+
+    pub fn get_bwt(&self) -> bool {
+        self.bwt
+    }
+}
 
 ///Compress a file using simple Hoffman Code
 #[derive(Debug)]
 pub struct CompressFile<S: CompState> {
     state: Box<ActualCompState>,
 
-    _marker: std::marker::PhantomData<S>,
+    _marker: PhantomData<S>,
 }
 
 pub enum StartComp {}
@@ -25,10 +75,13 @@ impl CompState for Compress {}
 impl CompState for OutputComp {}
 
 #[derive(Debug)]
+//only read by the methods using files
+#[cfg_attr(not(feature = "fs"), allow(dead_code))]
 struct ActualCompState {
     compressed_bytes: Vec<u8>,
     compressed_last_byte_length: u8,
     frequencies: [usize; 256],
+    options: CompressOptions,
 }
 
 ///initialize compression
@@ -39,6 +92,7 @@ impl CompressFile<StartComp> {
                 compressed_bytes: vec![],
                 compressed_last_byte_length: 0,
                 frequencies: [0; 256],
+                options: CompressOptions::new(),
             }),
             _marker: PhantomData,
         }
@@ -47,6 +101,13 @@ impl CompressFile<StartComp> {
 
 ///performs the actual compress
 impl CompressFile<Compress> {
+    ///set how the tree is built
+    pub fn options(mut self, options: CompressOptions) -> Self {
+        self.state.options = options;
+        self
+    }
+
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
     ///returns a tuple of (compressed_bytes, size of the last byte)
     fn compress_bytes(
         &mut self,
@@ -54,53 +115,26 @@ impl CompressFile<Compress> {
         frequencies: &[usize; 256],
     ) -> (Vec<u8>, u8) {
         let mut compressed_bytes = Vec::new();
-        let huff = HuffNode::from_frequencies(frequencies);
-
-        let table = HuffCode::from_tree(&huff);
-
-        let mut compressed_byte = 0;
-        let mut b_index = 0;
-
-        let mut has_remaining = false;
-
-        for byte in uncompressed_bytes {
-            let (code, length) = match table.get(byte) {
-                Some(item) => (item.get_code(), item.get_length()),
-                None => panic!("HashMap table key doesn't exist!"),
-            };
-
-            for i in 0..length {
-                let code_bit: u8 = ((code >> i) & 1).try_into().unwrap();
-
-                //There's a possibility bits remain in `compressed_byte`` when
-                //processing final `byte`
-                has_remaining = true;
-
-                compressed_byte |= code_bit << b_index;
-
-                if b_index == 7 {
-                    compressed_bytes.push(compressed_byte);
-                    b_index = 0;
-                    compressed_byte = 0;
-                    has_remaining = false;
-                } else {
-                    b_index += 1;
-                }
-            }
-        }
-
-        if has_remaining {
-            compressed_bytes.push(compressed_byte);
-        }
-
-        (compressed_bytes, b_index)
+        let last_byte_length = encode_payload(
+            uncompressed_bytes,
+            frequencies,
+            &self.state.options,
+            &mut compressed_bytes,
+        );
+
+        (compressed_bytes, last_byte_length)
     }
 
+    #[cfg(feature = "fs")]
     pub fn compress(mut self, file: &str) -> io::Result<CompressFile<OutputComp>> {
         let mut file = File::open(file)?;
         let mut uncompressed_bytes = Vec::new();
         file.read_to_end(&mut uncompressed_bytes)?;
 
+        if self.state.options.bwt {
+            uncompressed_bytes = bwt_transform(&uncompressed_bytes);
+        }
+
         let frequencies = get_byte_frequencies(&uncompressed_bytes);
 
         let (compressed_bytes, last_byte_len) =
@@ -114,36 +148,31 @@ impl CompressFile<Compress> {
             _marker: PhantomData,
         })
     }
+
+    #[cfg(feature = "mmap")]
+    ///compress `file` into `output` through a memory map, see `compress_file_mapped`
+    pub fn compress_mapped(self, file: &str, output: &str) -> io::Result<()> {
+        crate::compress_file_mapped(file, output, &self.state.options)
+    }
 }
 
 ///compression output implementations
 impl CompressFile<OutputComp> {
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
     ///create frequency header using state data
     fn create_freq_header(&self) -> Vec<u8> {
         let mut compressed_bytes: Vec<u8> = Vec::new();
-        let mut freq_list = Vec::new();
-
-        for (byte, freq) in self.state.frequencies.iter().enumerate() {
-            if *freq != 0 {
-                freq_list.push((byte as u8, *freq));
-            }
-        }
-
-        //table_size = remaining bit size + (valid frequency * (byte size + frequency weight size))
-        let table_size = 1 + (freq_list.len() * (1 + 8));
-
-        compressed_bytes.extend_from_slice(&table_size.to_be_bytes());
-
-        compressed_bytes.extend_from_slice(&self.state.compressed_last_byte_length.to_be_bytes());
-
-        for (byte, freq) in freq_list {
-            compressed_bytes.extend_from_slice(&byte.to_be_bytes());
-            compressed_bytes.extend_from_slice(&freq.to_be_bytes());
-        }
+        write_freq_header(
+            &self.state.frequencies,
+            self.state.compressed_last_byte_length,
+            &self.state.options,
+            &mut compressed_bytes,
+        );
 
         compressed_bytes
     }
 
+    #[cfg(feature = "fs")]
     ///output with frequency header table to `file`
     /// this is a ~very~ stupid implementation as the header will be LARGE
     pub fn output_freq(&mut self, file: &str) -> io::Result<()> {
@@ -157,6 +186,236 @@ impl CompressFile<OutputComp> {
     }
 }
 
+///append the codes for `uncompressed_bytes` to `compressed_bytes`, returns the size of the last byte
+fn encode_payload(
+    uncompressed_bytes: &[u8],
+    frequencies: &[usize; 256],
+    options: &CompressOptions,
+    compressed_bytes: &mut Vec<u8>,
+) -> u8 {
+    let coder = options.coder.entropy_coder();
+    let model = coder.build_model(frequencies);
+
+    coder
+        .encode(uncompressed_bytes, &model, options, compressed_bytes)
+        .expect("every byte of the input has a code")
+}
+
+///huffman codes for `uncompressed_bytes`, see `EntropyCoder::encode`
+pub(crate) fn encode_huffman(
+    uncompressed_bytes: &[u8],
+    frequencies: &[usize; 256],
+    tree_mode: TreeMode,
+    compressed_bytes: &mut Vec<u8>,
+) -> io::Result<u8> {
+    let huff = HuffNode::from_frequencies_with(frequencies, tree_mode);
+
+    let table = HuffCode::from_tree(&huff);
+
+    encode_with_table(uncompressed_bytes, &table, compressed_bytes)
+}
+
+///append the codes from `table` for `uncompressed_bytes` to `compressed_bytes`,
+///returns the size of the last byte, 0 if it is full.
+///A byte without a code is an error, `compressed_bytes` is left as it was then
+///
+///this is only the payload, the table has to be stored separately (see `export_table`)
+pub fn encode_with_table(
+    uncompressed_bytes: &[u8],
+    table: &CodeTable,
+    compressed_bytes: &mut Vec<u8>,
+) -> io::Result<u8> {
+    let codes = code_array(table);
+
+    let start = compressed_bytes.len();
+    let mut writer = CodeWriter::new(compressed_bytes);
+    //every byte costs at least a bit of output
+    writer.output.reserve(uncompressed_bytes.len() / 8);
+
+    for byte in uncompressed_bytes {
+        if let Err(err) = writer.put_byte(*byte, &codes, table) {
+            writer.output.truncate(start);
+            return Err(err);
+        }
+    }
+
+    Ok(writer.finish())
+}
+
+///the codes of `table` as (code, length) for `CodeWriter::put_byte`.
+///Codes of up to 64 bits, which are all of them unless the frequencies grow like the
+///fibonacci numbers, are in the array. Longer ones and missing codes are only marked
+pub(crate) fn code_array(table: &CodeTable) -> [(u64, u32); 256] {
+    let mut codes = [(0u64, LONG_CODE); 256];
+    for (byte, code) in table.iter() {
+        let length = code.get_length() as u32;
+        if length <= 64 {
+            let bits = code.get_code() as u64 & u64::MAX.checked_shr(64 - length).unwrap_or(0);
+            codes[byte as usize] = (bits, length);
+        }
+    }
+
+    codes
+}
+
+///length in `code_array` for bytes it has to look up in the table
+const LONG_CODE: u32 = u32::MAX;
+
+///Writes codes lowest bit first into a 64 bit accumulator, which goes to the output
+///as a whole word every time it is full
+pub(crate) struct CodeWriter<'a> {
+    pub(crate) output: &'a mut Vec<u8>,
+    bits: u64,
+    ///number of `bits` used, always less than 64
+    used: u32,
+}
+
+impl<'a> CodeWriter<'a> {
+    pub(crate) fn new(output: &'a mut Vec<u8>) -> Self {
+        CodeWriter {
+            output,
+            bits: 0,
+            used: 0,
+        }
+    }
+
+    ///append the lowest `length` bits of `code`, which has to be 0 above them
+    #[inline(always)]
+    fn put(&mut self, code: u64, length: u32) {
+        self.bits |= code << self.used;
+
+        let used = self.used + length;
+        if used < 64 {
+            self.used = used;
+            return;
+        }
+
+        self.output.extend_from_slice(&self.bits.to_le_bytes());
+        //the bits of `code` that didn't fit, none if it was all of it
+        self.bits = code.checked_shr(64 - self.used).unwrap_or(0);
+        self.used = used - 64;
+    }
+
+    ///append the code of `byte` from `codes`, made by `code_array` from `table`
+    #[inline(always)]
+    pub(crate) fn put_byte(
+        &mut self,
+        byte: u8,
+        codes: &[(u64, u32); 256],
+        table: &CodeTable,
+    ) -> io::Result<()> {
+        match codes[byte as usize] {
+            (code, length) if length <= 64 => self.put(code, length),
+            _ => match table.get(byte) {
+                Some(code) => {
+                    let length = code.get_length() as u32;
+                    self.put(code.get_code() as u64, 64);
+
+                    let high = (code.get_code() >> 64) as u64 & (u64::MAX >> (128 - length));
+                    self.put(high, length - 64);
+                }
+                None => return Err(invalid_data(format!("no code for byte {byte}"))),
+            },
+        }
+
+        Ok(())
+    }
+
+    ///write the bits that are left, returns the size of the last byte, 0 if it is full
+    pub(crate) fn finish(self) -> u8 {
+        let bytes = self.used.div_ceil(8) as usize;
+        self.output
+            .extend_from_slice(&self.bits.to_le_bytes()[..bytes]);
+
+        (self.used % 8) as u8
+    }
+}
+
+///append the frequency header for `frequencies` to `compressed_bytes`, holding the model of the options' coder
+pub(crate) fn write_freq_header(
+    frequencies: &[usize; 256],
+    last_byte_length: u8,
+    options: &CompressOptions,
+    compressed_bytes: &mut Vec<u8>,
+) {
+    let coder = options.coder.entropy_coder();
+    let mut table = Vec::new();
+    coder.write_model(&coder.build_model(frequencies), &mut table);
+
+    //table_size = remaining bit size + (valid frequency * (byte size + frequency weight size))
+    let table_size = 1 + table.len();
+
+    compressed_bytes.extend_from_slice(&(table_size as u64).to_be_bytes());
+
+    let mut flags = header_flags(options.tree_mode, options.coder);
+    if options.bwt {
+        flags |= FLAG_BWT;
+    }
+    compressed_bytes.extend_from_slice(&(last_byte_length | flags).to_be_bytes());
+
+    compressed_bytes.extend_from_slice(&table);
+}
+
+///compress `bytes` in memory, the result is identical to what `CompressFile::output_freq` writes
+pub fn compress_to_vec(bytes: &[u8]) -> Vec<u8> {
+    compress_to_vec_with_options(bytes, &CompressOptions::new())
+}
+
+///like `compress_to_vec`, building the tree as set in `options`
+pub fn compress_to_vec_with_options(bytes: &[u8], options: &CompressOptions) -> Vec<u8> {
+    let mut compressed_bytes = Vec::new();
+    compress_into_with_options(bytes, &mut compressed_bytes, options);
+
+    compressed_bytes
+}
+
+///like `compress_to_vec`, but appends to `compressed_bytes` so its allocation can be reused
+pub fn compress_into(bytes: &[u8], compressed_bytes: &mut Vec<u8>) {
+    compress_into_with_options(bytes, compressed_bytes, &CompressOptions::new())
+}
+
+///like `compress_into`, building the tree as set in `options`
+pub fn compress_into_with_options(
+    bytes: &[u8],
+    compressed_bytes: &mut Vec<u8>,
+    options: &CompressOptions,
+) {
+    let transformed;
+    let bytes = match options.bwt {
+        true => {
+            transformed = bwt_transform(bytes);
+            &transformed
+        }
+        false => bytes,
+    };
+
+    let frequencies = get_byte_frequencies(bytes);
+    let start = compressed_bytes.len();
+
+    //the size of the last byte is only known once everything is encoded
+    write_freq_header(&frequencies, 0, options, compressed_bytes);
+    let last_byte_length = encode_payload(bytes, &frequencies, options, compressed_bytes);
+    compressed_bytes[start + 8] |= last_byte_length;
+}
+
+///decompress `bytes` written by `compress_to_vec` or `CompressFile::output_freq`
+pub fn decompress_to_vec(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    decompress_to_vec_with_options(bytes, &DecompressOptions::new())
+}
+
+///like `decompress_to_vec`, enforcing the limits in `options`
+pub fn decompress_to_vec_with_options(
+    bytes: &[u8],
+    options: &DecompressOptions,
+) -> io::Result<Vec<u8>> {
+    let mut payload = bytes;
+    let header = FreqHeader::read(&mut payload, options)?;
+
+    options.check(header.total(), bytes.len())?;
+
+    header.decode_with_options(payload, options)
+}
+
 //...A lot of copied behavior
 
 ///Compress a file using simple Hoffman Code
@@ -164,7 +423,7 @@ impl CompressFile<OutputComp> {
 pub struct DecompressFile<S: DecompState> {
     state: Box<ActualDecompState>,
 
-    _marker: std::marker::PhantomData<S>,
+    _marker: PhantomData<S>,
 }
 
 pub enum StartDecomp {}
@@ -177,8 +436,11 @@ impl DecompState for Decompress {}
 impl DecompState for OutputDecomp {}
 
 #[derive(Debug)]
+//only read by the methods using files
+#[cfg_attr(not(feature = "fs"), allow(dead_code))]
 struct ActualDecompState {
     uncompressed_bytes: Vec<u8>,
+    options: DecompressOptions,
     //frequencies: [usize; 256],
 }
 
@@ -188,6 +450,7 @@ impl DecompressFile<StartDecomp> {
         DecompressFile {
             state: Box::new(ActualDecompState {
                 uncompressed_bytes: vec![],
+                options: DecompressOptions::new(),
                 //frequencies: [0; 256],
             }),
             _marker: PhantomData,
@@ -198,107 +461,317 @@ impl DecompressFile<StartDecomp> {
 ///perform actual decompression
 /// unlike compressing, decompressing has to use the file headers to achieve anything
 impl DecompressFile<Decompress> {
-    fn decompress_bytes(
-        &self,
-        bytes: &[u8],
-        frequencies: &[usize; 256],
-        last_byte_size: u8,
-    ) -> Vec<u8> {
-        let mut decompressed_bytes = Vec::new();
-        let huff = HuffNode::from_frequencies(frequencies);
+    ///set the resource limits checked before and during decompression
+    pub fn options(mut self, options: DecompressOptions) -> Self {
+        self.state.options = options;
+        self
+    }
+
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    ///decode `bytes` with the coder `header` was written for, undoing the bwt if it was used
+    fn decompress_bytes(&self, bytes: &[u8], header: &FreqHeader) -> io::Result<Vec<u8>> {
+        header.decode_with_options(bytes, &self.state.options)
+    }
 
-        let table = HuffCode::from_tree(&huff);
+    #[cfg(feature = "fs")]
+    pub fn decompress_freq(mut self, file: &str) -> io::Result<DecompressFile<OutputDecomp>> {
+        let mut file = File::open(file)?;
+        //pipes and other special files have no size until they are read
+        let metadata = file.metadata()?;
+        let options = self.state.options;
 
-        let mut new_table: HashMap<(u16, u128), u8> = HashMap::new();
+        let header = FreqHeader::read(&mut file, &options)?;
 
-        for (key, value) in table.iter() {
-            new_table.insert((value.get_length() - 1, value.get_code()), *key);
+        //all limits but the size after the bwt are known from the header alone,
+        //check before touching the payload
+        if metadata.is_file() {
+            let file_size = usize::try_from(metadata.len()).unwrap_or(usize::MAX);
+            options.check(header.total(), file_size)?;
         }
 
-        let bytes_length = bytes.len();
+        let mut compressed_bytes: Vec<u8> = Vec::new();
+        file.read_to_end(&mut compressed_bytes)?;
 
-        let mut remaining_bit_size = 8;
+        if !metadata.is_file() {
+            let read = header.size().saturating_add(compressed_bytes.len());
+            options.check(header.total(), read)?;
+        }
 
-        let mut current_code: u128 = 0;
-        let mut current_length: u16 = 0;
+        self.state.uncompressed_bytes = self.decompress_bytes(&compressed_bytes, &header)?;
 
-        for (i, byte) in bytes.iter().enumerate() {
-            if i == bytes_length - 1 {
-                //current byte is the last byte
+        Ok(DecompressFile {
+            state: self.state,
+            _marker: PhantomData,
+        })
+    }
+}
 
-                remaining_bit_size = last_byte_size;
-            }
-            for i in 0..remaining_bit_size {
-                let code_bit: u128 = ((byte >> i) & 1).into();
-                current_code |= code_bit << current_length;
-
-                if let Some(b) = new_table.get(&(current_length, current_code)) {
-                    decompressed_bytes.push(*b);
-                    current_length = 0;
-                    current_code = 0;
-                } else {
-                    current_length += 1;
-                }
-            }
-        }
+impl DecompressFile<OutputDecomp> {
+    #[cfg(feature = "fs")]
+    ///output decompressed file
+    pub fn output(&mut self, file: &str) -> io::Result<()> {
+        let mut file = File::create_new(file)?;
+        file.write_all(&self.state.uncompressed_bytes)?;
 
-        decompressed_bytes
+        Ok(())
     }
+}
 
-    pub fn decompress_freq(mut self, file: &str) -> io::Result<DecompressFile<OutputDecomp>> {
-        let mut file = File::open(file)?;
-        let mut frequencies = [0; 256];
+///decode a payload written by `CompressFile` using the frequencies from its header
+///
+///never panics or outputs more than the frequencies add up to, whatever `bytes` contains
+pub(crate) fn decode_payload(
+    bytes: &[u8],
+    frequencies: &[usize; 256],
+    last_byte_size: u8,
+    tree_mode: TreeMode,
+) -> io::Result<Vec<u8>> {
+    let total = frequencies
+        .iter()
+        .try_fold(0usize, |total, freq| total.checked_add(*freq))
+        .ok_or_else(|| invalid_data("sum of frequencies overflows"))?;
+
+    let huff = HuffNode::from_frequencies_with(frequencies, tree_mode);
+
+    let table = HuffCode::from_tree(&huff);
+
+    //a lone symbol has a code of length 0, nothing was written for it
+    if table.len() == 1 {
+        return repeat_byte(table.iter().next().unwrap().0, total);
+    }
 
-        let mut table_size = [0; 8];
+    let (decompressed_bytes, _) = decode_codes(bytes, &table, last_byte_size, total)?;
 
-        file.read_exact(&mut table_size)?;
+    if decompressed_bytes.len() != total {
+        return Err(payload_ended(decompressed_bytes.len(), total));
+    }
 
-        let mut table_size = usize::from_be_bytes(table_size);
+    Ok(decompressed_bytes)
+}
 
-        let mut last_byte_size = [0];
+pub(crate) fn payload_ended(decoded: usize, total: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!("payload ended after {decoded} of {total} bytes"),
+    )
+}
 
-        file.read_exact(&mut last_byte_size)?;
+///`total` times `byte`, for payloads of a single symbol
+pub(crate) fn repeat_byte(byte: u8, total: usize) -> io::Result<Vec<u8>> {
+    let mut decompressed_bytes = Vec::new();
+    decompressed_bytes
+        .try_reserve_exact(total)
+        .map_err(|_| invalid_data(format!("can't allocate {total} bytes")))?;
+    decompressed_bytes.resize(total, byte);
 
-        table_size -= 1;
+    Ok(decompressed_bytes)
+}
 
-        while table_size > 0 {
-            let mut byte = [0];
-            file.read_exact(&mut byte)?;
+///decode a payload written by `encode_with_table` with the same `table`
+pub fn decode_with_table(
+    bytes: &[u8],
+    table: &CodeTable,
+    last_byte_size: u8,
+) -> io::Result<Vec<u8>> {
+    let (decompressed_bytes, whole_codes) = decode_codes(bytes, table, last_byte_size, usize::MAX)?;
+
+    if !whole_codes {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "payload ended in the middle of a code",
+        ));
+    }
 
-            let mut frequency = [0; 8];
-            file.read_exact(&mut frequency)?;
+    Ok(decompressed_bytes)
+}
 
-            frequencies[byte[0] as usize] = usize::from_be_bytes(frequency);
+///decode up to `limit` bytes, returns them and whether the payload ended on a code boundary
+fn decode_codes(
+    bytes: &[u8],
+    table: &CodeTable,
+    last_byte_size: u8,
+    limit: usize,
+) -> io::Result<(Vec<u8>, bool)> {
+    let mut decoder = CodeDecoder::new(table, limit)?;
+
+    //every code is at least a bit long, don't trust `limit` any further than that
+    let mut decompressed_bytes = Vec::with_capacity(limit.min(bytes.len().saturating_mul(8)));
+
+    for (i, byte) in bytes.iter().enumerate() {
+        //a last byte size of 0 means it was filled completely
+        let bits = match last_byte_size {
+            0 => 8,
+            size if i == bytes.len() - 1 => size,
+            _ => 8,
+        };
 
-            table_size -= 9;
+        decoder.decode_byte(*byte, bits, &mut decompressed_bytes)?;
+        if decoder.stopped {
+            break;
         }
+    }
 
-        let mut compressed_bytes: Vec<u8> = Vec::new();
-        file.read_to_end(&mut compressed_bytes)?;
+    Ok((decompressed_bytes, decoder.is_between_codes()))
+}
 
-        self.state.uncompressed_bytes =
-            self.decompress_bytes(&compressed_bytes, &frequencies, last_byte_size[0]);
+///Decodes a payload of codes a byte at a time, so it can arrive in pieces
+#[derive(Debug, Clone)]
+pub(crate) struct CodeDecoder {
+    trie: CodeTrie,
+    ///node of the trie the bits so far lead to, 0 between codes
+    node: usize,
+    ///bytes still to decode
+    remaining: usize,
+    ///a code was complete after `remaining` ran out, the rest of the payload is ignored
+    stopped: bool,
+}
 
-        Ok(DecompressFile {
-            state: self.state,
-            _marker: PhantomData,
+impl CodeDecoder {
+    ///decoding at most `limit` bytes
+    pub(crate) fn new(table: &CodeTable, limit: usize) -> io::Result<Self> {
+        Ok(CodeDecoder {
+            trie: CodeTrie::new(table)?,
+            node: 0,
+            remaining: limit,
+            stopped: false,
         })
     }
-}
 
-impl DecompressFile<OutputDecomp> {
-    ///output decompressed file
-    pub fn output(&mut self, file: &str) -> io::Result<()> {
-        let mut file = File::create_new(file)?;
-        file.write_all(&self.state.uncompressed_bytes)?;
+    ///decode the lowest `bits` bits of `byte`, appending the bytes of complete codes to `output`
+    pub(crate) fn decode_byte(
+        &mut self,
+        byte: u8,
+        bits: u8,
+        output: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        if self.stopped {
+            return Ok(());
+        }
+
+        for i in 0..bits {
+            match self.trie.nodes[self.node][((byte >> i) & 1) as usize] {
+                Child::Node(next) => self.node = next as usize,
+                Child::Leaf(b) => {
+                    //never write more than the header promised, whatever the payload says
+                    if self.remaining == 0 {
+                        self.stopped = true;
+                        return Ok(());
+                    }
+
+                    output.push(b);
+                    self.remaining -= 1;
+                    self.node = 0;
+                }
+                //only possible with a table that isn't complete
+                Child::Missing => return Err(invalid_data("payload contains an unknown code")),
+            }
+        }
 
         Ok(())
     }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    ///whether the lowest `bits` bits of `byte` complete the last code, without decoding them
+    pub(crate) fn ends_within(&self, byte: u8, bits: u8) -> bool {
+        let (mut node, mut remaining) = (self.node, self.remaining);
+
+        for i in 0..bits {
+            match self.trie.nodes[node][((byte >> i) & 1) as usize] {
+                Child::Node(next) => node = next as usize,
+                Child::Leaf(_) if remaining == 1 => return true,
+                Child::Leaf(_) => {
+                    remaining = remaining.saturating_sub(1);
+                    node = 0;
+                }
+                Child::Missing => return false,
+            }
+        }
+
+        false
+    }
+
+    ///whether the payload can end here without cutting a code in two
+    pub(crate) fn is_between_codes(&self) -> bool {
+        self.stopped || self.node == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Child {
+    Missing,
+    Leaf(u8),
+    Node(u16),
+}
+
+///the codes of a table as a binary trie in a flat array, node 0 is the root
+#[derive(Debug, Clone)]
+pub(crate) struct CodeTrie {
+    pub(crate) nodes: Vec<[Child; 2]>,
+}
+
+impl CodeTrie {
+    pub(crate) fn new(table: &CodeTable) -> io::Result<Self> {
+        let mut codes: Vec<(u8, &HuffCode)> = table.iter().collect();
+        for (byte, code) in &codes {
+            if code.get_length() == 0 || code.get_length() > 128 {
+                return Err(invalid_data(format!(
+                    "invalid code length {} for byte {}",
+                    code.get_length(),
+                    byte
+                )));
+            }
+        }
+
+        //a code that starts with a shorter one is never reached, like in the payload
+        codes.sort_by_key(|(_, code)| code.get_length());
+
+        let mut trie = CodeTrie {
+            nodes: alloc::vec![[Child::Missing; 2]],
+        };
+
+        'codes: for (byte, code) in codes {
+            let length = code.get_length();
+            let bits = code.get_code();
+
+            //bits above the length can't be matched either
+            if length < 128 && bits >> length != 0 {
+                continue;
+            }
+
+            let mut node = 0;
+            for i in 0..length - 1 {
+                let bit = ((bits >> i) & 1) as usize;
+                node = match trie.nodes[node][bit] {
+                    Child::Node(next) => next as usize,
+                    Child::Leaf(_) => continue 'codes,
+                    Child::Missing => {
+                        //at most 128 nodes for each of the 256 codes
+                        let next = trie.nodes.len();
+                        trie.nodes.push([Child::Missing; 2]);
+                        trie.nodes[node][bit] = Child::Node(next as u16);
+                        next
+                    }
+                };
+            }
+
+            let bit = ((bits >> (length - 1)) & 1) as usize;
+            if let Child::Missing | Child::Leaf(_) = trie.nodes[node][bit] {
+                trie.nodes[node][bit] = Child::Leaf(byte);
+            }
+        }
+
+        Ok(trie)
+    }
 }
 
-#[cfg(test)]
+//most of them go through files
+#[cfg(all(test, feature = "fs"))]
 mod tests {
     use super::*;
+    use crate::{header::FLAG_STANDARD_TREE, LimitError};
 
     #[test]
     fn compress_bytes_test() {
@@ -319,13 +792,15 @@ mod tests {
                 compressed_bytes: comp,
                 compressed_last_byte_length: size,
                 frequencies,
+                options: CompressOptions::new(),
             }),
             _marker: PhantomData,
         };
 
+        let flags = 6 | FLAG_STANDARD_TREE;
         let output = [
-            0, 0, 0, 0, 0, 0, 0, 28, 6, 65, 0, 0, 0, 0, 0, 0, 0, 2, 67, 0, 0, 0, 0, 0, 0, 0, 1, 68,
-            0, 0, 0, 0, 0, 0, 0, 1,
+            0, 0, 0, 0, 0, 0, 0, 28, flags, 65, 0, 0, 0, 0, 0, 0, 0, 2, 67, 0, 0, 0, 0, 0, 0, 0, 1,
+            68, 0, 0, 0, 0, 0, 0, 0, 1,
         ];
 
         assert!(c.create_freq_header().eq(&output));
@@ -336,8 +811,301 @@ mod tests {
         let bytes = vec![b'A', b'A', b'C', b'D'];
         let frequencies = get_byte_frequencies(&bytes);
         let (comp, size) = CompressFile::new().compress_bytes(&bytes, &frequencies);
-        let decompressed = DecompressFile::new().decompress_bytes(&comp, &frequencies, size);
+
+        let mut header = Vec::new();
+        write_freq_header(&frequencies, size, &CompressOptions::new(), &mut header);
+        let header = FreqHeader::read(&mut &header[..], &DecompressOptions::new()).unwrap();
+
+        let decompressed = DecompressFile::new()
+            .decompress_bytes(&comp, &header)
+            .unwrap();
 
         assert_eq!(bytes, decompressed);
     }
+
+    fn temp_path(name: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("learning_huffman_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path.to_str().unwrap().to_string()
+    }
+
+    fn limit_error(options: DecompressOptions, file: &str) -> LimitError {
+        let err = match DecompressFile::new().options(options).decompress_freq(file) {
+            Ok(_) => panic!("limit not enforced"),
+            Err(err) => err,
+        };
+
+        *LimitError::from_io(&err).expect("not a LimitError")
+    }
+
+    #[test]
+    fn decompress_freq_limits_test() {
+        let compressed = temp_path("limits.huf");
+        CompressFile::new()
+            .compress("./test_files/135-0.txt")
+            .unwrap()
+            .output_freq(&compressed)
+            .unwrap();
+
+        assert!(matches!(
+            limit_error(DecompressOptions::new().max_output_size(1000), &compressed),
+            LimitError::OutputSize { limit: 1000, .. }
+        ));
+        assert!(matches!(
+            limit_error(DecompressOptions::new().max_ratio(1), &compressed),
+            LimitError::Ratio {
+                limit: 1,
+                actual: 1
+            }
+        ));
+        assert!(matches!(
+            limit_error(DecompressOptions::new().max_header_size(100), &compressed),
+            LimitError::HeaderSize { limit: 100, .. }
+        ));
+        assert!(matches!(
+            limit_error(DecompressOptions::new().max_memory(1 << 20), &compressed),
+            LimitError::Memory { limit: 1048576, .. }
+        ));
+
+        let options = DecompressOptions::new()
+            .max_output_size(1 << 30)
+            .max_ratio(2)
+            .max_header_size(2305)
+            .max_memory(1 << 30);
+        let decompressed = DecompressFile::new()
+            .options(options)
+            .decompress_freq(&compressed)
+            .unwrap();
+
+        assert_eq!(
+            decompressed.state.uncompressed_bytes,
+            std::fs::read("./test_files/135-0.txt").unwrap()
+        );
+
+        std::fs::remove_file(compressed).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn decompress_freq_pipe_test() {
+        use std::os::fd::AsRawFd;
+
+        let bytes = b"AAAAAAAB".repeat(1000);
+        let compressed = compress_to_vec(&bytes);
+
+        //a pipe's metadata has a size of 0, the ratio has to use the bytes read from it
+        let pipe = |compressed: &[u8]| {
+            let (reader, mut writer) = std::io::pipe().unwrap();
+            writer.write_all(compressed).unwrap();
+            drop(writer);
+            let path = format!("/dev/fd/{}", reader.as_raw_fd());
+            (reader, path)
+        };
+
+        let (_reader, path) = pipe(&compressed);
+        assert!(matches!(
+            limit_error(DecompressOptions::new().max_ratio(4), &path),
+            LimitError::Ratio {
+                limit: 4,
+                actual: 7
+            }
+        ));
+
+        let (_reader, path) = pipe(&compressed);
+        let decompressed = DecompressFile::new()
+            .options(DecompressOptions::new().max_ratio(10))
+            .decompress_freq(&path)
+            .unwrap();
+        assert_eq!(decompressed.state.uncompressed_bytes, bytes);
+    }
+
+    #[test]
+    fn compress_to_vec_test() {
+        let compressed = compress_to_vec(b"AACD");
+
+        assert_eq!(compressed.len(), 37);
+        assert_eq!(compressed[8], 6 | FLAG_STANDARD_TREE);
+        assert_eq!(compressed[36], 0b00011100);
+        assert_eq!(decompress_to_vec(&compressed).unwrap(), b"AACD");
+    }
+
+    #[test]
+    fn compress_into_test() {
+        let mut compressed = vec![1, 2, 3];
+        compress_into(b"AACD", &mut compressed);
+
+        assert_eq!(compressed[..3], [1, 2, 3]);
+        assert_eq!(compressed[3..], compress_to_vec(b"AACD"));
+    }
+
+    #[test]
+    fn compress_to_vec_matches_file_test() {
+        let compressed = temp_path("to_vec.huf");
+        CompressFile::new()
+            .compress("./test_files/135-0.txt")
+            .unwrap()
+            .output_freq(&compressed)
+            .unwrap();
+
+        let bytes = std::fs::read("./test_files/135-0.txt").unwrap();
+        let in_memory = compress_to_vec(&bytes);
+
+        assert!(in_memory == std::fs::read(&compressed).unwrap());
+        assert!(decompress_to_vec(&in_memory).unwrap() == bytes);
+
+        std::fs::remove_file(compressed).unwrap();
+    }
+
+    #[test]
+    fn decompress_to_vec_with_options_test() {
+        let compressed = compress_to_vec(b"AACD");
+        let options = DecompressOptions::new().max_output_size(3);
+        let err = decompress_to_vec_with_options(&compressed, &options).unwrap_err();
+
+        assert_eq!(
+            LimitError::from_io(&err),
+            Some(&LimitError::OutputSize {
+                limit: 3,
+                actual: 4
+            })
+        );
+    }
+
+    #[test]
+    fn decompress_legacy_tree_test() {
+        //written before tie-breaking was specified, its merged nodes tie differently
+        let compressed = [
+            0, 0, 0, 0, 0, 0, 0, 73, 4, 97, 0, 0, 0, 0, 0, 0, 0, 4, 98, 0, 0, 0, 0, 0, 0, 0, 2, 99,
+            0, 0, 0, 0, 0, 0, 0, 5, 100, 0, 0, 0, 0, 0, 0, 0, 2, 101, 0, 0, 0, 0, 0, 0, 0, 2, 102,
+            0, 0, 0, 0, 0, 0, 0, 2, 103, 0, 0, 0, 0, 0, 0, 0, 3, 104, 0, 0, 0, 0, 0, 0, 0, 3, 47,
+            227, 190, 59, 48, 117, 11, 78, 11,
+        ];
+        let bytes = b"bcehcabgadfehgcaahfdccg";
+
+        assert_eq!(decompress_to_vec(&compressed).unwrap(), bytes);
+        assert_ne!(compress_to_vec(bytes)[81..], compressed[81..]);
+    }
+
+    #[test]
+    fn compress_tree_mode_test() {
+        let bytes = b"bcehcabgadfehgcaahfdccg";
+
+        for mode in [TreeMode::Standard, TreeMode::Legacy] {
+            let options = CompressOptions::new().tree_mode(mode);
+            let compressed = compress_to_vec_with_options(bytes, &options);
+
+            assert_eq!(compressed[8] & !7, header_flags(mode, Coder::HUFFMAN));
+            assert_eq!(decompress_to_vec(&compressed).unwrap(), bytes);
+        }
+
+        //legacy files are still written the way old versions did
+        let options = CompressOptions::new().tree_mode(TreeMode::Legacy);
+        assert_eq!(
+            compress_to_vec_with_options(bytes, &options)[81..],
+            [47, 227, 190, 59, 48, 117, 11, 78, 11]
+        );
+    }
+
+    #[test]
+    fn compress_file_options_test() {
+        let compressed = temp_path("options.huf");
+        let options = CompressOptions::new().tree_mode(TreeMode::Legacy);
+        CompressFile::new()
+            .options(options)
+            .compress("./test_files/135-0.txt")
+            .unwrap()
+            .output_freq(&compressed)
+            .unwrap();
+
+        let bytes = std::fs::read("./test_files/135-0.txt").unwrap();
+
+        assert!(
+            compress_to_vec_with_options(&bytes, &options) == std::fs::read(&compressed).unwrap()
+        );
+
+        std::fs::remove_file(compressed).unwrap();
+    }
+
+    #[test]
+    fn compress_range_test() {
+        let bytes = std::fs::read("./test_files/135-0.txt").unwrap();
+        let options = CompressOptions::new().coder(Coder::RANGE);
+        let compressed = compress_to_vec_with_options(&bytes, &options);
+
+        assert!(compressed.len() < compress_to_vec(&bytes).len());
+        assert!(decompress_to_vec(&compressed).unwrap() == bytes);
+
+        let path = temp_path("range.huf");
+        std::fs::write(&path, &compressed).unwrap();
+        let decompressed = DecompressFile::new().decompress_freq(&path).unwrap();
+        assert!(decompressed.state.uncompressed_bytes == bytes);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn compress_ans_test() {
+        let bytes = std::fs::read("./test_files/135-0.txt").unwrap();
+        let options = CompressOptions::new().coder(Coder::ANS);
+        let compressed = compress_to_vec_with_options(&bytes, &options);
+        let header = FreqHeader::read(&mut &compressed[..], &DecompressOptions::new()).unwrap();
+
+        let huffman = compress_to_vec(&bytes);
+        let huffman_header =
+            FreqHeader::read(&mut &huffman[..], &DecompressOptions::new()).unwrap();
+
+        assert!(header.size() * 2 < huffman_header.size());
+        assert!(compressed.len() < huffman.len());
+        assert!(decompress_to_vec(&compressed).unwrap() == bytes);
+
+        let path = temp_path("ans.huf");
+        std::fs::write(&path, &compressed).unwrap();
+        let decompressed = DecompressFile::new().decompress_freq(&path).unwrap();
+        assert!(decompressed.state.uncompressed_bytes == bytes);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn compress_bwt_test() {
+        let bytes = std::fs::read("./test_files/135-0.txt").unwrap();
+        let options = CompressOptions::new().bwt(true);
+        let compressed = compress_to_vec_with_options(&bytes, &options);
+
+        //plain huffman needs more than 55%
+        assert!(compressed.len() * 3 < bytes.len());
+        assert_eq!(
+            compressed[8],
+            FLAG_BWT | FLAG_STANDARD_TREE | compressed[8] & 7
+        );
+        assert!(decompress_to_vec(&compressed).unwrap() == bytes);
+
+        let path = temp_path("bwt.huf");
+        std::fs::write(&path, &compressed).unwrap();
+        let decompressed = DecompressFile::new().decompress_freq(&path).unwrap();
+        assert!(decompressed.state.uncompressed_bytes == bytes);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn compress_file_bwt_test() {
+        let compressed = temp_path("bwt_file.huf");
+        let options = CompressOptions::new().coder(Coder::ANS).bwt(true);
+        CompressFile::new()
+            .options(options)
+            .compress("./README.md")
+            .unwrap()
+            .output_freq(&compressed)
+            .unwrap();
+
+        let bytes = std::fs::read("./README.md").unwrap();
+        let in_memory = compress_to_vec_with_options(&bytes, &options);
+
+        assert!(in_memory == std::fs::read(&compressed).unwrap());
+        assert!(decompress_to_vec(&in_memory).unwrap() == bytes);
+
+        std::fs::remove_file(compressed).unwrap();
+    }
 }
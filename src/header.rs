@@ -0,0 +1,390 @@
+use alloc::{boxed::Box, format, vec, vec::Vec};
+
+use crate::io::{self, Read};
+
+use crate::{
+    bwt::{bwt_untransform, bwt_untransformed_size},
+    Coder, DecompressOptions, FreqModel, TreeMode,
+};
+
+///no model is larger than a table of all 256 (byte (u8) | frequency (u64)) entries
+const MAX_TABLE_SIZE: u64 = 1 + 256 * (1 + 8);
+
+///the last byte size only needs the low 3 bits, the others are flags
+const LAST_BYTE_SIZE_MASK: u8 = 0x07;
+
+///tree is built with `TreeMode::Standard`, files written before tie-breaking
+///was specified don't have it and use `TreeMode::Legacy`
+///
+///versions before it read the whole byte as the last byte size, so they can't read files with it
+pub(crate) const FLAG_STANDARD_TREE: u8 = 0x08;
+
+///`EntropyCoder::id` of the payload's coder, tree flags are only used with coders that `uses_tree`
+///
+///its low 2 bits are at 0x60 and its high bit at 0x10, so ids 0 to 3 are stored as before 0x10 was used
+const CODER_MASK: u8 = 0x70;
+const CODER_LOW_SHIFT: u32 = 5;
+const CODER_HIGH: u8 = 0x10;
+
+///the payload decodes to the output of `bwt_transform`, with any coder
+pub(crate) const FLAG_BWT: u8 = 0x80;
+
+///Frequency table header in front of every compressed payload
+///
+///`table_size` (u64) | last byte size + flags (u8) | `table_size / 9` * (byte (u8) | frequency (u64))
+///
+///The entries are the model written by the header's coder, see `EntropyCoder::write_model`.
+///Everything is big endian. Only headers that the compressor could have
+///written are accepted, anything else is an [`io::ErrorKind::InvalidData`] error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FreqHeader {
+    model: FreqModel,
+    last_byte_size: u8,
+    tree_mode: TreeMode,
+    coder: Coder,
+    bwt: bool,
+    size: usize,
+}
+
+impl FreqHeader {
+    ///read and validate a header from the start of `reader`, leaving it at the payload
+    pub fn read<R: Read>(reader: &mut R, options: &DecompressOptions) -> io::Result<Self> {
+        let mut table_size = [0; 8];
+        reader.read_exact(&mut table_size)?;
+        let table_size = u64::from_be_bytes(table_size);
+
+        options.check_header_size(
+            usize::try_from(table_size)
+                .unwrap_or(usize::MAX)
+                .saturating_add(8),
+        )?;
+
+        let mut last_byte_size = [0];
+        reader.read_exact(&mut last_byte_size)?;
+        let bwt = last_byte_size[0] & FLAG_BWT != 0;
+        let flags = last_byte_size[0] & !LAST_BYTE_SIZE_MASK & !FLAG_BWT;
+        let last_byte_size = last_byte_size[0] & LAST_BYTE_SIZE_MASK;
+
+        let coder = Coder::from_id(coder_id(flags))
+            .ok_or_else(|| invalid_data(format!("unknown coder in header flags {flags:#04x}")))?;
+
+        //there is no tree to build for the other coders, so no tree flags either
+        let tree_mode = match (flags & !CODER_MASK, coder.uses_tree()) {
+            (0, true) => TreeMode::Legacy,
+            (FLAG_STANDARD_TREE, true) => TreeMode::Standard,
+            (0, false) => TreeMode::default(),
+            _ => return Err(invalid_data(format!("unknown header flags {flags:#04x}"))),
+        };
+
+        //only single stream huffman codes end in the middle of a byte
+        if coder != Coder::HUFFMAN && last_byte_size != 0 {
+            return Err(invalid_data(format!(
+                "last byte size {last_byte_size} for {coder} coded payload"
+            )));
+        }
+
+        if table_size == 0 || table_size > MAX_TABLE_SIZE {
+            return Err(invalid_data(format!("invalid table size {table_size}")));
+        }
+
+        let mut table = vec![0; table_size as usize - 1];
+        reader.read_exact(&mut table)?;
+        let model = coder.entropy_coder().read_model(&table)?;
+
+        Ok(FreqHeader {
+            model,
+            last_byte_size,
+            tree_mode,
+            coder,
+            bwt,
+            size: 8 + table_size as usize,
+        })
+    }
+
+    ///decode `payload`, which has to directly follow this header
+    pub fn decode(&self, payload: &[u8]) -> io::Result<Vec<u8>> {
+        self.decode_with_options(payload, &DecompressOptions::new())
+    }
+
+    ///like `decode`, enforcing the limits in `options` on sizes the header doesn't know
+    pub fn decode_with_options(
+        &self,
+        payload: &[u8],
+        options: &DecompressOptions,
+    ) -> io::Result<Vec<u8>> {
+        let decoded = self.coder.entropy_coder().decode(payload, self)?;
+
+        if !self.bwt {
+            return Ok(decoded);
+        }
+
+        //`total` is only the size of the transformed bytes
+        options.check(
+            bwt_untransformed_size(&decoded)?,
+            self.size.saturating_add(payload.len()),
+        )?;
+
+        bwt_untransform(&decoded)
+    }
+
+    ///frequencies from the model, normalized or not depending on the coder
+    pub fn frequencies(&self) -> &[usize; 256] {
+        self.model.frequencies()
+    }
+
+    pub fn model(&self) -> &FreqModel {
+        &self.model
+    }
+
+    pub fn last_byte_size(&self) -> u8 {
+        self.last_byte_size
+    }
+
+    ///how the tree has to be built to decode the payload
+    pub fn tree_mode(&self) -> TreeMode {
+        self.tree_mode
+    }
+
+    ///how the payload is coded
+    pub fn coder(&self) -> Coder {
+        self.coder
+    }
+
+    ///whether the payload decodes to bytes that still have to go through `bwt_untransform`
+    pub fn bwt(&self) -> bool {
+        self.bwt
+    }
+
+    ///number of bytes the header takes up, `table_size` included
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    ///number of bytes the payload decodes to, before `bwt_untransform`
+    pub fn total(&self) -> usize {
+        self.model.total()
+    }
+}
+
+///flag bits written for a payload coded with `coder` and, if it `uses_tree`, a tree built with `mode`
+pub(crate) fn header_flags(mode: TreeMode, coder: Coder) -> u8 {
+    if !coder.uses_tree() {
+        return coder_flags(coder.id());
+    }
+
+    let tree_flags = match mode {
+        TreeMode::Standard => FLAG_STANDARD_TREE,
+        //readable by versions that don't know about flags
+        TreeMode::Legacy => 0,
+    };
+
+    tree_flags | coder_flags(coder.id())
+}
+
+fn coder_flags(id: u8) -> u8 {
+    let high = if id & 0x04 != 0 { CODER_HIGH } else { 0 };
+    (id & 0x03) << CODER_LOW_SHIFT | high
+}
+
+fn coder_id(flags: u8) -> u8 {
+    (flags & !CODER_HIGH & CODER_MASK) >> CODER_LOW_SHIFT | (flags & CODER_HIGH) >> 2
+}
+
+pub(crate) fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn core::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //"AACD" as written by `CompressFile` before tie-breaking was specified
+    const AACD: [u8; 37] = [
+        0, 0, 0, 0, 0, 0, 0, 28, 6, 65, 0, 0, 0, 0, 0, 0, 0, 2, 67, 0, 0, 0, 0, 0, 0, 0, 1, 68, 0,
+        0, 0, 0, 0, 0, 0, 1, 0b00011100,
+    ];
+
+    fn read(bytes: &[u8]) -> io::Result<FreqHeader> {
+        FreqHeader::read(&mut &bytes[..], &DecompressOptions::new())
+    }
+
+    fn kind(result: io::Result<impl std::fmt::Debug>) -> io::ErrorKind {
+        result.unwrap_err().kind()
+    }
+
+    #[test]
+    fn read_test() {
+        let header = read(&AACD).unwrap();
+
+        assert_eq!(header.size(), 36);
+        assert_eq!(header.total(), 4);
+        assert_eq!(header.last_byte_size(), 6);
+        assert_eq!(header.tree_mode(), TreeMode::Legacy);
+        assert_eq!(header.frequencies()[b'A' as usize], 2);
+        assert_eq!(header.decode(&AACD[36..]).unwrap(), b"AACD");
+    }
+
+    #[test]
+    fn read_invalid_table_size_test() {
+        //used to underflow
+        let mut bytes = AACD;
+        bytes[7] = 0;
+        assert_eq!(kind(read(&bytes)), io::ErrorKind::InvalidData);
+
+        //used to loop until end of file
+        bytes[7] = 27;
+        assert_eq!(kind(read(&bytes)), io::ErrorKind::InvalidData);
+
+        bytes[0] = 0xff;
+        bytes[7] = 28;
+        assert_eq!(kind(read(&bytes)), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn read_invalid_entries_test() {
+        //huffman4 streams end on whole bytes
+        let mut bytes = AACD;
+        bytes[8] = 0x66;
+        assert_eq!(kind(read(&bytes)), io::ErrorKind::InvalidData);
+
+        let mut bytes = AACD;
+        bytes[18] = b'A';
+        assert_eq!(kind(read(&bytes)), io::ErrorKind::InvalidData);
+
+        let mut bytes = AACD;
+        bytes[17] = 0;
+        assert_eq!(kind(read(&bytes)), io::ErrorKind::InvalidData);
+
+        let mut bytes = AACD;
+        bytes[10..18].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert_eq!(kind(read(&bytes)), io::ErrorKind::InvalidData);
+
+        assert_eq!(kind(read(&AACD[..20])), io::ErrorKind::UnexpectedEof);
+        assert_eq!(kind(read(&[])), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn read_flags_test() {
+        let mut bytes = AACD;
+        bytes[8] = 6 | FLAG_STANDARD_TREE;
+        let header = read(&bytes).unwrap();
+
+        assert_eq!(header.last_byte_size(), 6);
+        assert_eq!(header.tree_mode(), TreeMode::Standard);
+        assert_eq!(header.decode(&bytes[36..]).unwrap(), b"AACD");
+
+        //0x10 is the high bit of the coder id, no coder has id 4
+        bytes[8] = 6 | 0x10;
+        assert_eq!(kind(read(&bytes)), io::ErrorKind::InvalidData);
+
+        bytes[8] = 6 | FLAG_STANDARD_TREE | 0x10;
+        assert_eq!(kind(read(&bytes)), io::ErrorKind::InvalidData);
+
+        //"AACD" isn't a transformed block
+        bytes[8] = 6 | FLAG_STANDARD_TREE | FLAG_BWT;
+        let header = read(&bytes).unwrap();
+        assert!(header.bwt());
+        assert!(header.decode(&bytes[36..]).is_err());
+
+        bytes[8] = header_flags(TreeMode::Standard, Coder::RANGE);
+        assert_eq!(read(&bytes).unwrap().coder(), Coder::RANGE);
+
+        bytes[8] = header_flags(TreeMode::Standard, Coder::RANGE) | FLAG_STANDARD_TREE;
+        assert_eq!(kind(read(&bytes)), io::ErrorKind::InvalidData);
+
+        bytes[8] = header_flags(TreeMode::Standard, Coder::RANGE) | 6;
+        assert_eq!(kind(read(&bytes)), io::ErrorKind::InvalidData);
+
+        //huffman4 keeps the tree flags
+        bytes[8] = header_flags(TreeMode::Legacy, Coder::HUFFMAN4);
+        let header = read(&bytes).unwrap();
+        assert_eq!(header.coder(), Coder::HUFFMAN4);
+        assert_eq!(header.tree_mode(), TreeMode::Legacy);
+    }
+
+    #[test]
+    fn coder_flags_test() {
+        //ids below 4 keep the flags they were written with before 0x10 was used
+        assert_eq!(coder_flags(Coder::RANGE.id()), 0x20);
+        assert_eq!(coder_flags(Coder::HUFFMAN4.id()), 0x60);
+
+        for id in 0..8 {
+            assert_eq!(coder_flags(id) & !CODER_MASK, 0);
+            assert_eq!(coder_id(coder_flags(id) | FLAG_STANDARD_TREE), id);
+        }
+    }
+
+    #[test]
+    fn read_ans_test() {
+        let compressed = crate::compress_to_vec_with_options(
+            b"AACD",
+            &crate::CompressOptions::new().coder(Coder::ANS),
+        );
+        let header = read(&compressed).unwrap();
+
+        //3 bytes for every entry instead of 9
+        assert_eq!(header.size(), 8 + 1 + 9 + 3 * 3);
+        assert_eq!(header.coder(), Coder::ANS);
+        assert_eq!(header.total(), 4);
+        assert_eq!(header.frequencies()[b'A' as usize], 16);
+        assert_eq!(header.decode(&compressed[27..]).unwrap(), b"AACD");
+
+        let mut bytes = compressed.clone();
+        bytes[7] = 20;
+        assert_eq!(kind(read(&bytes)), io::ErrorKind::InvalidData);
+
+        let mut bytes = compressed.clone();
+        bytes[6] = 0x10;
+        assert_eq!(kind(read(&bytes)), io::ErrorKind::InvalidData);
+
+        let mut bytes = compressed.clone();
+        bytes[9] = 4;
+        assert_eq!(kind(read(&bytes)), io::ErrorKind::InvalidData);
+
+        assert_eq!(kind(read(&compressed[..20])), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn decode_bwt_limits_test() {
+        let bytes = [b'A'; 10_000];
+        let options = crate::CompressOptions::new().bwt(true);
+        let compressed = crate::compress_to_vec_with_options(&bytes, &options);
+        let header = read(&compressed).unwrap();
+        let payload = &compressed[header.size()..];
+
+        //the transformed bytes are far fewer than the output
+        assert!(header.total() < 100);
+        assert_eq!(header.decode(payload).unwrap(), bytes);
+
+        let options = DecompressOptions::new().max_output_size(9_999);
+        let err = header.decode_with_options(payload, &options).unwrap_err();
+        assert!(crate::LimitError::from_io(&err).is_some());
+    }
+
+    #[test]
+    fn decode_truncated_payload_test() {
+        let header = read(&AACD).unwrap();
+
+        assert_eq!(kind(header.decode(&[])), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn decode_single_symbol_test() {
+        let mut bytes = vec![0, 0, 0, 0, 0, 0, 0, 10, 0, b'Z'];
+        bytes.extend_from_slice(&3u64.to_be_bytes());
+        let header = read(&bytes).unwrap();
+
+        assert_eq!(header.decode(&[]).unwrap(), b"ZZZ");
+    }
+
+    #[test]
+    fn read_header_size_limit_test() {
+        let options = DecompressOptions::new().max_header_size(20);
+        let err = FreqHeader::read(&mut &AACD[..], &options).unwrap_err();
+
+        assert!(crate::LimitError::from_io(&err).is_some());
+    }
+}
@@ -1,7 +1,7 @@
-use std::{
-    cmp::Ordering,
-    collections::{BinaryHeap, HashMap},
-};
+use alloc::{boxed::Box, collections::BinaryHeap, format, string::String, vec, vec::Vec};
+use core::{cmp::Ordering, fmt, ops::Index};
+
+use crate::io;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct HuffNode {
@@ -28,8 +28,100 @@ impl Ord for HuffNode {
     }
 }
 
+///Heap entry while building a tree, lower `tie` comes out first on equal weights
+#[derive(Debug, PartialEq, Eq)]
+struct Pending<N> {
+    weight: usize,
+    tie: usize,
+    node: N,
+}
+
+impl<N: Eq> PartialOrd for Pending<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N: Eq> Ord for Pending<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .weight
+            .cmp(&self.weight)
+            .then(other.tie.cmp(&self.tie))
+    }
+}
+
+///How ties between equal weights are broken while building a tree
+///
+///Every mode gives an optimal code, they only differ in which one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TreeMode {
+    ///see `HuffNode::from_frequencies`. Taking leaves before merged nodes is the
+    ///minimum-variance order: of all optimal codes this one has the smallest
+    ///variance and maximum of code lengths. Versions before tie-breaking was
+    ///specified can't read files written with it
+    #[default]
+    Standard,
+    ///order of versions before tie-breaking was specified, which depends on
+    ///`BinaryHeap` internals. Use it to read their files or write files they can read
+    Legacy,
+}
+
+impl core::str::FromStr for TreeMode {
+    type Err = io::Error;
+
+    fn from_str(mode: &str) -> io::Result<Self> {
+        match mode.to_ascii_lowercase().as_str() {
+            "standard" => Ok(TreeMode::Standard),
+            "legacy" => Ok(TreeMode::Legacy),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown tree mode {mode}"),
+            )),
+        }
+    }
+}
+
 impl HuffNode {
+    ///Build the tree for `frequencies`, `None` if they are all 0
+    ///
+    ///The tree only depends on `frequencies`, so it can be rebuilt exactly from the header:
+    ///* every byte with a frequency other than 0 becomes a leaf
+    ///* the two lightest nodes are merged until one is left, the first one
+    ///  taken becomes the left (`0`) branch, the second the right (`1`) branch
+    ///* on equal weights leaves are taken before merged nodes, leaves with the
+    ///  higher byte first and merged nodes in the order they were created
+    ///
+    ///Taking leaves first keeps merged nodes for last, which gives the shortest longest code, see `TreeMode::Standard`
     pub fn from_frequencies(frequencies: &[usize; 256]) -> Option<Box<Self>> {
+        Self::from_frequencies_with(frequencies, TreeMode::Standard)
+    }
+
+    pub fn from_frequencies_with(frequencies: &[usize; 256], mode: TreeMode) -> Option<Box<Self>> {
+        if mode == TreeMode::Legacy {
+            return Self::from_frequencies_legacy(frequencies);
+        }
+
+        let leaves = frequencies
+            .iter()
+            .enumerate()
+            .map(|(i, frequency)| HuffNode {
+                weight: *frequency,
+                byte: Some(i as u8),
+                left: None,
+                right: None,
+            });
+
+        merge_lightest(frequencies, leaves, |left, right| HuffNode {
+            weight: left.weight + right.weight,
+            byte: None,
+            left: Some(Box::new(left)),
+            right: Some(Box::new(right)),
+        })
+        .map(Box::new)
+    }
+
+    fn from_frequencies_legacy(frequencies: &[usize; 256]) -> Option<Box<Self>> {
         let mut heap = BinaryHeap::new();
 
         for (i, frequency) in frequencies.iter().enumerate() {
@@ -61,31 +153,131 @@ impl HuffNode {
 
         heap.pop().map(Box::new)
     }
+
+    pub fn get_weight(&self) -> usize {
+        self.weight
+    }
+
+    ///`None` for internal nodes
+    pub fn get_byte(&self) -> Option<u8> {
+        self.byte
+    }
+
+    ///the `0` branch
+    pub fn get_left(&self) -> Option<&HuffNode> {
+        self.left.as_deref()
+    }
+
+    ///the `1` branch
+    pub fn get_right(&self) -> Option<&HuffNode> {
+        self.right.as_deref()
+    }
+}
+
+///merge the two lightest nodes until one is left, `leaves` has one node for every frequency
+///and those with frequency 0 are skipped
+///
+///the first node taken is passed to `merge` first, see `HuffNode::from_frequencies` for the order
+fn merge_lightest<N: Eq>(
+    frequencies: &[usize],
+    leaves: impl Iterator<Item = N>,
+    mut merge: impl FnMut(N, N) -> N,
+) -> Option<N> {
+    let mut heap = BinaryHeap::new();
+
+    for (i, (frequency, node)) in frequencies.iter().zip(leaves).enumerate() {
+        if *frequency != 0 {
+            heap.push(Pending {
+                weight: *frequency,
+                tie: frequencies.len() - 1 - i,
+                node,
+            });
+        }
+    }
+
+    let mut merged = 0;
+
+    while heap.len() > 1 {
+        if let (Some(left), Some(right)) = (heap.pop(), heap.pop()) {
+            heap.push(Pending {
+                weight: left.weight + right.weight,
+                tie: frequencies.len() + merged,
+                node: merge(left.node, right.node),
+            });
+            merged += 1;
+        }
+    }
+
+    heap.pop().map(|pending| pending.node)
+}
+
+///code length of every symbol for an alphabet of any size, 0 for those with frequency 0
+///
+///the lengths of the tree `HuffNode::from_frequencies` builds for 256 byte frequencies
+pub fn code_lengths(frequencies: &[usize]) -> Vec<u16> {
+    let mut lengths = vec![0; frequencies.len()];
+
+    merge_lightest(
+        frequencies,
+        (0..frequencies.len()).map(|i| vec![i]),
+        |mut left, right| {
+            left.extend(right);
+            for symbol in &left {
+                lengths[*symbol] += 1;
+            }
+            left
+        },
+    );
+
+    lengths
 }
 
+#[derive(Clone, PartialEq, Eq)]
 pub struct HuffCode {
     weight: usize,
     length: u16,
     code: u128,
 }
 
-impl std::fmt::Debug for HuffCode {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl fmt::Debug for HuffCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("HuffCode")
             .field("weight", &format_args!("{}", &self.weight))
             .field("length", &format_args!("{}", &self.length))
-            .field("code", &format_args!("{:08b}", &self.code))
+            .field("code", &format_args!("{}", &self.to_bit_string()))
             .finish()
     }
 }
 
 impl HuffCode {
-    fn generate_codes(
-        node: &Option<Box<HuffNode>>,
-        table: &mut HashMap<u8, HuffCode>,
-        code: u128,
-        shift: u16,
-    ) {
+    ///`code` holds the first bit to be written in its lowest bit
+    pub fn new(weight: usize, length: u16, code: u128) -> Self {
+        HuffCode {
+            weight,
+            length,
+            code,
+        }
+    }
+
+    ///inverse of `to_bit_string`, `None` if `bits` isn't made of up to 128 `0`s and `1`s
+    pub fn from_bit_string(weight: usize, bits: &str) -> Option<Self> {
+        if bits.len() > 128 {
+            return None;
+        }
+
+        let mut code = 0;
+        for (i, bit) in bits.chars().enumerate() {
+            match bit {
+                '0' => {}
+                '1' => code |= 1 << i,
+                _ => return None,
+            }
+        }
+
+        Some(HuffCode::new(weight, bits.len() as u16, code))
+    }
+
+    fn generate_codes(node: &Option<Box<HuffNode>>, table: &mut CodeTable, code: u128, shift: u16) {
         if let Some(node) = node {
             if let Some(byte) = node.byte {
                 table.insert(
@@ -107,8 +299,8 @@ impl HuffCode {
         }
     }
 
-    pub fn from_tree(node: &Option<Box<HuffNode>>) -> HashMap<u8, Self> {
-        let mut lookup_table = HashMap::new();
+    pub fn from_tree(node: &Option<Box<HuffNode>>) -> CodeTable {
+        let mut lookup_table = CodeTable::new();
         Self::generate_codes(node, &mut lookup_table, 0, 0);
         lookup_table
     }
@@ -120,17 +312,177 @@ impl HuffCode {
     pub fn get_length(&self) -> u16 {
         self.length
     }
+
+    pub fn get_weight(&self) -> usize {
+        self.weight
+    }
+
+    ///code bits in the order they are written, the first bit is the lowest bit of `code`
+    pub fn to_bit_string(&self) -> String {
+        (0..self.length)
+            .map(|i| if (self.code >> i) & 1 == 1 { '1' } else { '0' })
+            .collect()
+    }
+}
+
+///The code of every byte that has one, in an array indexed by the byte
+#[derive(Clone, PartialEq, Eq)]
+pub struct CodeTable {
+    codes: Box<[Option<HuffCode>; 256]>,
+    len: usize,
+}
+
+impl CodeTable {
+    pub fn new() -> Self {
+        CodeTable {
+            codes: Box::new(core::array::from_fn(|_| None)),
+            len: 0,
+        }
+    }
+
+    pub fn get(&self, byte: u8) -> Option<&HuffCode> {
+        self.codes[byte as usize].as_ref()
+    }
+
+    ///returns the code `byte` had before
+    pub fn insert(&mut self, byte: u8, code: HuffCode) -> Option<HuffCode> {
+        let old = self.codes[byte as usize].replace(code);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    ///number of bytes with a code
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    ///bytes with a code and their code, lowest byte first
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &HuffCode)> {
+        (0..=u8::MAX)
+            .zip(self.codes.iter())
+            .filter_map(|(byte, code)| Some((byte, code.as_ref()?)))
+    }
+}
+
+impl Default for CodeTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for CodeTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+///panics if `byte` has no code
+impl Index<u8> for CodeTable {
+    type Output = HuffCode;
+
+    fn index(&self, byte: u8) -> &HuffCode {
+        self.get(byte).expect("byte has no code")
+    }
+}
+
+impl FromIterator<(u8, HuffCode)> for CodeTable {
+    fn from_iter<I: IntoIterator<Item = (u8, HuffCode)>>(iter: I) -> Self {
+        let mut table = CodeTable::new();
+        for (byte, code) in iter {
+            table.insert(byte, code);
+        }
+        table
+    }
 }
 
+///how often every byte occurs in `bytes`
+///
+///with the `parallel` feature, inputs of at least `PARALLEL_MIN_SIZE` bytes are counted
+///on every available thread
 pub fn get_byte_frequencies(bytes: &[u8]) -> [usize; 256] {
+    #[cfg(feature = "parallel")]
+    if bytes.len() >= PARALLEL_MIN_SIZE {
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        return get_byte_frequencies_parallel(bytes, threads);
+    }
+
     let mut frequencies = [0; 256];
-    for byte in bytes {
-        frequencies[*byte as usize] += 1;
+    //the counts are u32, a block is small enough for none of them to overflow
+    for block in bytes.chunks(COUNT_BLOCK_SIZE) {
+        count_block(block, &mut frequencies);
     }
 
     frequencies
 }
 
+///the smallest input `get_byte_frequencies` splits between threads with the `parallel` feature
+#[cfg(feature = "parallel")]
+pub const PARALLEL_MIN_SIZE: usize = 32 << 20;
+
+const COUNT_BLOCK_SIZE: usize = 1 << 30;
+
+///add the bytes of `block` to `frequencies`
+///
+///Every byte of a word goes to its own table, so a run of the same byte doesn't have to
+///wait for the previous increment to be stored before it can load the count again
+fn count_block(block: &[u8], frequencies: &mut [usize; 256]) {
+    let mut tables = [[0u32; 256]; 8];
+
+    let mut words = block.chunks_exact(8);
+    for word in &mut words {
+        let word = u64::from_le_bytes(word.try_into().unwrap());
+        for (i, table) in tables.iter_mut().enumerate() {
+            table[(word >> (8 * i)) as u8 as usize] += 1;
+        }
+    }
+    for byte in words.remainder() {
+        tables[0][*byte as usize] += 1;
+    }
+
+    for (byte, frequency) in frequencies.iter_mut().enumerate() {
+        *frequency += tables
+            .iter()
+            .map(|table| table[byte] as usize)
+            .sum::<usize>();
+    }
+}
+
+///like `get_byte_frequencies`, counting `threads` parts of `bytes` at the same time
+#[cfg(feature = "std")]
+pub fn get_byte_frequencies_parallel(bytes: &[u8], threads: usize) -> [usize; 256] {
+    let part_size = bytes.len().div_ceil(threads.max(1)).max(1);
+
+    std::thread::scope(|scope| {
+        let parts: Vec<_> = bytes
+            .chunks(part_size)
+            .map(|part| {
+                scope.spawn(|| {
+                    let mut frequencies = [0; 256];
+                    for block in part.chunks(COUNT_BLOCK_SIZE) {
+                        count_block(block, &mut frequencies);
+                    }
+                    frequencies
+                })
+            })
+            .collect();
+
+        let mut frequencies = [0; 256];
+        for part in parts {
+            let part = part.join().expect("counting thread panicked");
+            for (frequency, count) in frequencies.iter_mut().zip(part) {
+                *frequency += count;
+            }
+        }
+        frequencies
+    })
+}
+
 #[allow(non_snake_case)]
 #[cfg(test)]
 mod test {
@@ -142,6 +494,37 @@ mod test {
         assert!(get_byte_frequencies(&bytes)[b'A' as usize] == 2);
     }
 
+    #[test]
+    fn get_byte_frequencies_blocks_test() {
+        let bytes = std::fs::read("./test_files/135-0.txt").unwrap();
+        let mut expected = [0; 256];
+        for byte in &bytes {
+            expected[*byte as usize] += 1;
+        }
+
+        //every length around a whole word, and runs
+        for end in bytes.len() - 17..=bytes.len() {
+            let mut expected = expected;
+            for byte in &bytes[end..] {
+                expected[*byte as usize] -= 1;
+            }
+            assert_eq!(get_byte_frequencies(&bytes[..end]), expected);
+        }
+        assert_eq!(get_byte_frequencies(&[9; 1001])[9], 1001);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn get_byte_frequencies_parallel_test() {
+        let bytes = std::fs::read("./test_files/135-0.txt").unwrap();
+        let expected = get_byte_frequencies(&bytes);
+
+        for threads in [0, 1, 3, 8] {
+            assert_eq!(get_byte_frequencies_parallel(&bytes, threads), expected);
+        }
+        assert_eq!(get_byte_frequencies_parallel(&[], 4), [0; 256]);
+    }
+
     #[test]
     fn HuffNode_from_frequencies_test() {
         let bytes = vec![b'A', b'A', b'C', b'D'];
@@ -172,13 +555,200 @@ mod test {
 
         let table = HuffCode::from_tree(&node);
 
-        assert_eq!(table.get(&b'A').unwrap().length, 1);
-        assert_eq!(table.get(&b'C').unwrap().length, 2);
-        assert_eq!(table.get(&b'D').unwrap().length, 2);
+        assert_eq!(table.get(b'A').unwrap().length, 1);
+        assert_eq!(table.get(b'C').unwrap().length, 2);
+        assert_eq!(table.get(b'D').unwrap().length, 2);
+
+        assert_eq!(table.get(b'A').unwrap().code, 0);
+        assert_eq!(table.get(b'C').unwrap().code, 0b00000011);
+        assert_eq!(table.get(b'D').unwrap().code, 0b00000001);
+    }
+
+    #[test]
+    fn CodeTable_test() {
+        let mut table = CodeTable::new();
+        assert!(table.is_empty());
+
+        assert_eq!(table.insert(b'b', HuffCode::new(1, 1, 1)), None);
+        assert_eq!(table.insert(b'a', HuffCode::new(2, 1, 0)), None);
+        assert_eq!(
+            table.insert(b'b', HuffCode::new(3, 1, 1)),
+            Some(HuffCode::new(1, 1, 1))
+        );
+
+        assert_eq!(table.len(), 2);
+        assert_eq!(table[b'b'].weight, 3);
+        assert!(table.get(b'c').is_none());
+
+        let bytes: Vec<u8> = table.iter().map(|(byte, _)| byte).collect();
+        assert_eq!(bytes, b"ab");
+        let copy: CodeTable = table
+            .iter()
+            .map(|(byte, code)| (byte, code.clone()))
+            .collect();
+        assert!(copy == table);
+    }
+
+    #[test]
+    fn HuffCode_bit_string() {
+        let code = HuffCode::new(3, 4, 0b0011);
+
+        assert_eq!(code.to_bit_string(), "1100");
+        assert_eq!(HuffCode::from_bit_string(3, "1100"), Some(code));
+        assert_eq!(HuffCode::from_bit_string(3, "12"), None);
+        assert_eq!(HuffCode::from_bit_string(3, &"0".repeat(129)), None);
+
+        //long codes aren't cut off
+        let long = HuffCode::from_bit_string(1, &"01".repeat(50)).unwrap();
+        assert!(format!("{:?}", long).contains(&"01".repeat(50)));
+    }
+
+    fn codes(bytes: &[u8]) -> Vec<(u8, String)> {
+        let table = HuffCode::from_tree(&HuffNode::from_frequencies(&get_byte_frequencies(bytes)));
+        let mut codes: Vec<(u8, String)> = table
+            .iter()
+            .map(|(byte, code)| (byte, code.to_bit_string()))
+            .collect();
+        codes.sort();
+        codes
+    }
+
+    fn expected(codes: &[(u8, &str)]) -> Vec<(u8, String)> {
+        codes
+            .iter()
+            .map(|(byte, code)| (*byte, code.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn HuffNode_ties_between_leaves() {
+        //higher byte first
+        assert_eq!(
+            codes(b"ABCD"),
+            expected(&[(b'A', "11"), (b'B', "10"), (b'C', "01"), (b'D', "00")])
+        );
+    }
+
+    #[test]
+    fn HuffNode_ties_between_leaf_and_merged() {
+        //C+B weighs as much as A, A is taken first
+        assert_eq!(
+            codes(b"AABC"),
+            expected(&[(b'A', "0"), (b'B', "11"), (b'C', "10")])
+        );
+    }
+
+    #[test]
+    fn HuffNode_ties_between_merged() {
+        //four merged nodes of weight 2, taken in the order they were made
+        assert_eq!(
+            codes(b"ABCDEFGH"),
+            expected(&[
+                (b'A', "111"),
+                (b'B', "110"),
+                (b'C', "101"),
+                (b'D', "100"),
+                (b'E', "011"),
+                (b'F', "010"),
+                (b'G', "001"),
+                (b'H', "000"),
+            ])
+        );
+
+        //H+G, F+E, D+C (2), then B (2) before the merged ones: B+(H+G) (4),
+        //(F+E)+(D+C) (4), both of them (8) and at last A (5) with everything else
+        assert_eq!(
+            codes(b"AAAAABBCDEFGH"),
+            expected(&[
+                (b'A', "0"),
+                (b'B', "100"),
+                (b'C', "1111"),
+                (b'D', "1110"),
+                (b'E', "1101"),
+                (b'F', "1100"),
+                (b'G', "1011"),
+                (b'H', "1010"),
+            ])
+        );
+    }
+
+    fn lengths(frequencies: &[usize], mode: TreeMode) -> Vec<u16> {
+        let mut all = [0; 256];
+        all[..frequencies.len()].copy_from_slice(frequencies);
+        let table = HuffCode::from_tree(&HuffNode::from_frequencies_with(&all, mode));
+
+        (0..frequencies.len())
+            .map(|byte| table[byte as u8].length)
+            .collect()
+    }
+
+    fn total_length(frequencies: &[usize], lengths: &[u16]) -> usize {
+        frequencies
+            .iter()
+            .zip(lengths)
+            .map(|(freq, length)| freq * *length as usize)
+            .sum()
+    }
+
+    #[test]
+    fn HuffNode_standard_minimum_variance() {
+        let cases: [(&[usize], &[u16]); 3] = [
+            (
+                &[2, 4, 1, 2, 2, 2, 1, 1, 1, 2, 3],
+                &[3, 3, 4, 3, 3, 4, 4, 4, 4, 4, 3],
+            ),
+            (
+                &[1, 3, 1, 1, 4, 1, 2, 2, 2, 1, 1, 1, 1],
+                &[4, 3, 4, 4, 3, 4, 3, 4, 4, 4, 4, 4, 4],
+            ),
+            (
+                &[2, 2, 3, 2, 3, 4, 4, 1, 1, 1, 1],
+                &[3, 4, 3, 4, 3, 3, 3, 4, 4, 4, 4],
+            ),
+        ];
+
+        for (frequencies, standard) in cases {
+            let minimum = lengths(frequencies, TreeMode::Standard);
+            assert_eq!(minimum, standard);
+
+            //the legacy order is whatever `BinaryHeap` does, it is only as good or worse
+            let legacy = lengths(frequencies, TreeMode::Legacy);
+            assert_eq!(
+                total_length(frequencies, &legacy),
+                total_length(frequencies, &minimum)
+            );
+            assert!(legacy.iter().max() >= minimum.iter().max());
+        }
+    }
+
+    #[test]
+    fn HuffNode_standard_ties() {
+        //(H+G) and (F+E) are merged before B, but go after it as leaves come first
+        assert_eq!(
+            lengths(&[5, 2, 1, 1, 1, 1, 1, 1], TreeMode::Standard),
+            [1, 3, 4, 4, 4, 4, 4, 4]
+        );
+    }
 
-        assert_eq!(table.get(&b'A').unwrap().code, 0);
-        assert_eq!(table.get(&b'C').unwrap().code, 0b00000011);
-        assert_eq!(table.get(&b'D').unwrap().code, 0b00000001);
+    #[test]
+    fn code_lengths_test() {
+        let frequencies = [2, 4, 1, 2, 2, 2, 1, 1, 1, 2, 3];
+        let mut all = [0; 256];
+        all[..frequencies.len()].copy_from_slice(&frequencies);
+
+        assert_eq!(
+            code_lengths(&all)[..frequencies.len()],
+            lengths(&frequencies, TreeMode::Standard)
+        );
+        assert!(code_lengths(&all)[frequencies.len()..]
+            .iter()
+            .all(|l| *l == 0));
+
+        //more symbols than bytes
+        let lengths = code_lengths(&[1; 258]);
+        assert_eq!(lengths.iter().filter(|l| **l == 9).count(), 4);
+        assert_eq!(lengths.iter().filter(|l| **l == 8).count(), 254);
+        assert_eq!(code_lengths(&[0, 7, 0]), [0, 0, 0]);
     }
 
     #[test]
@@ -0,0 +1,373 @@
+use alloc::{format, vec, vec::Vec};
+
+use crate::io;
+
+use crate::{
+    fileops::{code_array, repeat_byte, Child, CodeTrie, CodeWriter},
+    header::invalid_data,
+    CodeTable, HuffCode, HuffNode, TreeMode,
+};
+
+//Huffman codes split round-robin between 4 streams, like zstd's Huff0:
+//byte i of the input is coded in stream i % 4, so the decoder can keep 4 codes in flight
+//instead of waiting for the length of one before it can look at the next.
+//
+//jump table: size of streams 0 to 2 (u64, big endian) | stream 0 | stream 1 | stream 2 | stream 3
+//
+//Every stream is written like `encode_with_table` and padded to a whole byte.
+
+///number of streams the codes are split between
+pub const STREAMS: usize = 4;
+
+///size of the jump table in front of the streams
+const JUMP_TABLE_SIZE: usize = (STREAMS - 1) * 8;
+
+///bits decoded with one lookup, longer codes walk the trie from there
+const LOOKUP_BITS: u32 = 11;
+
+///huffman codes for `uncompressed_bytes` in 4 streams, see `EntropyCoder::encode`
+pub(crate) fn encode_huffman4(
+    uncompressed_bytes: &[u8],
+    frequencies: &[usize; 256],
+    tree_mode: TreeMode,
+    compressed_bytes: &mut Vec<u8>,
+) -> io::Result<()> {
+    let table = HuffCode::from_tree(&HuffNode::from_frequencies_with(frequencies, tree_mode));
+
+    //a lone symbol has a code of length 0, nothing is written for it
+    if table.len() <= 1 {
+        return Ok(());
+    }
+
+    encode_interleaved(uncompressed_bytes, &table, compressed_bytes)
+}
+
+///decode a payload written by `encode_huffman4`
+///
+///never panics or outputs more than `total` bytes, whatever `bytes` contains
+pub(crate) fn decode_huffman4(
+    bytes: &[u8],
+    frequencies: &[usize; 256],
+    total: usize,
+    tree_mode: TreeMode,
+) -> io::Result<Vec<u8>> {
+    let table = HuffCode::from_tree(&HuffNode::from_frequencies_with(frequencies, tree_mode));
+
+    match table.len() {
+        0 => Ok(Vec::new()),
+        1 => repeat_byte(table.iter().next().unwrap().0, total),
+        _ => decode_interleaved(bytes, &table, total),
+    }
+}
+
+///append a jump table and the codes from `table` for `uncompressed_bytes` in 4 streams to `compressed_bytes`.
+///A byte without a code is an error, `compressed_bytes` is left as it was then
+///
+///this is only the payload, the table and the number of bytes have to be stored separately
+pub fn encode_interleaved(
+    uncompressed_bytes: &[u8],
+    table: &CodeTable,
+    compressed_bytes: &mut Vec<u8>,
+) -> io::Result<()> {
+    let codes = code_array(table);
+    let mut streams: [Vec<u8>; STREAMS] = Default::default();
+
+    let mut writers = streams.each_mut().map(CodeWriter::new);
+    for writer in &mut writers {
+        writer
+            .output
+            .reserve(uncompressed_bytes.len() / 8 / STREAMS);
+    }
+
+    let mut chunks = uncompressed_bytes.chunks_exact(STREAMS);
+    for chunk in &mut chunks {
+        for (writer, byte) in writers.iter_mut().zip(chunk) {
+            writer.put_byte(*byte, &codes, table)?;
+        }
+    }
+    for (writer, byte) in writers.iter_mut().zip(chunks.remainder()) {
+        writer.put_byte(*byte, &codes, table)?;
+    }
+
+    for writer in writers {
+        writer.finish();
+    }
+
+    for stream in &streams[..STREAMS - 1] {
+        compressed_bytes.extend_from_slice(&(stream.len() as u64).to_be_bytes());
+    }
+    for stream in &streams {
+        compressed_bytes.extend_from_slice(stream);
+    }
+
+    Ok(())
+}
+
+///decode the `total` bytes of a payload written by `encode_interleaved` with the same `table`
+///
+///the streams have to end on the byte their last code is in
+pub fn decode_interleaved(bytes: &[u8], table: &CodeTable, total: usize) -> io::Result<Vec<u8>> {
+    let streams = split_streams(bytes)?;
+    let lookup = CodeLookup::new(table)?;
+
+    //every code is at least a bit long, don't trust `total` any further than that
+    for (i, stream) in streams.iter().enumerate() {
+        let count = total / STREAMS + usize::from(i < total % STREAMS);
+        if count > stream.len().saturating_mul(8) {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("stream {i} is too short for {count} bytes"),
+            ));
+        }
+    }
+
+    let mut readers = streams.map(BitReader::new);
+    let mut decompressed_bytes = vec![0; total];
+
+    //the 4 lookups don't depend on each other, so they can run at the same time
+    let mut chunks = decompressed_bytes.chunks_exact_mut(STREAMS);
+    for chunk in &mut chunks {
+        let [a, b, c, d] = &mut readers;
+        chunk.copy_from_slice(&[
+            lookup.decode(a)?,
+            lookup.decode(b)?,
+            lookup.decode(c)?,
+            lookup.decode(d)?,
+        ]);
+    }
+    for (byte, reader) in chunks.into_remainder().iter_mut().zip(&mut readers) {
+        *byte = lookup.decode(reader)?;
+    }
+
+    for (i, reader) in readers.iter().enumerate() {
+        let used = reader.position.div_ceil(8);
+        if used > reader.bytes.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("stream {i} ended in the middle of a code"),
+            ));
+        }
+        if used < reader.bytes.len() {
+            return Err(invalid_data(format!(
+                "stream {i} has bytes after its last code"
+            )));
+        }
+    }
+
+    Ok(decompressed_bytes)
+}
+
+///the streams after the jump table at the start of `bytes`
+fn split_streams(bytes: &[u8]) -> io::Result<[&[u8]; STREAMS]> {
+    if bytes.len() < JUMP_TABLE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "payload ended in the jump table",
+        ));
+    }
+
+    let (jump_table, mut rest) = bytes.split_at(JUMP_TABLE_SIZE);
+    let mut streams = [&[][..]; STREAMS];
+
+    for (stream, size) in streams.iter_mut().zip(jump_table.chunks_exact(8)) {
+        let mut buffer = [0; 8];
+        buffer.copy_from_slice(size);
+        let size = u64::from_be_bytes(buffer);
+
+        match usize::try_from(size) {
+            Ok(size) if size <= rest.len() => (*stream, rest) = rest.split_at(size),
+            _ => {
+                return Err(invalid_data(format!(
+                    "stream size {size} is larger than the payload"
+                )))
+            }
+        }
+    }
+    streams[STREAMS - 1] = rest;
+
+    Ok(streams)
+}
+
+///Reads a stream lowest bit first, past its end it reads 0s
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    ///bits read so far
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, position: 0 }
+    }
+
+    ///at least the next 57 bits, without reading them
+    #[inline(always)]
+    fn peek(&self) -> u64 {
+        let index = self.position / 8;
+
+        let word = match self.bytes.get(index..index + 8) {
+            Some(word) => u64::from_le_bytes(word.try_into().unwrap()),
+            None => {
+                let rest = self.bytes.get(index..).unwrap_or_default();
+                let mut word = [0; 8];
+                word[..rest.len()].copy_from_slice(rest);
+                u64::from_le_bytes(word)
+            }
+        };
+
+        word >> (self.position % 8)
+    }
+}
+
+///Decodes the codes of a table that are up to `LOOKUP_BITS` long with a single lookup
+struct CodeLookup {
+    ///(byte, code length) for every value of the next bits, a length of 0 means walk the trie
+    entries: Vec<(u8, u8)>,
+    mask: u64,
+    trie: CodeTrie,
+}
+
+impl CodeLookup {
+    fn new(table: &CodeTable) -> io::Result<Self> {
+        let trie = CodeTrie::new(table)?;
+
+        let longest = table.iter().map(|(_, code)| code.get_length() as u32).max();
+        let bits = longest.unwrap_or(0).min(LOOKUP_BITS);
+
+        //walk the trie for every value, so both agree on tables that aren't complete
+        let entries = (0..1usize << bits)
+            .map(|value| {
+                let mut node = 0;
+                for i in 0..bits {
+                    match trie.nodes[node][(value >> i) & 1] {
+                        Child::Node(next) => node = next as usize,
+                        Child::Leaf(byte) => return (byte, i as u8 + 1),
+                        Child::Missing => break,
+                    }
+                }
+                (0, 0)
+            })
+            .collect();
+
+        Ok(CodeLookup {
+            entries,
+            mask: (1 << bits) - 1,
+            trie,
+        })
+    }
+
+    #[inline(always)]
+    fn decode(&self, reader: &mut BitReader) -> io::Result<u8> {
+        let (byte, length) = self.entries[(reader.peek() & self.mask) as usize];
+        if length == 0 {
+            return self.walk(reader);
+        }
+
+        reader.position += length as usize;
+        Ok(byte)
+    }
+
+    ///a bit at a time, for long codes and ones that aren't in the table
+    #[cold]
+    fn walk(&self, reader: &mut BitReader) -> io::Result<u8> {
+        let mut node = 0;
+
+        loop {
+            let bit = (reader.peek() & 1) as usize;
+            reader.position += 1;
+
+            match self.trie.nodes[node][bit] {
+                Child::Node(next) => node = next as usize,
+                Child::Leaf(byte) => return Ok(byte),
+                Child::Missing => return Err(invalid_data("payload contains an unknown code")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode_with_table, get_byte_frequencies};
+
+    fn table_for(bytes: &[u8]) -> CodeTable {
+        HuffCode::from_tree(&HuffNode::from_frequencies(&get_byte_frequencies(bytes)))
+    }
+
+    #[test]
+    fn interleaved_test() {
+        let text = include_bytes!("../README.md");
+        let table = table_for(text);
+
+        //every remainder of 4
+        for len in [2, 3, 4, 5, 6, 7, 100, text.len()] {
+            let bytes = &text[..len];
+            let mut compressed = Vec::new();
+            encode_interleaved(bytes, &table, &mut compressed).unwrap();
+
+            assert_eq!(decode_interleaved(&compressed, &table, len).unwrap(), bytes);
+
+            //every stream is what `encode_with_table` writes for its bytes
+            let streams = split_streams(&compressed).unwrap();
+            for (i, stream) in streams.iter().enumerate() {
+                let own: Vec<u8> = bytes.iter().skip(i).step_by(STREAMS).copied().collect();
+                let mut expected = Vec::new();
+                encode_with_table(&own, &table, &mut expected).unwrap();
+                assert!(*stream == expected);
+            }
+        }
+    }
+
+    #[test]
+    fn long_codes_test() {
+        //fibonacci frequencies give codes up to 70 bits long, past the lookup and the accumulator
+        let mut frequencies = [0; 256];
+        let (mut a, mut b) = (1usize, 1usize);
+        for frequency in frequencies.iter_mut().take(71) {
+            *frequency = a;
+            (a, b) = (b, a + b);
+        }
+        let table = HuffCode::from_tree(&HuffNode::from_frequencies(&frequencies));
+        assert!(table.iter().any(|(_, code)| code.get_length() > 64));
+
+        let bytes: Vec<u8> = (0..71u8).chain((0..71).rev()).cycle().take(1001).collect();
+        let mut compressed = Vec::new();
+        encode_interleaved(&bytes, &table, &mut compressed).unwrap();
+
+        assert_eq!(
+            decode_interleaved(&compressed, &table, bytes.len()).unwrap(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn errors_test() {
+        let bytes = b"abracadabra, abracadabra";
+        let table = table_for(bytes);
+        let mut compressed = Vec::new();
+        encode_interleaved(bytes, &table, &mut compressed).unwrap();
+
+        let decode = |compressed: &[u8], total| decode_interleaved(compressed, &table, total);
+        assert_eq!(
+            decode(&compressed[..20], bytes.len()).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        //stream 3 is cut short
+        assert!(decode(&compressed[..compressed.len() - 1], bytes.len()).is_err());
+        //more bytes than the streams hold
+        assert!(decode(&compressed, bytes.len() + 8).is_err());
+        assert!(decode(&[compressed.as_slice(), &[0]].concat(), bytes.len()).is_err());
+
+        let mut oversized = compressed.clone();
+        oversized[..8].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert_eq!(
+            decode(&oversized, bytes.len()).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        //nothing is written for a byte without a code
+        let mut output = vec![1];
+        assert!(encode_interleaved(b"abz", &table, &mut output).is_err());
+        assert_eq!(output, [1]);
+    }
+}
@@ -0,0 +1,81 @@
+//The error and reader types of the codec. With the `std` feature they are the ones from
+//`std::io`, without it the small part of them the codec uses, so the same code builds for both
+
+#[cfg(feature = "std")]
+pub use std::io::{Error, ErrorKind, Read, Result};
+
+#[cfg(not(feature = "std"))]
+pub use no_std::*;
+
+#[cfg(not(feature = "std"))]
+mod no_std {
+    use alloc::boxed::Box;
+    use core::{error, fmt};
+
+    ///The kinds of `std::io::ErrorKind` the codec returns
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ErrorKind {
+        InvalidData,
+        InvalidInput,
+        UnexpectedEof,
+    }
+
+    ///Like `std::io::Error`, a kind and the error it wraps
+    #[derive(Debug)]
+    pub struct Error {
+        kind: ErrorKind,
+        error: Box<dyn error::Error + Send + Sync>,
+    }
+
+    impl Error {
+        pub fn new<E>(kind: ErrorKind, error: E) -> Self
+        where
+            E: Into<Box<dyn error::Error + Send + Sync>>,
+        {
+            Error {
+                kind,
+                error: error.into(),
+            }
+        }
+
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+
+        pub fn get_ref(&self) -> Option<&(dyn error::Error + Send + Sync + 'static)> {
+            Some(&*self.error)
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.error.fmt(f)
+        }
+    }
+
+    impl error::Error for Error {}
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    ///`std::io::Read` reduced to `read_exact`, for reading headers from slices
+    pub trait Read {
+        fn read_exact(&mut self, buf: &mut [u8]) -> Result<()>;
+    }
+
+    impl Read for &[u8] {
+        fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+            if buf.len() > self.len() {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                ));
+            }
+
+            let (start, rest) = self.split_at(buf.len());
+            buf.copy_from_slice(start);
+            *self = rest;
+
+            Ok(())
+        }
+    }
+}
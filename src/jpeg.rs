@@ -0,0 +1,841 @@
+use std::io;
+
+use crate::{
+    bits::BitReader,
+    header::invalid_data,
+    huff::{CodeTable, HuffCode},
+    DecompressOptions,
+};
+
+///natural (row by row) index of every coefficient in the order they are coded,
+///from the lowest frequencies to the highest
+pub const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+const SOF0: u8 = 0xc0;
+const DHT: u8 = 0xc4;
+const JPG: u8 = 0xc8;
+const DAC: u8 = 0xcc;
+const RST0: u8 = 0xd0;
+const SOI: u8 = 0xd8;
+const EOI: u8 = 0xd9;
+const SOS: u8 = 0xda;
+const DQT: u8 = 0xdb;
+const DRI: u8 = 0xdd;
+const TEM: u8 = 0x01;
+
+///longest code a DHT segment can hold
+const MAX_CODE_LENGTH: usize = 16;
+
+///largest DC difference and AC coefficient of 8 bit samples, in bits
+const MAX_DC_SIZE: u32 = 11;
+const MAX_AC_SIZE: u32 = 10;
+
+///blocks of all components in one MCU of an interleaved scan
+const MAX_MCU_BLOCKS: usize = 10;
+
+///AC symbols without a coefficient, end of block and a run of 16 zeros
+const EOB: u8 = 0x00;
+const ZRL: u8 = 0xf0;
+
+///A huffman table from a DHT segment, the canonical code given by the number of codes of
+///every length and the symbols in code order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JpegHuffmanTable {
+    class: u8,
+    id: u8,
+    counts: [u8; MAX_CODE_LENGTH],
+    symbols: Vec<u8>,
+}
+
+impl JpegHuffmanTable {
+    ///`class` is 0 for DC and 1 for AC tables, `id` 0 to 3. `counts[i]` codes have length `i + 1`
+    ///
+    ///fails if `symbols` doesn't have a symbol for every code or there are more codes than fit
+    pub fn new(class: u8, id: u8, counts: [u8; 16], symbols: Vec<u8>) -> io::Result<Self> {
+        if class > 1 || id > 3 {
+            return Err(invalid_data(format!(
+                "invalid JPEG huffman table class {class} id {id}"
+            )));
+        }
+
+        if counts.iter().map(|count| *count as usize).sum::<usize>() != symbols.len() {
+            return Err(invalid_data(
+                "JPEG huffman table symbols don't match its counts",
+            ));
+        }
+
+        let mut code = 0u32;
+        for (length, count) in (1..).zip(counts) {
+            code += count as u32;
+            if code > 1 << length {
+                return Err(invalid_data("JPEG huffman table has too many codes"));
+            }
+            code <<= 1;
+        }
+
+        Ok(JpegHuffmanTable {
+            class,
+            id,
+            counts,
+            symbols,
+        })
+    }
+
+    pub fn get_class(&self) -> u8 {
+        self.class
+    }
+
+    pub fn get_id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn get_counts(&self) -> &[u8; 16] {
+        &self.counts
+    }
+
+    pub fn get_symbols(&self) -> &[u8] {
+        &self.symbols
+    }
+
+    ///code of every symbol, with weight 0 as the table doesn't say how often they occur.
+    ///A symbol listed twice keeps its first code
+    pub fn to_codes(&self) -> CodeTable {
+        let mut codes = CodeTable::new();
+        let mut symbols = self.symbols.iter();
+        let mut code = 0u128;
+
+        for (length, count) in (1..).zip(self.counts) {
+            for symbol in symbols.by_ref().take(count as usize) {
+                //JPEG writes the highest bit first, `HuffCode` the lowest
+                let reversed = code.reverse_bits() >> (128 - length);
+                if codes.get(*symbol).is_none() {
+                    codes.insert(*symbol, HuffCode::new(0, length, reversed));
+                }
+                code += 1;
+            }
+            code <<= 1;
+        }
+
+        codes
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> io::Result<u8> {
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+
+        for count in self.counts.map(u32::from) {
+            code = code << 1 | reader.bit()?;
+
+            if code >= first && code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+        }
+
+        Err(invalid_data("invalid JPEG huffman code"))
+    }
+}
+
+///every table in the payload of a DHT segment, after its length
+pub fn parse_dht(mut segment: &[u8]) -> io::Result<Vec<JpegHuffmanTable>> {
+    let mut tables = Vec::new();
+
+    while let [class_id, rest @ ..] = segment {
+        let counts: [u8; MAX_CODE_LENGTH] = rest
+            .get(..MAX_CODE_LENGTH)
+            .and_then(|counts| counts.try_into().ok())
+            .ok_or_else(|| invalid_data("DHT segment ended"))?;
+        let rest = &rest[MAX_CODE_LENGTH..];
+
+        let total = counts.iter().map(|count| *count as usize).sum();
+        let symbols = rest
+            .get(..total)
+            .ok_or_else(|| invalid_data("DHT segment ended"))?;
+
+        tables.push(JpegHuffmanTable::new(
+            class_id >> 4,
+            class_id & 0xf,
+            counts,
+            symbols.to_vec(),
+        )?);
+        segment = &rest[total..];
+    }
+
+    Ok(tables)
+}
+
+///The coefficients of one component, quantized and in natural order with DC at index 0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JpegComponent {
+    id: u8,
+    horizontal: u8,
+    vertical: u8,
+    quantization_table: u8,
+    blocks_wide: usize,
+    blocks_high: usize,
+    blocks: Vec<[i16; 64]>,
+}
+
+impl JpegComponent {
+    pub fn get_id(&self) -> u8 {
+        self.id
+    }
+
+    ///horizontal sampling factor, blocks across in one MCU
+    pub fn get_horizontal(&self) -> u8 {
+        self.horizontal
+    }
+
+    ///vertical sampling factor, blocks down in one MCU
+    pub fn get_vertical(&self) -> u8 {
+        self.vertical
+    }
+
+    ///id of the table in `JpegImage::get_quantization_table` the coefficients are divided by
+    pub fn get_quantization_table(&self) -> u8 {
+        self.quantization_table
+    }
+
+    ///blocks in a row, including the padding up to whole MCUs
+    pub fn get_blocks_wide(&self) -> usize {
+        self.blocks_wide
+    }
+
+    pub fn get_blocks_high(&self) -> usize {
+        self.blocks_high
+    }
+
+    ///all blocks row by row
+    pub fn get_blocks(&self) -> &[[i16; 64]] {
+        &self.blocks
+    }
+
+    pub fn get_block(&self, x: usize, y: usize) -> Option<&[i16; 64]> {
+        match x < self.blocks_wide {
+            true => self.blocks.get(y * self.blocks_wide + x),
+            false => None,
+        }
+    }
+}
+
+///A baseline JPEG decoded up to its coefficients, before dequantization and the IDCT
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JpegImage {
+    width: u16,
+    height: u16,
+    components: Vec<JpegComponent>,
+    quantization_tables: [Option<[u16; 64]>; 4],
+    huffman_tables: Vec<JpegHuffmanTable>,
+    restart_interval: u16,
+}
+
+impl JpegImage {
+    pub fn get_width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u16 {
+        self.height
+    }
+
+    pub fn get_components(&self) -> &[JpegComponent] {
+        &self.components
+    }
+
+    ///last table defined for `id`, in natural order like the blocks
+    pub fn get_quantization_table(&self, id: u8) -> Option<&[u16; 64]> {
+        self.quantization_tables.get(id as usize)?.as_ref()
+    }
+
+    ///every table of every DHT segment in file order, later ones replace earlier ones of the
+    ///same class and id for the scans after them
+    pub fn get_huffman_tables(&self) -> &[JpegHuffmanTable] {
+        &self.huffman_tables
+    }
+
+    ///MCUs between restart markers, 0 without them
+    pub fn get_restart_interval(&self) -> u16 {
+        self.restart_interval
+    }
+}
+
+///decode the coefficients of every component of a baseline JPEG file
+pub fn decode_jpeg(bytes: &[u8]) -> io::Result<JpegImage> {
+    decode_jpeg_with_options(bytes, &DecompressOptions::new())
+}
+
+///like `decode_jpeg`, enforcing the limits in `options` on the size of the coefficients,
+///2 bytes each, before they are allocated
+pub fn decode_jpeg_with_options(
+    bytes: &[u8],
+    options: &DecompressOptions,
+) -> io::Result<JpegImage> {
+    let mut rest = bytes
+        .strip_prefix(&[0xff, SOI])
+        .ok_or_else(|| invalid_data("not a JPEG file"))?;
+
+    let mut image: Option<JpegImage> = None;
+    let mut quantization_tables = [None; 4];
+    let mut huffman_tables = Vec::new();
+    //tables the next scan uses, by class and id
+    let mut current_tables: [[Option<JpegHuffmanTable>; 4]; 2] = Default::default();
+    let mut restart_interval = 0;
+
+    loop {
+        let (marker, segment, after) = next_segment(rest)?;
+        rest = after;
+
+        match marker {
+            EOI => break,
+            SOF0 if image.is_some() => return Err(invalid_data("JPEG file has two frames")),
+            SOF0 => image = Some(read_frame(segment, options, bytes.len())?),
+            DHT => {
+                for table in parse_dht(segment)? {
+                    current_tables[table.class as usize][table.id as usize] = Some(table.clone());
+                    huffman_tables.push(table);
+                }
+            }
+            DQT => read_quantization_tables(segment, &mut quantization_tables)?,
+            DRI => restart_interval = read_u16(segment)?,
+            SOS => {
+                let image = image
+                    .as_mut()
+                    .ok_or_else(|| invalid_data("JPEG scan before its frame"))?;
+                rest = read_scan(segment, rest, image, &current_tables, restart_interval)?;
+            }
+            0xc1..=0xcf if marker != JPG && marker != DAC => {
+                return Err(invalid_data("only baseline JPEG files are supported"))
+            }
+            //APPn, COM and others don't change the coefficients
+            _ => {}
+        }
+    }
+
+    let mut image = image.ok_or_else(|| invalid_data("JPEG file has no frame"))?;
+    image.quantization_tables = quantization_tables;
+    image.huffman_tables = huffman_tables;
+    image.restart_interval = restart_interval;
+
+    Ok(image)
+}
+
+///the marker at the start of `bytes`, its payload and the bytes after it
+fn next_segment(bytes: &[u8]) -> io::Result<(u8, &[u8], &[u8])> {
+    let bytes = bytes
+        .strip_prefix(&[0xff])
+        .ok_or_else(|| invalid_data("expected a JPEG marker"))?;
+
+    //any number of 0xff can fill the space before a marker
+    let start = bytes
+        .iter()
+        .position(|byte| *byte != 0xff)
+        .ok_or_else(|| invalid_data("JPEG file ended"))?;
+    let marker = bytes[start];
+    let bytes = &bytes[start + 1..];
+
+    if matches!(marker, SOI | EOI | TEM | RST0..=0xd7) {
+        return Ok((marker, &[], bytes));
+    }
+
+    let length = read_u16(bytes)? as usize;
+    if length < 2 || length > bytes.len() {
+        return Err(invalid_data("invalid JPEG segment length"));
+    }
+
+    Ok((marker, &bytes[2..length], &bytes[length..]))
+}
+
+fn read_u16(bytes: &[u8]) -> io::Result<u16> {
+    match bytes {
+        [high, low, ..] => Ok(u16::from_be_bytes([*high, *low])),
+        _ => Err(invalid_data("JPEG segment ended")),
+    }
+}
+
+fn read_frame(segment: &[u8], options: &DecompressOptions, size: usize) -> io::Result<JpegImage> {
+    let [precision, header @ .., count] = segment.get(..6).unwrap_or_default() else {
+        return Err(invalid_data("JPEG frame header ended"));
+    };
+    if *precision != 8 {
+        return Err(invalid_data("only 8 bit JPEG samples are supported"));
+    }
+
+    let height = read_u16(header)?;
+    let width = read_u16(&header[2..])?;
+    if height == 0 || width == 0 {
+        return Err(invalid_data(
+            "JPEG files without a height or width aren't supported",
+        ));
+    }
+
+    let specs = &segment[6..];
+    if *count == 0 || *count > 4 || specs.len() != *count as usize * 3 {
+        return Err(invalid_data("invalid JPEG frame components"));
+    }
+
+    let mut components = Vec::new();
+    for spec in specs.chunks(3) {
+        let (horizontal, vertical) = (spec[1] >> 4, spec[1] & 0xf);
+        if !(1..=4).contains(&horizontal) || !(1..=4).contains(&vertical) || spec[2] > 3 {
+            return Err(invalid_data("invalid JPEG frame components"));
+        }
+
+        components.push(JpegComponent {
+            id: spec[0],
+            horizontal,
+            vertical,
+            quantization_table: spec[2],
+            blocks_wide: 0,
+            blocks_high: 0,
+            blocks: Vec::new(),
+        });
+    }
+
+    //every component covers the same number of whole MCUs
+    let max_horizontal = components.iter().map(|c| c.horizontal).max().unwrap_or(1) as usize;
+    let max_vertical = components.iter().map(|c| c.vertical).max().unwrap_or(1) as usize;
+    let mcus_wide = (width as usize).div_ceil(8 * max_horizontal);
+    let mcus_high = (height as usize).div_ceil(8 * max_vertical);
+
+    //saturating, as 4 components of the largest frame don't fit in 32 bits
+    let blocks = components
+        .iter()
+        .map(|c| mcus_wide * c.horizontal as usize * mcus_high * c.vertical as usize)
+        .fold(0usize, usize::saturating_add);
+    options.check(blocks.saturating_mul(128), size)?;
+
+    for component in &mut components {
+        component.blocks_wide = mcus_wide * component.horizontal as usize;
+        component.blocks_high = mcus_high * component.vertical as usize;
+        component.blocks = vec![[0; 64]; component.blocks_wide * component.blocks_high];
+    }
+
+    Ok(JpegImage {
+        width,
+        height,
+        components,
+        quantization_tables: [None; 4],
+        huffman_tables: Vec::new(),
+        restart_interval: 0,
+    })
+}
+
+fn read_quantization_tables(
+    mut segment: &[u8],
+    tables: &mut [Option<[u16; 64]>; 4],
+) -> io::Result<()> {
+    while let [precision_id, rest @ ..] = segment {
+        let (wide, id) = (precision_id >> 4, precision_id & 0xf);
+        let value_size = match wide {
+            0 => 1,
+            1 => 2,
+            _ => return Err(invalid_data("invalid JPEG quantization table precision")),
+        };
+        if id > 3 {
+            return Err(invalid_data("invalid JPEG quantization table id"));
+        }
+
+        let values = rest
+            .get(..64 * value_size)
+            .ok_or_else(|| invalid_data("DQT segment ended"))?;
+
+        let mut table = [0; 64];
+        for (index, value) in ZIGZAG.iter().zip(values.chunks(value_size)) {
+            table[*index] = value
+                .iter()
+                .fold(0, |table, byte| table << 8 | *byte as u16);
+        }
+
+        tables[id as usize] = Some(table);
+        segment = &rest[64 * value_size..];
+    }
+
+    Ok(())
+}
+
+///decode the entropy coded data in `bytes` after the SOS `segment` into the blocks of `image`,
+///returns the bytes after it
+fn read_scan<'a>(
+    segment: &[u8],
+    mut bytes: &'a [u8],
+    image: &mut JpegImage,
+    tables: &[[Option<JpegHuffmanTable>; 4]; 2],
+    restart_interval: u16,
+) -> io::Result<&'a [u8]> {
+    let [count, rest @ ..] = segment else {
+        return Err(invalid_data("JPEG scan header ended"));
+    };
+    let count = *count as usize;
+    if count == 0 || count > 4 || rest.len() != count * 2 + 3 {
+        return Err(invalid_data("invalid JPEG scan header"));
+    }
+
+    //spectral selection and successive approximation are for progressive files
+    if rest[count * 2..] != [0, 63, 0] {
+        return Err(invalid_data("only baseline JPEG scans are supported"));
+    }
+
+    let mut scan: Vec<(usize, &JpegHuffmanTable, &JpegHuffmanTable)> = Vec::new();
+    for spec in rest[..count * 2].chunks(2) {
+        let component = image
+            .components
+            .iter()
+            .position(|c| c.id == spec[0])
+            .filter(|component| scan.iter().all(|(other, ..)| other != component))
+            .ok_or_else(|| invalid_data("invalid JPEG scan component"))?;
+
+        let table = |class: usize, id: u8| {
+            tables[class]
+                .get(id as usize)
+                .and_then(Option::as_ref)
+                .ok_or_else(|| invalid_data("JPEG scan uses an undefined huffman table"))
+        };
+        scan.push((component, table(0, spec[1] >> 4)?, table(1, spec[1] & 0xf)?));
+    }
+
+    //a single component is coded block by block, only over the part of it in the image
+    let (mcus_wide, mcus_high) = match scan[..] {
+        [(component, ..)] => {
+            let component = &image.components[component];
+            let max_horizontal = image.components.iter().map(|c| c.horizontal).max();
+            let max_vertical = image.components.iter().map(|c| c.vertical).max();
+
+            let wide = (image.width as usize * component.horizontal as usize)
+                .div_ceil(max_horizontal.unwrap_or(1) as usize);
+            let high = (image.height as usize * component.vertical as usize)
+                .div_ceil(max_vertical.unwrap_or(1) as usize);
+            (wide.div_ceil(8), high.div_ceil(8))
+        }
+        _ => {
+            let blocks: usize = scan
+                .iter()
+                .map(|(c, ..)| image.components[*c].horizontal * image.components[*c].vertical)
+                .map(usize::from)
+                .sum();
+            if blocks > MAX_MCU_BLOCKS {
+                return Err(invalid_data("JPEG scan has too many blocks in a MCU"));
+            }
+
+            let first = &image.components[scan[0].0];
+            (
+                first.blocks_wide / first.horizontal as usize,
+                first.blocks_high / first.vertical as usize,
+            )
+        }
+    };
+
+    let total = mcus_wide * mcus_high;
+    let interval = match restart_interval {
+        0 => total,
+        interval => interval as usize,
+    };
+
+    let mut mcu = 0;
+    let mut restarts = 0;
+
+    loop {
+        let (coded, after) = entropy_coded_segment(bytes);
+        let mut reader = BitReader::new(&coded);
+        //DC coefficients are coded as the difference to the previous one of their component
+        let mut predictions = [0i16; 4];
+
+        let end = total.min(mcu + interval);
+        for mcu in mcu..end {
+            let (x, y) = (mcu % mcus_wide, mcu / mcus_wide);
+
+            for ((component, dc, ac), prediction) in scan.iter().zip(&mut predictions) {
+                let component = &mut image.components[*component];
+
+                let (horizontal, vertical) = match count {
+                    1 => (1, 1),
+                    _ => (component.horizontal as usize, component.vertical as usize),
+                };
+
+                for block_y in y * vertical..(y + 1) * vertical {
+                    for block_x in x * horizontal..(x + 1) * horizontal {
+                        let block =
+                            &mut component.blocks[block_y * component.blocks_wide + block_x];
+                        read_block(&mut reader, dc, ac, prediction, block)?;
+                    }
+                }
+            }
+        }
+
+        mcu = end;
+        bytes = after;
+
+        if mcu == total {
+            return Ok(bytes);
+        }
+
+        let (marker, _, after) = next_segment(bytes)?;
+        if marker != RST0 + restarts % 8 {
+            return Err(invalid_data("expected a JPEG restart marker"));
+        }
+        restarts = restarts.wrapping_add(1);
+        bytes = after;
+    }
+}
+
+///coded bytes up to the next marker with the 0 stuffed after every 0xff removed,
+///and the bytes from that marker on
+fn entropy_coded_segment(bytes: &[u8]) -> (Vec<u8>, &[u8]) {
+    let mut coded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while let Some(byte) = bytes.get(i) {
+        if *byte == 0xff {
+            match bytes.get(i + 1) {
+                Some(0) => i += 1,
+                _ => break,
+            }
+        }
+
+        coded.push(*byte);
+        i += 1;
+    }
+
+    (coded, &bytes[i..])
+}
+
+fn read_block(
+    reader: &mut BitReader,
+    dc: &JpegHuffmanTable,
+    ac: &JpegHuffmanTable,
+    prediction: &mut i16,
+    block: &mut [i16; 64],
+) -> io::Result<()> {
+    let size = dc.decode(reader)? as u32;
+    if size > MAX_DC_SIZE {
+        return Err(invalid_data("invalid JPEG DC coefficient size"));
+    }
+    *prediction = prediction.wrapping_add(extend(reader.read(size)?, size));
+    block[0] = *prediction;
+
+    let mut k = 1;
+    while k < 64 {
+        let symbol = ac.decode(reader)?;
+        let (run, size) = ((symbol >> 4) as usize, (symbol & 0xf) as u32);
+
+        match symbol {
+            EOB => break,
+            ZRL => k += 16,
+            _ if size == 0 || size > MAX_AC_SIZE || k + run > 63 => {
+                return Err(invalid_data("invalid JPEG AC coefficient"))
+            }
+            _ => {
+                k += run;
+                block[ZIGZAG[k]] = extend(reader.read(size)?, size);
+                k += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+///`size` bits stand for the values with that many bits, the negative ones below the positive
+fn extend(value: u32, size: u32) -> i16 {
+    match size {
+        0 => 0,
+        _ if value < 1 << (size - 1) => (value as i32 - (1 << size) + 1) as i16,
+        _ => value as i16,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LimitError;
+
+    fn test_file(name: &str) -> Vec<u8> {
+        std::fs::read(format!("./test_files/jpeg/{name}")).unwrap()
+    }
+
+    #[test]
+    fn zigzag_test() {
+        let mut sorted = ZIGZAG;
+        sorted.sort();
+        assert!(sorted.iter().copied().eq(0..64));
+
+        //neighbours in the zigzag order are neighbours in the block
+        for pair in ZIGZAG.windows(2) {
+            let (row, column) = (pair[0] / 8, pair[0] % 8);
+            let (next_row, next_column) = (pair[1] / 8, pair[1] % 8);
+            assert!(row.abs_diff(next_row) <= 1 && column.abs_diff(next_column) <= 1);
+        }
+    }
+
+    #[test]
+    fn parse_dht_test() {
+        //luminance DC table from the JPEG standard, K.3
+        let mut segment = vec![0x00, 0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+        segment.extend(0..12);
+        //and a small AC table after it
+        segment.extend([
+            0x13, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0xf0, 0x01,
+        ]);
+
+        let tables = parse_dht(&segment).unwrap();
+        assert_eq!(tables.len(), 2);
+        assert_eq!((tables[0].get_class(), tables[0].get_id()), (0, 0));
+        assert_eq!((tables[1].get_class(), tables[1].get_id()), (1, 3));
+        assert_eq!(tables[1].get_symbols(), [0x00, 0xf0, 0x01]);
+
+        let codes = tables[0].to_codes();
+        let expected = [
+            "00",
+            "010",
+            "011",
+            "100",
+            "101",
+            "110",
+            "1110",
+            "11110",
+            "111110",
+            "1111110",
+            "11111110",
+            "111111110",
+        ];
+        for (symbol, bits) in (0..).zip(expected) {
+            assert_eq!(codes[symbol], HuffCode::from_bit_string(0, bits).unwrap());
+        }
+
+        let codes = tables[1].to_codes();
+        assert_eq!(codes[0x00].to_bit_string(), "0");
+        assert_eq!(codes[0xf0].to_bit_string(), "10");
+        assert_eq!(codes[0x01].to_bit_string(), "110");
+    }
+
+    #[test]
+    fn parse_dht_invalid_test() {
+        //3 codes of length 1
+        let mut segment = vec![
+            0x00, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3,
+        ];
+        assert!(parse_dht(&segment).is_err());
+
+        segment[1] = 2;
+        assert!(parse_dht(&segment[..19]).is_ok());
+        assert!(parse_dht(&segment[..18]).is_err());
+
+        segment[0] = 0x20;
+        assert!(parse_dht(&segment[..19]).is_err());
+        segment[0] = 0x04;
+        assert!(parse_dht(&segment[..19]).is_err());
+    }
+
+    #[test]
+    fn decode_color_test() {
+        let image = decode_jpeg(&test_file("color.jpg")).unwrap();
+
+        assert_eq!((image.get_width(), image.get_height()), (40, 24));
+        assert_eq!(image.get_restart_interval(), 2);
+        assert_eq!(image.get_huffman_tables().len(), 4);
+        assert!(image.get_quantization_table(0).is_some());
+        assert!(image.get_quantization_table(1).is_some());
+        assert!(image.get_quantization_table(2).is_none());
+
+        //4:2:0, 3 by 2 MCUs
+        let components = image.get_components();
+        assert_eq!(components.len(), 3);
+        let sizes: Vec<_> = components
+            .iter()
+            .map(|c| {
+                (
+                    c.get_horizontal(),
+                    c.get_vertical(),
+                    c.get_blocks_wide(),
+                    c.get_blocks_high(),
+                )
+            })
+            .collect();
+        assert_eq!(sizes, [(2, 2, 6, 4), (1, 1, 3, 2), (1, 1, 3, 2)]);
+        assert_eq!(components[0].get_blocks().len(), 24);
+
+        //a red disc in the middle on a gradient, Cr is highest in it
+        let cr = &components[2];
+        let center = cr.get_block(1, 0).unwrap()[0];
+        assert!(cr.get_blocks().iter().all(|block| block[0] <= center));
+        assert!(center > 0);
+        assert!(cr.get_block(3, 0).is_none());
+    }
+
+    #[test]
+    fn decode_gray_test() {
+        let image = decode_jpeg(&test_file("gray.jpg")).unwrap();
+
+        assert_eq!((image.get_width(), image.get_height()), (20, 12));
+        assert_eq!(image.get_restart_interval(), 0);
+
+        let component = &image.get_components()[0];
+        assert_eq!(
+            (component.get_blocks_wide(), component.get_blocks_high()),
+            (3, 2)
+        );
+
+        //DC is 8 times the mean of the block, less 128
+        let quantization = image
+            .get_quantization_table(component.get_quantization_table())
+            .unwrap();
+        for (i, block) in component.get_blocks().iter().enumerate() {
+            let (x, y) = (i % 3, i / 3);
+            let pixels: Vec<i32> = (0..64)
+                .map(|p| (x * 8 + p % 8, y * 8 + p / 8))
+                //the encoder repeats the last row and column into the padding
+                .map(|(x, y)| ((x.min(19) as i32 * 11) ^ (y.min(11) as i32 * 17)) as u8 as i32)
+                .collect();
+            let mean = pixels.iter().sum::<i32>() as f64 / 64.0;
+            let dc = (block[0] as i32 * quantization[0] as i32) as f64 / 8.0 + 128.0;
+
+            assert!((mean - dc).abs() < 2.0, "block {i}: {mean} {dc}");
+        }
+    }
+
+    #[test]
+    fn decode_invalid_test() {
+        let bytes = test_file("color.jpg");
+
+        for end in [0, 2, 100, 700, bytes.len() - 2] {
+            assert!(decode_jpeg(&bytes[..end]).is_err(), "{end}");
+        }
+
+        //progressive
+        let sof = bytes.windows(2).position(|w| w == [0xff, SOF0]).unwrap();
+        let mut progressive = bytes.clone();
+        progressive[sof + 1] = 0xc2;
+        assert!(decode_jpeg(&progressive).is_err());
+
+        //restart markers out of order
+        let rst = bytes.windows(2).position(|w| w == [0xff, RST0]).unwrap();
+        let mut restart = bytes.clone();
+        restart[rst + 1] = RST0 + 1;
+        assert!(decode_jpeg(&restart).is_err());
+
+        //a table that isn't there
+        let sos = bytes.windows(2).position(|w| w == [0xff, SOS]).unwrap();
+        let mut table = bytes.clone();
+        table[sos + 6] = 0x22;
+        assert!(decode_jpeg(&table).is_err());
+    }
+
+    #[test]
+    fn decode_limits_test() {
+        let bytes = test_file("color.jpg");
+
+        //(24 + 6 + 6) blocks of 64 coefficients
+        let options = DecompressOptions::new().max_output_size(36 * 128);
+        assert!(decode_jpeg_with_options(&bytes, &options).is_ok());
+
+        let options = DecompressOptions::new().max_output_size(36 * 128 - 1);
+        let err = decode_jpeg_with_options(&bytes, &options).unwrap_err();
+        assert!(LimitError::from_io(&err).is_some());
+    }
+}
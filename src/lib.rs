@@ -1,5 +1,63 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+//the tree, code tables and in-memory codec only need `alloc`
+pub mod ans;
+pub mod bwt;
+pub mod coder;
 pub mod fileops;
+pub mod header;
 pub mod huff;
+pub mod interleaved;
+pub mod io;
+pub mod limits;
+pub mod range;
+pub mod stream;
 
+#[cfg(feature = "tokio")]
+pub mod async_io;
+#[cfg(feature = "std")]
+mod bits;
+#[cfg(feature = "std")]
+pub mod bzip2;
+#[cfg(feature = "std")]
+pub mod dot;
+#[cfg(feature = "std")]
+pub mod hpack;
+#[cfg(feature = "std")]
+pub mod jpeg;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod table;
+
+pub use ans::*;
+pub use bwt::*;
+pub use coder::*;
 pub use fileops::*;
+pub use header::*;
 pub use huff::*;
+pub use interleaved::*;
+pub use limits::*;
+pub use range::*;
+pub use stream::*;
+
+#[cfg(feature = "tokio")]
+pub use async_io::*;
+#[cfg(feature = "std")]
+pub use bzip2::*;
+#[cfg(feature = "std")]
+pub use dot::*;
+#[cfg(feature = "std")]
+pub use hpack::*;
+#[cfg(feature = "std")]
+pub use jpeg::*;
+#[cfg(feature = "mmap")]
+pub use mmap::*;
+#[cfg(feature = "std")]
+pub use stats::*;
+#[cfg(feature = "std")]
+pub use table::*;
@@ -0,0 +1,133 @@
+use core::fmt;
+
+use crate::io;
+
+///Resource limits enforced while decompressing untrusted input
+///
+///Every limit is optional, an unset limit is never checked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecompressOptions {
+    max_output_size: Option<usize>,
+    max_ratio: Option<usize>,
+    max_header_size: Option<usize>,
+    max_memory: Option<usize>,
+}
+
+impl DecompressOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///maximum number of decompressed bytes
+    pub fn max_output_size(mut self, bytes: usize) -> Self {
+        self.max_output_size = Some(bytes);
+        self
+    }
+
+    ///maximum ratio of decompressed size to compressed size
+    pub fn max_ratio(mut self, ratio: usize) -> Self {
+        self.max_ratio = Some(ratio);
+        self
+    }
+
+    ///maximum size of the frequency table header, `table_size` included
+    pub fn max_header_size(mut self, bytes: usize) -> Self {
+        self.max_header_size = Some(bytes);
+        self
+    }
+
+    ///maximum number of bytes held in memory at once (compressed + decompressed)
+    pub fn max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = Some(bytes);
+        self
+    }
+
+    ///check every limit that depends on the size of the input and the decompressed output
+    pub(crate) fn check(&self, output: usize, compressed: usize) -> Result<(), LimitError> {
+        self.check_output_size(output)?;
+        self.check_ratio(output, compressed)?;
+        self.check_memory(compressed.saturating_add(output))
+    }
+
+    pub(crate) fn check_header_size(&self, actual: usize) -> Result<(), LimitError> {
+        match self.max_header_size {
+            Some(limit) if actual > limit => Err(LimitError::HeaderSize { limit, actual }),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_output_size(&self, actual: usize) -> Result<(), LimitError> {
+        match self.max_output_size {
+            Some(limit) if actual > limit => Err(LimitError::OutputSize { limit, actual }),
+            _ => Ok(()),
+        }
+    }
+
+    ///`compressed` is the full size of the compressed input, header included
+    fn check_ratio(&self, output: usize, compressed: usize) -> Result<(), LimitError> {
+        match self.max_ratio {
+            Some(limit) if output > limit.saturating_mul(compressed) => Err(LimitError::Ratio {
+                limit,
+                actual: output / compressed.max(1),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_memory(&self, actual: usize) -> Result<(), LimitError> {
+        match self.max_memory {
+            Some(limit) if actual > limit => Err(LimitError::Memory { limit, actual }),
+            _ => Ok(()),
+        }
+    }
+}
+
+///A limit from [`DecompressOptions`] was exceeded
+///
+///Returned wrapped in an [`io::Error`] of kind [`io::ErrorKind::InvalidData`],
+///use [`LimitError::from_io`] to get it back out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitError {
+    OutputSize { limit: usize, actual: usize },
+    Ratio { limit: usize, actual: usize },
+    HeaderSize { limit: usize, actual: usize },
+    Memory { limit: usize, actual: usize },
+}
+
+impl LimitError {
+    ///returns the `LimitError` inside `err` if there is one
+    pub fn from_io(err: &io::Error) -> Option<&LimitError> {
+        err.get_ref().and_then(|inner| inner.downcast_ref())
+    }
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitError::OutputSize { limit, actual } => write!(
+                f,
+                "decompressed size of {actual} bytes exceeds limit of {limit} bytes"
+            ),
+            LimitError::Ratio { limit, actual } => write!(
+                f,
+                "compression ratio of {actual}:1 exceeds limit of {limit}:1"
+            ),
+            LimitError::HeaderSize { limit, actual } => write!(
+                f,
+                "header size of {actual} bytes exceeds limit of {limit} bytes"
+            ),
+            LimitError::Memory { limit, actual } => write!(
+                f,
+                "required memory of {actual} bytes exceeds limit of {limit} bytes"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for LimitError {}
+
+impl From<LimitError> for io::Error {
+    fn from(err: LimitError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
@@ -1,15 +1,25 @@
 use std::{
     fs::File,
-    io::{self, Read},
+    io::{self, Read, Write},
     path::PathBuf,
 };
 
-use clap::{command, Args, Parser};
-use learning_huffman::{get_byte_frequencies, CompressFile, DecompressFile};
+use clap::{
+    builder::{PossibleValuesParser, TypedValueParser},
+    Args, Parser, Subcommand,
+};
+use learning_huffman::{
+    compress_bzip2, decompress_bzip2_with_options, export_table, get_byte_frequencies, tree_to_dot,
+    Coder, CompressFile, CompressOptions, DecompressFile, DecompressOptions, DotOptions, HuffCode,
+    HuffNode, Stats, TableFormat, TreeMode, CODERS,
+};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     #[command(flatten)]
     compops: CompOps,
 
@@ -20,17 +30,80 @@ struct Cli {
     #[arg(short, long, value_name = "FILE")]
     frequencies: Option<PathBuf>,
 
+    ///print compression statistics for file
+    #[arg(short, long, value_name = "FILE")]
+    stats: Option<PathBuf>,
+
+    ///print statistics as json
+    #[arg(long, requires = "stats")]
+    json: bool,
+
+    ///how to build the tree: standard or legacy
+    #[arg(long, value_name = "MODE", default_value = "standard", global = true)]
+    tree_mode: TreeMode,
+
     ///output for compress/decompress
     #[arg(value_name = "FILE")]
     output: Option<PathBuf>,
 }
 
+#[derive(Subcommand, Debug)]
+enum Commands {
+    ///write the Huffman tree of a file as a Graphviz DOT graph
+    Tree {
+        ///file to build the tree for
+        #[arg(value_name = "FILE")]
+        input: PathBuf,
+
+        ///output for the graph, stdout if not given
+        #[arg(value_name = "OUTPUT")]
+        output: Option<PathBuf>,
+
+        ///draw subtrees lighter than WEIGHT as a single node
+        #[arg(long, value_name = "WEIGHT", default_value_t = 0)]
+        collapse_below: usize,
+    },
+
+    ///write the code table of a file as csv, json or toml
+    Table {
+        ///file to build the code table for
+        #[arg(value_name = "FILE")]
+        input: PathBuf,
+
+        ///output for the table, stdout if not given
+        #[arg(value_name = "OUTPUT")]
+        output: Option<PathBuf>,
+
+        ///csv, json or toml
+        #[arg(long, value_name = "FORMAT", default_value = "csv")]
+        format: TableFormat,
+    },
+}
+
 #[derive(Args, Debug)]
 #[group(requires_all = ["compress", "output"])]
 struct CompOps {
     ///compress file
     #[arg(short, long, value_name = "FILE")]
     compress: Option<PathBuf>,
+
+    ///entropy coder for compress [default: huffman]
+    #[arg(long, value_name = "CODER", value_parser = coder_parser())]
+    coder: Option<Coder>,
+
+    ///run BWT, MTF and zero-run coding before the coder, much smaller for text
+    #[arg(long)]
+    bwt: bool,
+
+    ///write a bzip2 stream with blocks of LEVEL * 100k bytes instead, -d reads them too
+    #[arg(long, value_name = "LEVEL", conflicts_with_all = ["coder", "bwt"])]
+    #[arg(value_parser = clap::value_parser!(u8).range(1..=9))]
+    bzip2: Option<u8>,
+
+    ///read the file through a memory map and write the output as it is encoded
+    #[cfg(feature = "mmap")]
+    #[arg(long, conflicts_with = "bzip2")]
+    mmap: bool,
 }
 
 #[derive(Args, Debug)]
@@ -39,6 +112,49 @@ struct DecompOps {
     ///compress file
     #[arg(short, long, value_name = "FILE")]
     decompress: Option<PathBuf>,
+
+    ///refuse to decompress to more than BYTES
+    #[arg(long, value_name = "BYTES")]
+    max_output_size: Option<usize>,
+
+    ///refuse to decompress when output is more than RATIO times the input size
+    #[arg(long, value_name = "RATIO")]
+    max_ratio: Option<usize>,
+
+    ///refuse to read a frequency header larger than BYTES
+    #[arg(long, value_name = "BYTES")]
+    max_header_size: Option<usize>,
+
+    ///refuse to hold more than BYTES of input and output in memory
+    #[arg(long, value_name = "BYTES")]
+    max_memory: Option<usize>,
+}
+
+impl DecompOps {
+    fn options(&self) -> DecompressOptions {
+        let mut options = DecompressOptions::new();
+
+        if let Some(bytes) = self.max_output_size {
+            options = options.max_output_size(bytes);
+        }
+        if let Some(ratio) = self.max_ratio {
+            options = options.max_ratio(ratio);
+        }
+        if let Some(bytes) = self.max_header_size {
+            options = options.max_header_size(bytes);
+        }
+        if let Some(bytes) = self.max_memory {
+            options = options.max_memory(bytes);
+        }
+
+        options
+    }
+}
+
+///any of `CODERS` by name, listed in the help
+fn coder_parser() -> impl clap::builder::TypedValueParser<Value = Coder> {
+    PossibleValuesParser::new(CODERS.map(Coder::name))
+        .map(|name| Coder::from_name(&name).expect("listed coder"))
 }
 
 fn get_file_bytes(path: &PathBuf) -> Vec<u8> {
@@ -49,19 +165,95 @@ fn get_file_bytes(path: &PathBuf) -> Vec<u8> {
     bytes
 }
 
+///our files start with the 0 bytes of a small table size instead of "BZh"
+fn is_bzip2(path: &PathBuf) -> io::Result<bool> {
+    let mut magic = Vec::new();
+    File::open(path)?.take(3).read_to_end(&mut magic)?;
+
+    Ok(magic == b"BZh")
+}
+
+///write `text` to a new file at `output`, or to stdout
+fn write_output(output: &Option<PathBuf>, text: &str) -> io::Result<()> {
+    match output {
+        Some(output) => File::create_new(output)?.write_all(text.as_bytes()),
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
+
 fn main() -> Result<(), io::Error> {
     let cli = Cli::parse();
 
-    if let Some(path) = cli.frequencies {
+    if let Some(Commands::Tree {
+        input,
+        output,
+        collapse_below,
+    }) = &cli.command
+    {
+        let frequencies = get_byte_frequencies(&get_file_bytes(input));
+        let options = DotOptions::new().collapse_below(*collapse_below);
+        let dot = tree_to_dot(
+            &HuffNode::from_frequencies_with(&frequencies, cli.tree_mode),
+            &options,
+        );
+
+        write_output(output, &dot)?;
+    } else if let Some(Commands::Table {
+        input,
+        output,
+        format,
+    }) = &cli.command
+    {
+        let frequencies = get_byte_frequencies(&get_file_bytes(input));
+        let table = HuffCode::from_tree(&HuffNode::from_frequencies_with(
+            &frequencies,
+            cli.tree_mode,
+        ));
+
+        write_output(output, &export_table(&table, *format))?;
+    } else if let Some(path) = cli.frequencies {
         let bytes = get_file_bytes(&path);
         println!("{:?}", get_byte_frequencies(&bytes));
+    } else if let Some(path) = cli.stats {
+        let stats = Stats::from_bytes_with(&get_file_bytes(&path), cli.tree_mode);
+
+        if cli.json {
+            println!("{}", stats.to_json());
+        } else {
+            print!("{}", stats);
+        }
+    } else if let (Some(input), Some(output), Some(level)) =
+        (&cli.compops.compress, &cli.output, cli.compops.bzip2)
+    {
+        let compressed = compress_bzip2(&get_file_bytes(input), level);
+        File::create_new(output)?.write_all(&compressed)?;
     } else if let (Some(input), Some(output)) = (&cli.compops.compress, &cli.output) {
         if let (Some(input), Some(output)) = (input.to_str(), output.to_str()) {
-            CompressFile::new().compress(input)?.output_freq(output)?;
+            let compress = CompressFile::new().options(
+                CompressOptions::new()
+                    .tree_mode(cli.tree_mode)
+                    .coder(cli.compops.coder.unwrap_or_default())
+                    .bwt(cli.compops.bwt),
+            );
+
+            #[cfg(feature = "mmap")]
+            if cli.compops.mmap {
+                return compress.compress_mapped(input, output);
+            }
+
+            compress.compress(input)?.output_freq(output)?;
         }
     } else if let (Some(input), Some(output)) = (&cli.decompops.decompress, &cli.output) {
-        if let (Some(input), Some(output)) = (input.to_str(), output.to_str()) {
+        if is_bzip2(input)? {
+            let decompressed =
+                decompress_bzip2_with_options(&get_file_bytes(input), &cli.decompops.options())?;
+            File::create_new(output)?.write_all(&decompressed)?;
+        } else if let (Some(input), Some(output)) = (input.to_str(), output.to_str()) {
             DecompressFile::new()
+                .options(cli.decompops.options())
                 .decompress_freq(input)?
                 .output(output)?;
         }
@@ -0,0 +1,206 @@
+use std::{
+    fs::File,
+    io::{self, Seek, SeekFrom, Write},
+};
+
+use memmap2::Mmap;
+#[cfg(unix)]
+use memmap2::{Advice, UncheckedAdvice};
+
+use crate::{
+    fileops::{code_array, write_freq_header, CodeWriter},
+    get_byte_frequencies,
+    header::invalid_data,
+    CodeTable, Coder, CompressOptions, HuffCode, HuffNode,
+};
+
+///bytes of the mapping read at a time, they are dropped from memory before the next ones
+const WINDOW_SIZE: usize = 64 << 20;
+
+///Compress the file `input` into `output` like `CompressFile::compress` and `output_freq`,
+///reading it through a memory map instead of copying it into memory
+///
+///The mapping is read twice a window at a time, for the frequencies and for the codes.
+///Huffman codes without the BWT are written to `output` as they are encoded, so memory use
+///doesn't grow with the file. The other coders and the BWT need all of the input,
+///their output is built in memory and written at the end.
+///
+///`input` must not be changed while it is compressed, a byte that wasn't counted is an `InvalidData` error
+pub fn compress_file_mapped(
+    input: &str,
+    output: &str,
+    options: &CompressOptions,
+) -> io::Result<()> {
+    let input = File::open(input)?;
+    //SAFETY: like any other reader of the file, this relies on nobody changing it meanwhile
+    let map = unsafe { Mmap::map(&input)? };
+    #[cfg(unix)]
+    map.advise(Advice::Sequential)?;
+
+    let mut output = File::create_new(output)?;
+
+    if options.get_bwt() || options.get_coder() != Coder::HUFFMAN {
+        let compressed = crate::compress_to_vec_with_options(&map, options);
+        return output.write_all(&compressed);
+    }
+
+    compress_map(&map, &mut output, options, WINDOW_SIZE)
+}
+
+///huffman codes for `map` with their header, written to `output` `window_size` bytes of input at a time
+fn compress_map(
+    map: &Mmap,
+    output: &mut File,
+    options: &CompressOptions,
+    window_size: usize,
+) -> io::Result<()> {
+    let mut frequencies = [0; 256];
+    for (i, window) in map.chunks(window_size).enumerate() {
+        let counts = get_byte_frequencies(window);
+        for (frequency, count) in frequencies.iter_mut().zip(counts) {
+            *frequency += count;
+        }
+        release(map, i * window_size, window.len());
+    }
+
+    //the size of the last byte is only known once everything is encoded
+    let mut header = Vec::new();
+    write_freq_header(&frequencies, 0, options, &mut header);
+    output.write_all(&header)?;
+
+    let huff = HuffNode::from_frequencies_with(&frequencies, options.get_tree_mode());
+    let table = HuffCode::from_tree(&huff);
+    let codes = code_array(&table);
+
+    let mut buffer = Vec::with_capacity(window_size / 2);
+    let mut writer = CodeWriter::new(&mut buffer);
+
+    for (i, window) in map.chunks(window_size).enumerate() {
+        encode_window(&mut writer, window, &codes, &table)?;
+
+        //the bits that don't fill a word yet stay in the writer
+        output.write_all(writer.output)?;
+        writer.output.clear();
+        release(map, i * window_size, window.len());
+    }
+
+    let last_byte_length = writer.finish();
+    output.write_all(&buffer)?;
+
+    if last_byte_length != 0 {
+        output.seek(SeekFrom::Start(8))?;
+        output.write_all(&[header[8] | last_byte_length])?;
+    }
+
+    Ok(())
+}
+
+///codes of a window, the bytes that weren't there when the frequencies were counted don't have one
+fn encode_window(
+    writer: &mut CodeWriter,
+    window: &[u8],
+    codes: &[(u64, u32); 256],
+    table: &CodeTable,
+) -> io::Result<()> {
+    for byte in window {
+        writer
+            .put_byte(*byte, codes, table)
+            .map_err(|_| invalid_data("input changed while it was compressed"))?;
+    }
+
+    Ok(())
+}
+
+///drop the pages of a window that was read, so they don't count towards the memory of the process
+fn release(map: &Mmap, offset: usize, len: usize) {
+    //SAFETY: the mapping is read only, dropped pages are read from the file again if they are used
+    #[cfg(unix)]
+    unsafe {
+        //only advice, the pages stay if it fails
+        let _ = map.unchecked_advise_range(UncheckedAdvice::DontNeed, offset, len);
+    }
+
+    #[cfg(not(unix))]
+    let _ = (map, offset, len);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress_to_vec_with_options, TreeMode};
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "learning_huffman_mmap_{}_{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_file(&path);
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn compress_file_mapped_test() {
+        let readme = std::fs::read("./README.md").unwrap();
+        let options = [
+            CompressOptions::new(),
+            CompressOptions::new().tree_mode(TreeMode::Legacy),
+            CompressOptions::new().coder(Coder::HUFFMAN4),
+            CompressOptions::new().bwt(true),
+        ];
+
+        for (i, bytes) in [&readme[..], b"", b"aaaa"].into_iter().enumerate() {
+            let input = temp_path(&format!("input_{i}"));
+            std::fs::write(&input, bytes).unwrap();
+
+            for (j, options) in options.iter().enumerate() {
+                let output = temp_path(&format!("output_{i}_{j}"));
+                compress_file_mapped(&input, &output, options).unwrap();
+
+                assert!(
+                    std::fs::read(&output).unwrap() == compress_to_vec_with_options(bytes, options),
+                    "{options:?}"
+                );
+                std::fs::remove_file(output).unwrap();
+            }
+            std::fs::remove_file(input).unwrap();
+        }
+    }
+
+    #[test]
+    fn windows_test() {
+        let bytes = std::fs::read("./README.md").unwrap();
+        let input = temp_path("windows");
+        std::fs::write(&input, &bytes).unwrap();
+        let map = unsafe { Mmap::map(&File::open(&input).unwrap()).unwrap() };
+
+        //windows that don't end on a word or a page
+        for window_size in [1, 7, 1000, 4099] {
+            let output = temp_path(&format!("windows_{window_size}"));
+            compress_map(
+                &map,
+                &mut File::create_new(&output).unwrap(),
+                &CompressOptions::new(),
+                window_size,
+            )
+            .unwrap();
+
+            assert!(std::fs::read(&output).unwrap() == crate::compress_to_vec(&bytes));
+            std::fs::remove_file(output).unwrap();
+        }
+        std::fs::remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn changed_input_test() {
+        let huff = HuffNode::from_frequencies(&get_byte_frequencies(b"abc"));
+        let table = HuffCode::from_tree(&huff);
+        let codes = code_array(&table);
+        let mut buffer = Vec::new();
+        let mut writer = CodeWriter::new(&mut buffer);
+
+        assert!(encode_window(&mut writer, b"cab", &codes, &table).is_ok());
+        let error = encode_window(&mut writer, b"abd", &codes, &table).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,312 @@
+use alloc::vec::Vec;
+
+use crate::{fileops::repeat_byte, header::invalid_data, io};
+
+///scaled frequencies add up to at most 2^16, so every symbol keeps a range of at least 2^8
+const TOTAL_BITS: u32 = 16;
+
+///the range is renormalized once it drops below this
+const TOP: u32 = 1 << 24;
+
+///bytes the encoder flushes at the end and the decoder reads up front
+const FLUSH_SIZE: usize = 5;
+
+///Frequencies from the header, scaled the same way by encoder and decoder
+struct Model {
+    frequencies: [u32; 256],
+    starts: [u32; 257],
+}
+
+impl Model {
+    ///every byte with a frequency other than 0 keeps at least 1
+    fn new(frequencies: &[usize; 256]) -> Self {
+        let sum: u128 = frequencies.iter().map(|freq| *freq as u128).sum();
+        let target = (1 << TOTAL_BITS) - 256;
+
+        let mut scaled = [0; 256];
+        for (scaled, freq) in scaled.iter_mut().zip(frequencies) {
+            if *freq != 0 {
+                *scaled = if sum <= 1 << TOTAL_BITS {
+                    *freq as u32
+                } else {
+                    ((*freq as u128 * target / sum) as u32).max(1)
+                };
+            }
+        }
+
+        let mut starts = [0; 257];
+        for byte in 0..256 {
+            starts[byte + 1] = starts[byte] + scaled[byte];
+        }
+
+        Model {
+            frequencies: scaled,
+            starts,
+        }
+    }
+
+    fn total(&self) -> u32 {
+        self.starts[256]
+    }
+
+    ///byte for every value in 0..total
+    fn symbols(&self) -> Vec<u8> {
+        let mut symbols = Vec::with_capacity(self.total() as usize);
+        for (byte, freq) in self.frequencies.iter().enumerate() {
+            symbols.resize(symbols.len() + *freq as usize, byte as u8);
+        }
+
+        symbols
+    }
+}
+
+///Carry-propagating range encoder, as used by LZMA
+struct Encoder<'a> {
+    low: u64,
+    range: u32,
+    cache: u8,
+    cache_size: usize,
+    compressed_bytes: &'a mut Vec<u8>,
+}
+
+impl<'a> Encoder<'a> {
+    fn new(compressed_bytes: &'a mut Vec<u8>) -> Self {
+        Encoder {
+            low: 0,
+            range: u32::MAX,
+            cache: 0,
+            cache_size: 1,
+            compressed_bytes,
+        }
+    }
+
+    fn encode(&mut self, start: u32, freq: u32, total: u32) {
+        let r = self.range / total;
+        self.low += start as u64 * r as u64;
+        self.range = r * freq;
+
+        while self.range < TOP {
+            self.range <<= 8;
+            self.shift_low();
+        }
+    }
+
+    ///move the top byte of `low` out, holding back 0xff bytes a carry could still change
+    fn shift_low(&mut self) {
+        if (self.low as u32) < 0xff00_0000 || self.low > u32::MAX as u64 {
+            let carry = (self.low >> 32) as u8;
+            let mut byte = self.cache;
+
+            for _ in 0..self.cache_size {
+                self.compressed_bytes.push(byte.wrapping_add(carry));
+                byte = 0xff;
+            }
+
+            self.cache_size = 0;
+            self.cache = (self.low >> 24) as u8;
+        }
+
+        self.cache_size += 1;
+        self.low = (self.low & 0x00ff_ffff) << 8;
+    }
+
+    fn finish(mut self) {
+        for _ in 0..FLUSH_SIZE {
+            self.shift_low();
+        }
+    }
+}
+
+struct Decoder<'a> {
+    code: u32,
+    range: u32,
+    bytes: core::slice::Iter<'a, u8>,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8]) -> io::Result<Self> {
+        let mut decoder = Decoder {
+            code: 0,
+            range: u32::MAX,
+            bytes: bytes.iter(),
+        };
+
+        //the encoder always starts with a 0 byte
+        if decoder.next_byte()? != 0 {
+            return Err(invalid_data("range coded payload doesn't start with 0"));
+        }
+        for _ in 1..FLUSH_SIZE {
+            decoder.code = decoder.code << 8 | decoder.next_byte()? as u32;
+        }
+
+        Ok(decoder)
+    }
+
+    fn next_byte(&mut self) -> io::Result<u8> {
+        self.bytes.next().copied().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "range coded payload ended")
+        })
+    }
+
+    ///the value in 0..total the next symbol was encoded at, `decode` has to follow
+    fn value(&mut self, total: u32) -> io::Result<(u32, u32)> {
+        let r = self.range / total;
+        let value = self.code / r;
+
+        if value >= total {
+            return Err(invalid_data("range coded value out of range"));
+        }
+
+        Ok((value, r))
+    }
+
+    fn decode(&mut self, start: u32, freq: u32, r: u32) -> io::Result<()> {
+        self.code -= start * r;
+        self.range = r * freq;
+
+        while self.range < TOP {
+            self.code = self.code << 8 | self.next_byte()? as u32;
+            self.range <<= 8;
+        }
+
+        Ok(())
+    }
+}
+
+///append `uncompressed_bytes` range coded with `frequencies` to `compressed_bytes`
+///
+///`frequencies` have to be those of `uncompressed_bytes`, like `get_byte_frequencies` returns.
+///Nothing is written for fewer than 2 different bytes, their output is known from the frequencies alone
+pub fn encode_range(
+    uncompressed_bytes: &[u8],
+    frequencies: &[usize; 256],
+    compressed_bytes: &mut Vec<u8>,
+) {
+    if frequencies.iter().filter(|freq| **freq != 0).count() < 2 {
+        return;
+    }
+
+    let model = Model::new(frequencies);
+    let mut encoder = Encoder::new(compressed_bytes);
+
+    for byte in uncompressed_bytes {
+        let byte = *byte as usize;
+        encoder.encode(model.starts[byte], model.frequencies[byte], model.total());
+    }
+
+    encoder.finish();
+}
+
+///decode a payload written by `encode_range` with the same `frequencies`
+///
+///never panics or outputs more than the frequencies add up to, whatever `bytes` contains
+pub fn decode_range(bytes: &[u8], frequencies: &[usize; 256]) -> io::Result<Vec<u8>> {
+    let total = frequencies
+        .iter()
+        .try_fold(0usize, |total, freq| total.checked_add(*freq))
+        .ok_or_else(|| invalid_data("sum of frequencies overflows"))?;
+
+    let mut used = frequencies
+        .iter()
+        .enumerate()
+        .filter(|(_, freq)| **freq != 0);
+
+    match (used.next(), used.next()) {
+        (None, _) => return Ok(Vec::new()),
+        (Some((byte, _)), None) => return repeat_byte(byte as u8, total),
+        _ => {}
+    }
+
+    let model = Model::new(frequencies);
+    let symbols = model.symbols();
+    let mut decoder = Decoder::new(bytes)?;
+    let mut decompressed_bytes = Vec::new();
+
+    for _ in 0..total {
+        let (value, r) = decoder.value(model.total())?;
+        let byte = symbols[value as usize] as usize;
+
+        decoder.decode(model.starts[byte], model.frequencies[byte], r)?;
+        decompressed_bytes.push(byte as u8);
+    }
+
+    Ok(decompressed_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_byte_frequencies;
+
+    fn round_trip(bytes: &[u8]) -> Vec<u8> {
+        let frequencies = get_byte_frequencies(bytes);
+        let mut compressed = Vec::new();
+        encode_range(bytes, &frequencies, &mut compressed);
+
+        let decompressed = decode_range(&compressed, &frequencies).unwrap();
+        assert!(decompressed == bytes);
+
+        compressed
+    }
+
+    #[test]
+    fn round_trip_test() {
+        assert_eq!(round_trip(b"AACD").len(), FLUSH_SIZE);
+        assert!(round_trip(&[]).is_empty());
+        assert!(round_trip(b"ZZZZ").is_empty());
+
+        let bytes = std::fs::read("./test_files/135-0.txt").unwrap();
+        round_trip(&bytes);
+    }
+
+    #[test]
+    fn carry_test() {
+        //long runs of the likely symbol push `low` up to 0xff.. and make carries happen
+        let mut bytes = vec![0; 100_000];
+        for i in (0..bytes.len()).step_by(997) {
+            bytes[i] = (i % 255) as u8 + 1;
+        }
+
+        round_trip(&bytes);
+    }
+
+    #[test]
+    fn skewed_test() {
+        //huffman can't spend less than a bit on a byte
+        let mut bytes = vec![b'a'; 10_000];
+        bytes.extend_from_slice(b"bc");
+
+        assert!(round_trip(&bytes).len() < 10_000 / 8 / 10);
+    }
+
+    #[test]
+    fn scaled_frequencies_test() {
+        let mut frequencies = [0; 256];
+        frequencies[0] = usize::MAX / 2;
+        frequencies[1] = 1;
+        frequencies[2] = 1 << 20;
+        let model = Model::new(&frequencies);
+
+        assert!(model.total() <= 1 << TOTAL_BITS);
+        assert_eq!(model.frequencies[1], 1);
+        assert_eq!(model.frequencies[3], 0);
+    }
+
+    #[test]
+    fn decode_invalid_test() {
+        let frequencies = get_byte_frequencies(b"AACD");
+        let mut compressed = Vec::new();
+        encode_range(b"AACD", &frequencies, &mut compressed);
+
+        let err = decode_range(&compressed[..3], &frequencies).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        compressed[0] = 1;
+        let err = decode_range(&compressed, &frequencies).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        for byte in 0..=255 {
+            let _ = decode_range(&[0, byte, 255, 255, 255, 255], &frequencies);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt::{self, Write};
+
+use crate::{get_byte_frequencies, HuffCode, HuffNode, TreeMode};
+
+///Statistics of a single byte value in the input
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolStats {
+    pub byte: u8,
+    pub count: usize,
+    pub probability: f64,
+    pub length: u16,
+    ///code bits in the order they are written
+    pub code: String,
+}
+
+///Report on how well the input compresses, see `Stats::from_bytes`
+///
+///Sizes are in bytes, entropy and code lengths in bits per input byte
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub input_size: usize,
+    pub entropy: f64,
+    pub average_code_length: f64,
+    pub max_code_length: u16,
+    ///variance of the code length per input byte around `average_code_length`
+    pub code_length_variance: f64,
+    ///smallest possible payload for a byte-wise code, `entropy` * `input_size`
+    pub theoretical_min_size: usize,
+    pub payload_size: usize,
+    pub header_size: usize,
+    ///`input_size` / (`header_size` + `payload_size`)
+    pub ratio: f64,
+    ///most frequent first
+    pub symbols: Vec<SymbolStats>,
+}
+
+impl Stats {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self::from_bytes_with(bytes, TreeMode::Standard)
+    }
+
+    ///like `from_bytes`, for a tree built with `mode`
+    pub fn from_bytes_with(bytes: &[u8], mode: TreeMode) -> Self {
+        Self::from_frequencies_with(&get_byte_frequencies(bytes), mode)
+    }
+
+    pub fn from_frequencies(frequencies: &[usize; 256]) -> Self {
+        Self::from_frequencies_with(frequencies, TreeMode::Standard)
+    }
+
+    ///like `from_frequencies`, for a tree built with `mode`
+    pub fn from_frequencies_with(frequencies: &[usize; 256], mode: TreeMode) -> Self {
+        let table = HuffCode::from_tree(&HuffNode::from_frequencies_with(frequencies, mode));
+        let input_size: usize = frequencies.iter().sum();
+
+        let mut symbols: Vec<SymbolStats> = table
+            .iter()
+            .map(|(byte, code)| SymbolStats {
+                byte,
+                count: frequencies[byte as usize],
+                probability: frequencies[byte as usize] as f64 / input_size as f64,
+                length: code.get_length(),
+                code: code.to_bit_string(),
+            })
+            .collect();
+        symbols.sort_by(|a, b| b.count.cmp(&a.count).then(a.byte.cmp(&b.byte)));
+
+        let entropy: f64 = symbols
+            .iter()
+            .map(|symbol| symbol.probability * (1.0 / symbol.probability).log2())
+            .sum();
+
+        let payload_bits: usize = symbols
+            .iter()
+            .map(|symbol| symbol.count * symbol.length as usize)
+            .sum();
+
+        //same layout `CompressFile::output_freq` writes
+        let header_size = 8 + 1 + symbols.len() * (1 + 8);
+        let payload_size = payload_bits.div_ceil(8);
+
+        let average_code_length = if input_size == 0 {
+            0.0
+        } else {
+            payload_bits as f64 / input_size as f64
+        };
+
+        let code_length_variance = symbols
+            .iter()
+            .map(|symbol| symbol.probability * (symbol.length as f64 - average_code_length).powi(2))
+            .sum();
+
+        Stats {
+            input_size,
+            entropy,
+            average_code_length,
+            max_code_length: symbols
+                .iter()
+                .map(|symbol| symbol.length)
+                .max()
+                .unwrap_or(0),
+            code_length_variance,
+            theoretical_min_size: (entropy * input_size as f64 / 8.0).ceil() as usize,
+            payload_size,
+            header_size,
+            ratio: input_size as f64 / (header_size + payload_size) as f64,
+            symbols,
+        }
+    }
+
+    pub fn compressed_size(&self) -> usize {
+        self.header_size + self.payload_size
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+
+        write!(
+            json,
+            "{{\"input_size\":{},\"entropy\":{},\"average_code_length\":{},\
+             \"max_code_length\":{},\"code_length_variance\":{},\
+             \"theoretical_min_size\":{},\"payload_size\":{},\"header_size\":{},\
+             \"compressed_size\":{},\"ratio\":{},\"symbols\":[",
+            self.input_size,
+            self.entropy,
+            self.average_code_length,
+            self.max_code_length,
+            self.code_length_variance,
+            self.theoretical_min_size,
+            self.payload_size,
+            self.header_size,
+            self.compressed_size(),
+            self.ratio,
+        )
+        .unwrap();
+
+        for (i, symbol) in self.symbols.iter().enumerate() {
+            if i != 0 {
+                json.push(',');
+            }
+
+            write!(
+                json,
+                "{{\"byte\":{},\"char\":\"{}\",\"count\":{},\"probability\":{},\
+                 \"length\":{},\"code\":\"{}\"}}",
+                symbol.byte,
+                json_escape(&escape_byte(symbol.byte)),
+                symbol.count,
+                symbol.probability,
+                symbol.length,
+                symbol.code,
+            )
+            .unwrap();
+        }
+
+        json.push_str("]}");
+        json
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "input size:          {} bytes", self.input_size)?;
+        writeln!(f, "entropy:             {:.4} bits/byte", self.entropy)?;
+        writeln!(
+            f,
+            "average code length: {:.4} bits/byte",
+            self.average_code_length
+        )?;
+        writeln!(f, "max code length:     {} bits", self.max_code_length)?;
+        writeln!(f, "length variance:     {:.4}", self.code_length_variance)?;
+        writeln!(
+            f,
+            "theoretical minimum: {} bytes",
+            self.theoretical_min_size
+        )?;
+        writeln!(f, "payload size:        {} bytes", self.payload_size)?;
+        writeln!(f, "header size:         {} bytes", self.header_size)?;
+        writeln!(f, "compressed size:     {} bytes", self.compressed_size())?;
+        writeln!(f, "compression ratio:   {:.4}:1", self.ratio)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<6} {:>12} {:>12} {:>6}  code",
+            "char", "count", "probability", "length"
+        )?;
+
+        for symbol in &self.symbols {
+            writeln!(
+                f,
+                "{:<6} {:>12} {:>12.6} {:>6}  {}",
+                escape_byte(symbol.byte),
+                symbol.count,
+                symbol.probability,
+                symbol.length,
+                symbol.code
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+///printable ascii as is, everything else as an escape like `\n` or `\xff`
+pub(crate) fn escape_byte(byte: u8) -> String {
+    std::ascii::escape_default(byte).to_string()
+}
+
+fn json_escape(string: &str) -> String {
+    string.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compress_to_vec;
+
+    #[test]
+    fn from_bytes_test() {
+        let stats = Stats::from_bytes(b"AACD");
+
+        assert_eq!(stats.input_size, 4);
+        assert_eq!(stats.entropy, 1.5);
+        assert_eq!(stats.average_code_length, 1.5);
+        assert_eq!(stats.max_code_length, 2);
+        assert_eq!(stats.code_length_variance, 0.25);
+        assert_eq!(stats.theoretical_min_size, 1);
+        assert_eq!(stats.payload_size, 1);
+        assert_eq!(stats.header_size, 36);
+
+        assert_eq!(stats.symbols[0].byte, b'A');
+        assert_eq!(stats.symbols[0].probability, 0.5);
+        assert_eq!(stats.symbols[0].code, "0");
+        assert_eq!(stats.symbols[1].code, "11");
+        assert_eq!(stats.symbols[2].code, "10");
+    }
+
+    #[test]
+    fn compressed_size_test() {
+        let bytes = std::fs::read("./test_files/135-0.txt").unwrap();
+        let stats = Stats::from_bytes(&bytes);
+
+        assert_eq!(stats.compressed_size(), compress_to_vec(&bytes).len());
+        assert!(stats.entropy <= stats.average_code_length);
+        assert!(stats.average_code_length < stats.entropy + 1.0);
+        assert!(stats.theoretical_min_size <= stats.payload_size);
+    }
+
+    #[test]
+    fn empty_test() {
+        let stats = Stats::from_bytes(&[]);
+
+        assert_eq!(stats.entropy, 0.0);
+        assert_eq!(stats.average_code_length, 0.0);
+        assert_eq!(stats.compressed_size(), 9);
+        assert!(stats.symbols.is_empty());
+    }
+
+    #[test]
+    fn minimum_variance_test() {
+        //consecutive bytes, where the legacy tree can be deeper than needed
+        let mut frequencies = [0; 256];
+        frequencies[60..71].copy_from_slice(&[2, 4, 1, 2, 2, 2, 1, 1, 1, 2, 3]);
+
+        let legacy = Stats::from_frequencies_with(&frequencies, TreeMode::Legacy);
+        let minimum = Stats::from_frequencies_with(&frequencies, TreeMode::Standard);
+
+        assert_eq!(legacy.average_code_length, minimum.average_code_length);
+        assert_eq!(legacy.payload_size, minimum.payload_size);
+        assert_eq!(minimum.max_code_length, 4);
+        assert!(legacy.max_code_length >= minimum.max_code_length);
+        assert!(legacy.code_length_variance >= minimum.code_length_variance);
+    }
+
+    #[test]
+    fn to_json_test() {
+        let json = Stats::from_bytes(b"\"\"\\\n").to_json();
+
+        assert!(json.starts_with("{\"input_size\":4,\"entropy\":1.5,"));
+        assert!(json.contains("{\"byte\":34,\"char\":\"\\\\\\\"\",\"count\":2,"));
+        assert!(json.contains("{\"byte\":10,\"char\":\"\\\\n\","));
+        assert!(json.ends_with("}]}"));
+    }
+}
@@ -0,0 +1,427 @@
+use alloc::vec::Vec;
+
+use crate::{
+    compress_into_with_options,
+    fileops::{payload_ended, CodeDecoder},
+    io, Coder, CompressOptions, DecompressOptions, FreqHeader, HuffCode, HuffNode,
+};
+
+///What `Encoder` and `Decoder` have in common, for wrappers that work with either
+///
+///Neither does any IO, the caller moves the bytes in and out
+pub trait StreamCodec {
+    ///take the next piece of the input, appending any output that is ready to `output`
+    fn feed(&mut self, bytes: &[u8], output: &mut Vec<u8>) -> io::Result<()>;
+
+    ///end the input, appending the rest of the output to `output`
+    fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()>;
+}
+
+fn already_finished() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "already finished")
+}
+
+///Compresses input that arrives in pieces, the output is the same as `compress_to_vec_with_options`
+///
+///The frequency header needs all of the input, so everything is written by `finish`
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    options: CompressOptions,
+    input: Vec<u8>,
+    finished: bool,
+}
+
+impl Encoder {
+    pub fn new(options: CompressOptions) -> Self {
+        Encoder {
+            options,
+            input: Vec::new(),
+            finished: false,
+        }
+    }
+
+    pub fn get_options(&self) -> &CompressOptions {
+        &self.options
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new(CompressOptions::new())
+    }
+}
+
+impl StreamCodec for Encoder {
+    fn feed(&mut self, bytes: &[u8], _output: &mut Vec<u8>) -> io::Result<()> {
+        if self.finished {
+            return Err(already_finished());
+        }
+
+        self.input.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        if self.finished {
+            return Err(already_finished());
+        }
+        self.finished = true;
+
+        let input = core::mem::take(&mut self.input);
+        compress_into_with_options(&input, output, &self.options);
+        Ok(())
+    }
+}
+
+///What `Decoder::feed` needs next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeStatus {
+    ///more of the input, or `finish` if there is no more
+    NeedInput,
+    ///all of the output was given, the rest of the input is ignored and `finish` only checks it
+    Done,
+}
+
+///Decompresses input that arrives in pieces, like `decompress_to_vec_with_options`
+///
+///The header is read and checked against the limits as soon as all of it arrived.
+///Huffman payloads without the bwt are then decoded as they arrive, codes can be split
+///between pieces anywhere. The other coders and the bwt need all of the payload,
+///it is decoded by `finish`
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    options: DecompressOptions,
+    ///the header while it is incomplete
+    input: Vec<u8>,
+    header: Option<FreqHeader>,
+    payload: Payload,
+    ///bytes of the payload fed so far
+    payload_size: usize,
+    finished: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Payload {
+    ///every byte but the last one, which is the only one that can be partly filled,
+    ///is decoded as soon as it arrives. The last one is decoded early if its first
+    ///`last_bits` bits complete the output, the rest of it doesn't matter then
+    Codes {
+        decoder: CodeDecoder,
+        last: Option<u8>,
+        last_bits: u8,
+    },
+    Buffered(Vec<u8>),
+}
+
+impl Decoder {
+    pub fn new(options: DecompressOptions) -> Self {
+        Decoder {
+            options,
+            input: Vec::new(),
+            header: None,
+            payload: Payload::Buffered(Vec::new()),
+            payload_size: 0,
+            finished: false,
+        }
+    }
+
+    pub fn get_options(&self) -> &DecompressOptions {
+        &self.options
+    }
+
+    ///`None` until all of it was fed
+    pub fn get_header(&self) -> Option<&FreqHeader> {
+        self.header.as_ref()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    ///take the next piece of the input, appending what can already be decoded to `output`
+    pub fn feed(&mut self, bytes: &[u8], output: &mut Vec<u8>) -> io::Result<DecodeStatus> {
+        if self.finished {
+            return Err(already_finished());
+        }
+
+        if self.header.is_some() {
+            return self.feed_payload(bytes, output);
+        }
+
+        self.input.extend_from_slice(bytes);
+        if !self.read_header()? {
+            return Ok(DecodeStatus::NeedInput);
+        }
+
+        let payload = core::mem::take(&mut self.input);
+        self.feed_payload(&payload, output)
+    }
+
+    ///end the input, appending the rest of the output to `output`
+    pub fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        if self.finished {
+            return Err(already_finished());
+        }
+        self.finished = true;
+
+        let Some(header) = &self.header else {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "input ended in the header",
+            ));
+        };
+
+        self.options.check(
+            header.total(),
+            header.size().saturating_add(self.payload_size),
+        )?;
+
+        match &mut self.payload {
+            Payload::Buffered(payload) => {
+                output.extend(header.decode_with_options(payload, &self.options)?);
+            }
+            Payload::Codes {
+                decoder,
+                last,
+                last_bits,
+            } => {
+                if let Some(byte) = last.take() {
+                    decoder.decode_byte(byte, *last_bits, output)?;
+                }
+
+                if decoder.remaining() != 0 {
+                    let total = header.total();
+                    return Err(payload_ended(total - decoder.remaining(), total));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    ///read the header once it is complete, any error but running out of input is final
+    fn read_header(&mut self) -> io::Result<bool> {
+        let mut payload = &self.input[..];
+        let header = match FreqHeader::read(&mut payload, &self.options) {
+            Ok(header) => header,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(err) => return Err(err),
+        };
+
+        //the other limits need the size of the payload, they are checked by `finish`
+        self.options.check_output_size(header.total())?;
+
+        let table = HuffCode::from_tree(&HuffNode::from_frequencies_with(
+            header.frequencies(),
+            header.tree_mode(),
+        ));
+
+        //a lone symbol has no codes, `decode_with_options` repeats it
+        if header.coder() == Coder::HUFFMAN && !header.bwt() && table.len() > 1 {
+            self.payload = Payload::Codes {
+                decoder: CodeDecoder::new(&table, header.total())?,
+                last: None,
+                //a last byte size of 0 means it was filled completely
+                last_bits: match header.last_byte_size() {
+                    0 => 8,
+                    size => size,
+                },
+            };
+        }
+
+        self.input.drain(..header.size());
+        self.header = Some(header);
+        Ok(true)
+    }
+
+    fn feed_payload(&mut self, bytes: &[u8], output: &mut Vec<u8>) -> io::Result<DecodeStatus> {
+        self.payload_size = self.payload_size.saturating_add(bytes.len());
+
+        match &mut self.payload {
+            Payload::Buffered(payload) => {
+                payload.extend_from_slice(bytes);
+                Ok(DecodeStatus::NeedInput)
+            }
+            Payload::Codes {
+                decoder,
+                last,
+                last_bits,
+            } => {
+                for byte in bytes {
+                    if let Some(full) = last.replace(*byte) {
+                        decoder.decode_byte(full, 8, output)?;
+                    }
+                }
+
+                if let Some(byte) = *last {
+                    if decoder.remaining() != 0 && decoder.ends_within(byte, *last_bits) {
+                        decoder.decode_byte(byte, 8, output)?;
+                        *last = None;
+                    }
+                }
+
+                match decoder.remaining() {
+                    0 => Ok(DecodeStatus::Done),
+                    _ => Ok(DecodeStatus::NeedInput),
+                }
+            }
+        }
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new(DecompressOptions::new())
+    }
+}
+
+impl StreamCodec for Decoder {
+    fn feed(&mut self, bytes: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        Decoder::feed(self, bytes, output).map(|_| ())
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        Decoder::finish(self, output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress_to_vec_with_options, Coder, LimitError, CODERS};
+
+    fn in_pieces(codec: &mut impl StreamCodec, bytes: &[u8], size: usize) -> io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        for piece in bytes.chunks(size) {
+            codec.feed(piece, &mut output)?;
+        }
+        codec.finish(&mut output)?;
+
+        Ok(output)
+    }
+
+    #[test]
+    fn encoder_decoder_test() {
+        let bytes = std::fs::read("./README.md").unwrap();
+
+        for coder in CODERS {
+            for bwt in [false, true] {
+                let options = CompressOptions::new().coder(coder).bwt(bwt);
+                for size in [1, 7, 4096] {
+                    let compressed = in_pieces(&mut Encoder::new(options), &bytes, size).unwrap();
+                    assert!(compressed == compress_to_vec_with_options(&bytes, &options));
+
+                    let decompressed = in_pieces(&mut Decoder::default(), &compressed, size);
+                    assert!(decompressed.unwrap() == bytes, "{coder:?} {bwt} {size}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn decoder_header_test() {
+        let compressed = crate::compress_to_vec(b"abracadabra");
+        let mut decoder = Decoder::default();
+        let mut output = Vec::new();
+
+        decoder.feed(&compressed[..8], &mut output).unwrap();
+        assert!(decoder.get_header().is_none());
+        decoder.feed(&compressed[8..], &mut output).unwrap();
+        assert_eq!(decoder.get_header().unwrap().total(), 11);
+
+        //the limit fails as soon as the header is there
+        let options = DecompressOptions::new().max_output_size(10);
+        let err = in_pieces(&mut Decoder::new(options), &compressed, 20).unwrap_err();
+        assert!(matches!(
+            LimitError::from_io(&err),
+            Some(LimitError::OutputSize { .. })
+        ));
+    }
+
+    #[test]
+    fn decoder_incremental_test() {
+        let bytes = std::fs::read("./README.md").unwrap();
+        let compressed = crate::compress_to_vec(&bytes);
+
+        let mut decoder = Decoder::default();
+        let mut output = Vec::new();
+        let mut status = DecodeStatus::NeedInput;
+        for (i, byte) in compressed.iter().enumerate() {
+            //output comes out as the codes arrive, not only from `finish`
+            if i == compressed.len() / 2 {
+                assert!(output.len() > bytes.len() / 3);
+            }
+            status = decoder.feed(&[*byte], &mut output).unwrap();
+        }
+
+        //the last code ends in the last byte, nothing is left for `finish`
+        assert_eq!(status, DecodeStatus::Done);
+        assert!(output == bytes);
+        decoder.finish(&mut output).unwrap();
+        assert!(output == bytes);
+
+        //the bwt needs all of the payload
+        let options = CompressOptions::new().bwt(true);
+        let compressed = compress_to_vec_with_options(&bytes, &options);
+        let mut decoder = Decoder::default();
+        let mut output = Vec::new();
+        assert_eq!(
+            decoder.feed(&compressed, &mut output).unwrap(),
+            DecodeStatus::NeedInput
+        );
+        assert!(output.is_empty());
+        decoder.finish(&mut output).unwrap();
+        assert!(output == bytes);
+    }
+
+    #[test]
+    fn decoder_matches_whole_input_test() {
+        let bytes = b"a mostly boring payload, ending in a few rare bytes: xyz";
+        let compressed = crate::compress_to_vec(bytes);
+
+        //cut off, with a wrong last byte size and with bytes after the payload
+        let mut inputs = Vec::new();
+        for end in compressed.len() - 8..=compressed.len() {
+            inputs.push(compressed[..end].to_vec());
+        }
+        for size in 1..8 {
+            let mut input = compressed.clone();
+            input[8] = input[8] & !0x07 | size;
+            inputs.push(input);
+        }
+        inputs.push([&compressed[..], &[0x55; 3]].concat());
+
+        for input in inputs {
+            let whole = crate::decompress_to_vec(&input);
+            for size in [1, 2, 5] {
+                let pieces = in_pieces(&mut Decoder::default(), &input, size);
+                match (&whole, &pieces) {
+                    (Ok(whole), Ok(pieces)) => assert!(whole == pieces),
+                    (Err(whole), Err(pieces)) => assert_eq!(whole.kind(), pieces.kind()),
+                    _ => panic!("{whole:?} {pieces:?} {size} {input:?}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn errors_test() {
+        let mut encoder = Encoder::new(CompressOptions::new().coder(Coder::ANS));
+        let mut output = Vec::new();
+        encoder.finish(&mut output).unwrap();
+        assert!(encoder.is_finished());
+        assert!(encoder.feed(b"a", &mut output).is_err());
+        assert!(encoder.finish(&mut output).is_err());
+
+        let compressed = crate::compress_to_vec(b"abcabc");
+        let err = in_pieces(&mut Decoder::default(), &compressed[..10], 3).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let err = in_pieces(&mut Decoder::default(), &[0xff; 20], 3).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
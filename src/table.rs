@@ -0,0 +1,488 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    io,
+    str::{Chars, FromStr},
+};
+
+use crate::{header::invalid_data, CodeTable, HuffCode};
+
+///Human-readable formats a code table can be written in
+///
+///Each one lists every symbol (byte value) with its weight, code length and
+///code bits in the order they are written, sorted by symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableFormat {
+    Csv,
+    Json,
+    Toml,
+}
+
+impl FromStr for TableFormat {
+    type Err = io::Error;
+
+    fn from_str(format: &str) -> io::Result<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "csv" => Ok(TableFormat::Csv),
+            "json" => Ok(TableFormat::Json),
+            "toml" => Ok(TableFormat::Toml),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown table format {format}"),
+            )),
+        }
+    }
+}
+
+///one row of a table, what every format stores
+struct Entry {
+    symbol: u8,
+    weight: usize,
+    length: u16,
+    bits: String,
+}
+
+pub fn export_table(table: &CodeTable, format: TableFormat) -> String {
+    let entries: Vec<Entry> = table
+        .iter()
+        .map(|(symbol, code)| match code.get_length() {
+            //a lone symbol's code is empty, `import_table` needs at least a bit to use it
+            0 => Entry {
+                symbol,
+                weight: code.get_weight(),
+                length: 1,
+                bits: "0".to_string(),
+            },
+            length => Entry {
+                symbol,
+                weight: code.get_weight(),
+                length,
+                bits: code.to_bit_string(),
+            },
+        })
+        .collect();
+
+    let mut output = String::new();
+
+    match format {
+        TableFormat::Csv => {
+            output.push_str("symbol,weight,length,code\n");
+            for entry in entries {
+                writeln!(
+                    output,
+                    "{},{},{},{}",
+                    entry.symbol, entry.weight, entry.length, entry.bits
+                )
+                .unwrap();
+            }
+        }
+        TableFormat::Json => {
+            output.push_str("{\"codes\":[");
+            for (i, entry) in entries.iter().enumerate() {
+                if i != 0 {
+                    output.push(',');
+                }
+                write!(
+                    output,
+                    "\n  {{\"symbol\":{},\"weight\":{},\"length\":{},\"code\":\"{}\"}}",
+                    entry.symbol, entry.weight, entry.length, entry.bits
+                )
+                .unwrap();
+            }
+            output.push_str("\n]}\n");
+        }
+        TableFormat::Toml => {
+            for (i, entry) in entries.iter().enumerate() {
+                if i != 0 {
+                    output.push('\n');
+                }
+                write!(
+                    output,
+                    "[[code]]\nsymbol = {}\nweight = {}\nlength = {}\ncode = \"{}\"\n",
+                    entry.symbol, entry.weight, entry.length, entry.bits
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    output
+}
+
+///read a table written by `export_table`, or by hand in the same format
+///
+///the result is checked to be usable by `encode_with_table` and `decode_with_table`:
+///no symbol twice, lengths match the code bits, no code is empty and no code is a prefix of another.
+///A lone symbol's empty code is rejected too, as nothing tells how often it was written,
+///`export_table` writes it as `0`
+pub fn import_table(text: &str, format: TableFormat) -> io::Result<CodeTable> {
+    let fields = match format {
+        TableFormat::Csv => parse_csv(text)?,
+        TableFormat::Json => parse_json(text)?,
+        TableFormat::Toml => parse_toml(text)?,
+    };
+
+    let mut table = CodeTable::new();
+
+    for fields in fields {
+        let entry = entry_from_fields(&fields)?;
+        let code = HuffCode::from_bit_string(entry.weight, &entry.bits)
+            .ok_or_else(|| invalid_data(format!("invalid code {:?}", entry.bits)))?;
+
+        if code.get_length() != entry.length {
+            return Err(invalid_data(format!(
+                "length {} doesn't match code {}",
+                entry.length, entry.bits
+            )));
+        }
+
+        if table.insert(entry.symbol, code).is_some() {
+            return Err(invalid_data(format!("symbol {} repeated", entry.symbol)));
+        }
+    }
+
+    check_prefix_free(&table)?;
+
+    Ok(table)
+}
+
+fn entry_from_fields(fields: &HashMap<String, String>) -> io::Result<Entry> {
+    fn field<T: FromStr>(fields: &HashMap<String, String>, name: &str) -> io::Result<T> {
+        let value = fields
+            .get(name)
+            .ok_or_else(|| invalid_data(format!("missing {name}")))?;
+
+        value
+            .parse()
+            .map_err(|_| invalid_data(format!("invalid {name} {value:?}")))
+    }
+
+    Ok(Entry {
+        symbol: field(fields, "symbol")?,
+        weight: field(fields, "weight")?,
+        length: field(fields, "length")?,
+        bits: field(fields, "code")?,
+    })
+}
+
+fn check_prefix_free(table: &CodeTable) -> io::Result<()> {
+    for (symbol, code) in table.iter() {
+        if code.get_length() == 0 {
+            return Err(invalid_data(format!("empty code for symbol {symbol}")));
+        }
+
+        for (other_symbol, other) in table.iter() {
+            if symbol == other_symbol || other.get_length() < code.get_length() {
+                continue;
+            }
+
+            let mask = u128::MAX >> (128 - code.get_length());
+            if other.get_code() & mask == code.get_code() {
+                return Err(invalid_data(format!(
+                    "code of symbol {symbol} is a prefix of symbol {other_symbol}"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_csv(text: &str) -> io::Result<Vec<HashMap<String, String>>> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let names: Vec<&str> = match lines.next() {
+        Some(header) => header.split(',').map(str::trim).collect(),
+        None => return Ok(vec![]),
+    };
+
+    lines
+        .map(|line| {
+            let values: Vec<&str> = line.split(',').map(str::trim).collect();
+            if values.len() != names.len() {
+                return Err(invalid_data(format!("wrong number of columns: {line}")));
+            }
+
+            Ok(names
+                .iter()
+                .zip(values)
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect())
+        })
+        .collect()
+}
+
+///only what `export_table` writes: `[[code]]` tables of integer and string keys
+fn parse_toml(text: &str) -> io::Result<Vec<HashMap<String, String>>> {
+    let mut entries = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line == "[[code]]" {
+            entries.push(HashMap::new());
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| invalid_data(format!("invalid line: {line}")))?;
+        let value = value.trim();
+        let value = match value.strip_prefix('"') {
+            Some(value) => value
+                .strip_suffix('"')
+                .ok_or_else(|| invalid_data(format!("unterminated string: {line}")))?,
+            None => value,
+        };
+
+        entries
+            .last_mut()
+            .ok_or_else(|| invalid_data("key outside of [[code]]"))?
+            .insert(key.trim().to_string(), value.to_string());
+    }
+
+    Ok(entries)
+}
+
+///only what `export_table` writes: `{"codes": [{...}, ...]}` with integer and string values
+fn parse_json(text: &str) -> io::Result<Vec<HashMap<String, String>>> {
+    let mut parser = JsonParser {
+        chars: text.chars(),
+    };
+    let mut entries = Vec::new();
+
+    parser.expect('{')?;
+    if parser.string()? != "codes" {
+        return Err(invalid_data("expected \"codes\""));
+    }
+    parser.expect(':')?;
+    parser.expect('[')?;
+
+    let mut next = parser.next_token()?;
+    while next == '{' {
+        let mut fields = HashMap::new();
+
+        loop {
+            let key = parser.string()?;
+            parser.expect(':')?;
+            let (value, end) = parser.value()?;
+            fields.insert(key, value);
+
+            match end {
+                ',' => continue,
+                '}' => break,
+                other => return Err(invalid_data(format!("unexpected {other:?}"))),
+            }
+        }
+
+        entries.push(fields);
+
+        next = match parser.next_token()? {
+            ',' => parser.next_token()?,
+            other => other,
+        };
+    }
+
+    if next != ']' {
+        return Err(invalid_data(format!("unexpected {next:?}")));
+    }
+    parser.expect('}')?;
+
+    Ok(entries)
+}
+
+struct JsonParser<'a> {
+    chars: Chars<'a>,
+}
+
+impl JsonParser<'_> {
+    fn next_token(&mut self) -> io::Result<char> {
+        self.chars
+            .by_ref()
+            .find(|c| !c.is_whitespace())
+            .ok_or_else(|| invalid_data("unexpected end of json"))
+    }
+
+    fn expect(&mut self, expected: char) -> io::Result<()> {
+        match self.next_token()? {
+            c if c == expected => Ok(()),
+            c => Err(invalid_data(format!("expected {expected:?}, found {c:?}"))),
+        }
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        self.expect('"')?;
+        self.rest_of_string()
+    }
+
+    fn rest_of_string(&mut self) -> io::Result<String> {
+        let mut string = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => match self.chars.next() {
+                    Some(c @ ('"' | '\\' | '/')) => string.push(c),
+                    other => return Err(invalid_data(format!("unsupported escape {other:?}"))),
+                },
+                Some(c) => string.push(c),
+                None => return Err(invalid_data("unterminated string")),
+            }
+        }
+    }
+
+    ///a string or a number, returned with the token after it
+    fn value(&mut self) -> io::Result<(String, char)> {
+        match self.next_token()? {
+            '"' => {
+                let value = self.rest_of_string()?;
+                Ok((value, self.next_token()?))
+            }
+            c if c.is_ascii_digit() => {
+                let mut value = String::from(c);
+                loop {
+                    match self.chars.next() {
+                        Some(c) if c.is_ascii_digit() => value.push(c),
+                        Some(c) if c.is_whitespace() => return Ok((value, self.next_token()?)),
+                        Some(c) => return Ok((value, c)),
+                        None => return Err(invalid_data("unexpected end of json")),
+                    }
+                }
+            }
+            c => Err(invalid_data(format!(
+                "unsupported value starting with {c:?}"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_with_table, encode_with_table, get_byte_frequencies, HuffNode};
+
+    fn aacd_table() -> CodeTable {
+        HuffCode::from_tree(&HuffNode::from_frequencies(&get_byte_frequencies(b"AACD")))
+    }
+
+    #[test]
+    fn export_csv_test() {
+        assert_eq!(
+            export_table(&aacd_table(), TableFormat::Csv),
+            "symbol,weight,length,code\n65,2,1,0\n67,1,2,11\n68,1,2,10\n"
+        );
+    }
+
+    #[test]
+    fn export_json_test() {
+        assert_eq!(
+            export_table(&aacd_table(), TableFormat::Json),
+            "{\"codes\":[
+  {\"symbol\":65,\"weight\":2,\"length\":1,\"code\":\"0\"},
+  {\"symbol\":67,\"weight\":1,\"length\":2,\"code\":\"11\"},
+  {\"symbol\":68,\"weight\":1,\"length\":2,\"code\":\"10\"}
+]}
+"
+        );
+    }
+
+    #[test]
+    fn export_toml_test() {
+        assert!(export_table(&aacd_table(), TableFormat::Toml).starts_with(
+            "[[code]]\nsymbol = 65\nweight = 2\nlength = 1\ncode = \"0\"\n\n[[code]]\n"
+        ));
+    }
+
+    #[test]
+    fn import_round_trip_test() {
+        let bytes = std::fs::read("./test_files/135-0.txt").unwrap();
+        let table = HuffCode::from_tree(&HuffNode::from_frequencies(&get_byte_frequencies(&bytes)));
+
+        for format in [TableFormat::Csv, TableFormat::Json, TableFormat::Toml] {
+            let imported = import_table(&export_table(&table, format), format).unwrap();
+            assert!(imported == table, "{format:?}");
+
+            let mut compressed = Vec::new();
+            let last_byte_size = encode_with_table(&bytes, &imported, &mut compressed).unwrap();
+            assert!(decode_with_table(&compressed, &imported, last_byte_size).unwrap() == bytes);
+        }
+    }
+
+    #[test]
+    fn import_lone_symbol_test() {
+        let bytes = b"AAAA";
+        let table = HuffCode::from_tree(&HuffNode::from_frequencies(&get_byte_frequencies(bytes)));
+        assert_eq!(table[b'A'].get_length(), 0);
+
+        for format in [TableFormat::Csv, TableFormat::Json, TableFormat::Toml] {
+            let imported = import_table(&export_table(&table, format), format).unwrap();
+            assert_eq!(imported[b'A'].get_length(), 1, "{format:?}");
+
+            let mut compressed = Vec::new();
+            let last_byte_size = encode_with_table(bytes, &imported, &mut compressed).unwrap();
+            assert!(decode_with_table(&compressed, &imported, last_byte_size).unwrap() == bytes);
+        }
+    }
+
+    #[test]
+    fn import_by_hand_test() {
+        let json = r#"{ "codes": [
+            { "symbol": 104, "weight": 0, "length": 1, "code": "1" },
+            { "symbol": 105, "weight": 0, "length": 1, "code": "0" }
+        ] }"#;
+        let table = import_table(json, TableFormat::Json).unwrap();
+
+        let mut compressed = Vec::new();
+        let last_byte_size = encode_with_table(b"hihi", &table, &mut compressed).unwrap();
+
+        assert_eq!(compressed, [0b0101]);
+        assert_eq!(
+            decode_with_table(&compressed, &table, last_byte_size).unwrap(),
+            b"hihi"
+        );
+    }
+
+    #[test]
+    fn import_invalid_test() {
+        let csv = |rows: &str| {
+            import_table(
+                &format!("symbol,weight,length,code\n{rows}"),
+                TableFormat::Csv,
+            )
+        };
+
+        assert!(csv("1,1,1,0\n2,1,2,01\n").is_err(), "prefix");
+        assert!(csv("1,1,1,0\n1,1,1,1\n").is_err(), "repeated symbol");
+        assert!(csv("1,1,2,0\n2,1,1,1\n").is_err(), "wrong length");
+        assert!(csv("1,1,1,2\n2,1,1,1\n").is_err(), "not a bit");
+        assert!(csv("256,1,1,0\n").is_err(), "not a byte");
+        assert!(csv("1,1,1\n").is_err(), "missing column");
+        //nothing is written for it, so `decode_with_table` couldn't decode it
+        assert!(csv("1,1,0,\n").is_err(), "lone symbol");
+
+        assert!(import_table("[[code]]\nsymbol = 1", TableFormat::Toml).is_err());
+        assert!(import_table("{\"codes\":[{\"symbol\":1}", TableFormat::Json).is_err());
+    }
+
+    #[test]
+    fn decode_incomplete_table_test() {
+        let table =
+            import_table("symbol,weight,length,code\n1,1,2,00\n", TableFormat::Csv).unwrap();
+
+        assert!(decode_with_table(&[0b11], &table, 2).is_err());
+    }
+
+    #[test]
+    fn decode_shorter_code_first_test() {
+        //not prefix free, so only possible without `import_table`
+        let mut table = CodeTable::new();
+        table.insert(b'a', HuffCode::new(0, 1, 0b0));
+        table.insert(b'b', HuffCode::new(0, 2, 0b10));
+        table.insert(b'c', HuffCode::new(0, 1, 0b1));
+
+        assert_eq!(decode_with_table(&[0b0110], &table, 4).unwrap(), b"acca");
+    }
+}
@@ -0,0 +1,154 @@
+/*
+ * Exercises include/learning_huffman.h from C, run by tests/ffi/run.sh
+ *
+ * usage: ffi_test <input> <compressed by the command line> <output for the command line>
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "learning_huffman.h"
+
+#define CHECK(condition)                                                                           \
+    do {                                                                                           \
+        if (!(condition)) {                                                                        \
+            fprintf(stderr, "%s:%d: %s failed: %s\n", __FILE__, __LINE__, #condition,              \
+                    lh_last_error());                                                              \
+            exit(1);                                                                               \
+        }                                                                                          \
+    } while (0)
+
+static uint8_t *read_file(const char *path, size_t *len) {
+    FILE *file = fopen(path, "rb");
+    CHECK(file != NULL);
+
+    fseek(file, 0, SEEK_END);
+    *len = (size_t)ftell(file);
+    fseek(file, 0, SEEK_SET);
+
+    uint8_t *bytes = malloc(*len + 1);
+    CHECK(fread(bytes, 1, *len, file) == *len);
+    fclose(file);
+
+    return bytes;
+}
+
+static void write_file(const char *path, const uint8_t *bytes, size_t len) {
+    FILE *file = fopen(path, "wb");
+    CHECK(file != NULL);
+    CHECK(fwrite(bytes, 1, len, file) == len);
+    fclose(file);
+}
+
+static void buffers(const uint8_t *input, size_t input_len) {
+    const char *coders[] = {NULL, "huffman", "range", "ans", "huffman4"};
+    const uint32_t flags[] = {0, LH_FLAG_BWT, LH_FLAG_LEGACY_TREE};
+
+    for (size_t c = 0; c < sizeof coders / sizeof *coders; c++) {
+        for (size_t f = 0; f < sizeof flags / sizeof *flags; f++) {
+            uint8_t *compressed, *decompressed;
+            size_t compressed_len, decompressed_len;
+
+            CHECK(lh_compress(input, input_len, coders[c], flags[f], &compressed,
+                              &compressed_len) == LH_OK);
+            CHECK(lh_decompress(compressed, compressed_len, 0, &decompressed,
+                                &decompressed_len) == LH_OK);
+            CHECK(decompressed_len == input_len);
+            CHECK(memcmp(decompressed, input, input_len) == 0);
+
+            lh_free(compressed, compressed_len);
+            lh_free(decompressed, decompressed_len);
+        }
+    }
+}
+
+static void errors(const uint8_t *input, size_t input_len) {
+    uint8_t *output;
+    size_t output_len;
+
+    CHECK(lh_compress(input, input_len, "zip", 0, &output, &output_len) == LH_INVALID_ARGUMENT);
+    CHECK(strcmp(lh_last_error(), "unknown coder zip") == 0);
+    CHECK(lh_compress_new("zip", 0) == NULL);
+
+    CHECK(lh_decompress(input, input_len, 0, &output, &output_len) == LH_INVALID_DATA);
+    CHECK(strcmp(lh_status_message(LH_INVALID_DATA), "invalid data") == 0);
+    CHECK(strcmp(lh_status_message(42), "unknown status") == 0);
+
+    CHECK(lh_compress(input, input_len, NULL, 0, &output, &output_len) == LH_OK);
+    uint8_t *decompressed;
+    size_t decompressed_len;
+    CHECK(lh_decompress(output, output_len, input_len - 1, &decompressed, &decompressed_len) ==
+          LH_LIMIT);
+    lh_free(output, output_len);
+}
+
+/* compress in pieces with a context, the same as lh_compress */
+static void contexts(const uint8_t *input, size_t input_len) {
+    lh_context *context = lh_compress_new("ans", LH_FLAG_BWT);
+    CHECK(context != NULL);
+
+    for (size_t start = 0; start < input_len; start += 4096) {
+        size_t len = input_len - start < 4096 ? input_len - start : 4096;
+        CHECK(lh_write(context, input + start, len) == LH_OK);
+    }
+    CHECK(lh_finish(context) == LH_OK);
+    CHECK(lh_write(context, input, 1) == LH_INVALID_STATE);
+
+    uint8_t *expected;
+    size_t expected_len;
+    CHECK(lh_compress(input, input_len, "ans", LH_FLAG_BWT, &expected, &expected_len) == LH_OK);
+
+    uint8_t buffer[1000];
+    size_t read, position = 0;
+    do {
+        CHECK(lh_read(context, buffer, sizeof buffer, &read) == LH_OK);
+        CHECK(position + read <= expected_len);
+        CHECK(memcmp(buffer, expected + position, read) == 0);
+        position += read;
+    } while (read > 0);
+    CHECK(position == expected_len);
+    lh_context_free(context);
+
+    context = lh_decompress_new(0);
+    CHECK(lh_write(context, expected, expected_len) == LH_OK);
+    CHECK(lh_finish(context) == LH_OK);
+
+    uint8_t *decompressed = malloc(input_len + 1);
+    CHECK(lh_read(context, decompressed, input_len + 1, &read) == LH_OK);
+    CHECK(read == input_len);
+    CHECK(memcmp(decompressed, input, input_len) == 0);
+    lh_context_free(context);
+
+    free(decompressed);
+    lh_free(expected, expected_len);
+}
+
+int main(int argc, char **argv) {
+    CHECK(argc == 4);
+
+    size_t input_len, cli_len;
+    uint8_t *input = read_file(argv[1], &input_len);
+    uint8_t *cli = read_file(argv[2], &cli_len);
+
+    buffers(input, input_len);
+    errors(input, input_len);
+    contexts(input, input_len);
+
+    /* files from the command line and for it */
+    uint8_t *output;
+    size_t output_len;
+    CHECK(lh_decompress(cli, cli_len, 0, &output, &output_len) == LH_OK);
+    CHECK(output_len == input_len && memcmp(output, input, input_len) == 0);
+    lh_free(output, output_len);
+
+    CHECK(lh_compress(input, input_len, "range", 0, &output, &output_len) == LH_OK);
+    write_file(argv[3], output, output_len);
+    lh_free(output, output_len);
+
+    free(input);
+    free(cli);
+    printf("ffi_test passed\n");
+
+    return 0;
+}
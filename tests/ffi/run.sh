@@ -0,0 +1,29 @@
+#!/bin/sh
+#builds the C test program against the static library and runs it, passing files to and
+#from the command line
+#
+#usage: tests/ffi/run.sh [TARGET_DIR]
+#with TARGET_DIR, the library and binary already built there are used instead of a release build,
+#the library from LIB_DIR if it is set. The static library is built by the ffi crate, with
+#`cargo build -p learning_huffman-ffi`
+set -eu
+
+cd "$(dirname "$0")/../.."
+
+if [ $# -ge 1 ]; then
+    target=$1
+else
+    cargo build --release --workspace
+    target=target/release
+fi
+
+work=$(mktemp -d)
+trap 'rm -rf "$work"' EXIT
+
+${CC:-cc} -std=c99 -Wall -Wextra -Werror -Iinclude tests/ffi/ffi_test.c \
+    "${LIB_DIR:-$target}/liblearning_huffman_ffi.a" -lpthread -ldl -lm -o "$work/ffi_test"
+
+"$target/learning_huffman" -c README.md --coder ans "$work/cli.huf"
+"$work/ffi_test" README.md "$work/cli.huf" "$work/c.huf"
+"$target/learning_huffman" -d "$work/c.huf" "$work/c.txt"
+cmp README.md "$work/c.txt"
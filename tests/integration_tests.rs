@@ -6,9 +6,19 @@ mod test {
         process::Command,
     };
 
+    const BINARY: &str = env!("CARGO_BIN_EXE_learning_huffman");
+
+    ///path for `name` in the temp dir, making sure it isn't left over from a previous run
+    fn test_output(name: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("learning_huffman_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path.to_str().unwrap().to_string()
+    }
+
     #[test]
     fn get_byte_frequencies_file() {
-        let response = Command::new("./target/debug/learning_huffman")
+        let response = Command::new(BINARY)
             .arg("-f")
             .arg("./test_files/135-0.txt")
             .output()
@@ -22,16 +32,16 @@ mod test {
 
     #[test]
     fn compress_file_freq_header() {
-        Command::new("./target/debug/learning_huffman")
+        let compressed = test_output("header_compressed.txt");
+
+        Command::new(BINARY)
             .arg("-c")
             .arg("./test_files/135-0.txt")
-            .arg("-o")
-            .arg("./test_output/compressed.txt")
+            .arg(&compressed)
             .output()
             .expect("Binary not found?");
 
-        let mut file =
-            File::open("./test_output/compressed.txt").expect("Couldn't open compressed txt");
+        let mut file = File::open(&compressed).expect("Couldn't open compressed txt");
 
         file.seek(SeekFrom::Start(9 + 1)).expect("couldn't seek");
 
@@ -46,24 +56,26 @@ mod test {
 
     #[test]
     fn test_compression_decompression() {
-        Command::new("./target/debug/learning_huffman")
+        let compressed = test_output("compressed.txt");
+        let decompressed = test_output("decompressed.txt");
+
+        Command::new(BINARY)
             .arg("-c")
             .arg("./test_files/135-0.txt")
-            .arg("./test_output/compressed.txt")
+            .arg(&compressed)
             .output()
             .expect("Binary not found?");
 
-        Command::new("./target/debug/learning_huffman")
+        Command::new(BINARY)
             .arg("-d")
-            .arg("./test_output/compressed.txt")
-            .arg("./test_output/decompressed.txt")
+            .arg(&compressed)
+            .arg(&decompressed)
             .output()
             .expect("Binary not found?");
 
         let mut file1 = File::open("./test_files/135-0.txt").expect("Couldn't open compressed txt");
 
-        let mut file2 =
-            File::open("./test_output/decompressed.txt").expect("Couldn't open decompressed txt");
+        let mut file2 = File::open(&decompressed).expect("Couldn't open decompressed txt");
 
         let mut comp = Vec::new();
         let mut decomp = Vec::new();
@@ -72,4 +84,140 @@ mod test {
 
         assert!(comp.eq(&decomp));
     }
+
+    #[test]
+    fn compress_decompress_coders() {
+        for coder in ["range", "ans", "huffman4"] {
+            let compressed = test_output(&format!("{coder}_compressed.txt"));
+            let decompressed = test_output(&format!("{coder}_decompressed.txt"));
+
+            let status = Command::new(BINARY)
+                .args(["-c", "./README.md", "--coder", coder, &compressed])
+                .status()
+                .expect("Binary not found?");
+            assert!(status.success());
+
+            let status = Command::new(BINARY)
+                .args(["-d", &compressed, &decompressed])
+                .status()
+                .expect("Binary not found?");
+            assert!(status.success());
+
+            assert_eq!(
+                std::fs::read("./README.md").unwrap(),
+                std::fs::read(&decompressed).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn compress_decompress_bwt() {
+        let compressed = test_output("bwt_compressed.txt");
+        let decompressed = test_output("bwt_decompressed.txt");
+
+        let status = Command::new(BINARY)
+            .args(["-c", "./README.md", "--bwt", &compressed])
+            .status()
+            .expect("Binary not found?");
+        assert!(status.success());
+
+        let status = Command::new(BINARY)
+            .args(["-d", &compressed, &decompressed])
+            .status()
+            .expect("Binary not found?");
+        assert!(status.success());
+
+        assert_eq!(
+            std::fs::read("./README.md").unwrap(),
+            std::fs::read(&decompressed).unwrap()
+        );
+    }
+
+    #[test]
+    fn compress_decompress_bzip2() {
+        let compressed = test_output("bzip2_compressed.bz2");
+        let decompressed = test_output("bzip2_decompressed.txt");
+
+        let status = Command::new(BINARY)
+            .args(["-c", "./README.md", "--bzip2", "9", &compressed])
+            .status()
+            .expect("Binary not found?");
+        assert!(status.success());
+
+        let status = Command::new(BINARY)
+            .args(["-d", &compressed, &decompressed])
+            .status()
+            .expect("Binary not found?");
+        assert!(status.success());
+
+        assert_eq!(
+            std::fs::read("./README.md").unwrap(),
+            std::fs::read(&decompressed).unwrap()
+        );
+
+        //checked with the real thing where it is installed
+        if let Ok(output) = Command::new("bzip2").args(["-dc", &compressed]).output() {
+            assert!(output.status.success());
+            assert_eq!(std::fs::read("./README.md").unwrap(), output.stdout);
+        }
+    }
+
+    #[test]
+    fn decompress_bzip2_sample() {
+        let decompressed = test_output("bzip2_sample.bin");
+
+        let status = Command::new(BINARY)
+            .args(["-d", "./test_files/bzip2/runs.bin.bz2", &decompressed])
+            .status()
+            .expect("Binary not found?");
+        assert!(status.success());
+
+        assert_eq!(
+            std::fs::read("./test_files/bzip2/runs.bin").unwrap(),
+            std::fs::read(&decompressed).unwrap()
+        );
+    }
+
+    #[test]
+    fn ffi_c_program() {
+        //needs a C compiler
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+
+        let status = Command::new(env!("CARGO"))
+            .args(["build", "-p", "learning_huffman-ffi"])
+            .status()
+            .expect("cargo not found?");
+        assert!(status.success());
+
+        let target = std::path::Path::new(BINARY).parent().unwrap();
+        let output = Command::new("sh")
+            .arg("./tests/ffi/run.sh")
+            .arg(target)
+            .output()
+            .expect("sh not found?");
+
+        assert!(
+            output.status.success(),
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    #[test]
+    fn no_std_target() {
+        let output = Command::new("sh")
+            .arg("./tests/no_std/run.sh")
+            .output()
+            .expect("sh not found?");
+
+        assert!(
+            output.status.success(),
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
 }
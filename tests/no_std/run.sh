@@ -0,0 +1,16 @@
+#!/bin/sh
+#builds the library without default features for thumbv7em-none-eabi, which has no std,
+#so a dependency that needs std fails the build. Skipped if the target isn't installed
+#
+#usage: tests/no_std/run.sh [TARGET]
+set -eu
+
+cd "$(dirname "$0")/../.."
+
+target=${1:-thumbv7em-none-eabi}
+if ! rustup target list --installed 2>/dev/null | grep -qx "$target"; then
+    echo "skipped, install the target with: rustup target add $target"
+    exit 0
+fi
+
+cargo build --lib --no-default-features --target "$target"
@@ -0,0 +1,359 @@
+#[cfg(test)]
+mod test {
+    use std::{env, fs};
+
+    use learning_huffman::{
+        bwt_untransform, compress_to_vec_with_options, decompress_to_vec, encode_with_table,
+        get_byte_frequencies, hpack_decode, hpack_encode, hpack_encoded_len, suffix_array,
+        CodeTable, Coder, CompressFile, CompressOptions, DecompressFile, HuffCode, HuffNode, Stats,
+        TreeMode,
+    };
+
+    const CASES: usize = 64;
+
+    ///xorshift64* so the suite is reproducible without pulling in a crate,
+    ///set `LEARNING_HUFFMAN_SEED` to try other inputs
+    struct Rng(u64);
+
+    impl Rng {
+        fn new() -> Self {
+            let seed = env::var("LEARNING_HUFFMAN_SEED")
+                .ok()
+                .and_then(|seed| seed.parse().ok())
+                .unwrap_or(0x2545_f491_4f6c_dd1d);
+
+            Rng(seed | 1)
+        }
+
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn bytes(&mut self, len: usize) -> Vec<u8> {
+            (0..len).map(|_| self.next() as u8).collect()
+        }
+
+        ///bytes from a small alphabet where each symbol is about twice as likely as the next
+        fn skewed(&mut self, len: usize) -> Vec<u8> {
+            let size = 1 + self.below(16);
+            let alphabet = self.bytes(size);
+
+            (0..len)
+                .map(|_| {
+                    let bits = self.next() | 1 << 63;
+                    alphabet[(bits.trailing_zeros() as usize).min(alphabet.len() - 1)]
+                })
+                .collect()
+        }
+    }
+
+    ///compress and decompress `bytes` through files, like the CLI does
+    fn round_trip(name: &str, bytes: &[u8]) -> Vec<u8> {
+        let path = |ext: &str| {
+            let path = env::temp_dir().join(format!(
+                "learning_huffman_{}_{}.{}",
+                std::process::id(),
+                name,
+                ext
+            ));
+            let _ = fs::remove_file(&path);
+            path.to_str().unwrap().to_string()
+        };
+        let (input, compressed, output) = (path("in"), path("huf"), path("out"));
+
+        fs::write(&input, bytes).unwrap();
+        CompressFile::new()
+            .compress(&input)
+            .unwrap()
+            .output_freq(&compressed)
+            .unwrap();
+        DecompressFile::new()
+            .decompress_freq(&compressed)
+            .unwrap()
+            .output(&output)
+            .unwrap();
+
+        let decompressed = fs::read(&output).unwrap();
+        for file in [input, compressed, output] {
+            fs::remove_file(file).unwrap();
+        }
+
+        decompressed
+    }
+
+    ///sum of 2^-length over the table, scaled by 2^max_length to stay in integers
+    fn assert_kraft_equality(bytes: &[u8]) {
+        let frequencies = get_byte_frequencies(bytes);
+        let table = HuffCode::from_tree(&HuffNode::from_frequencies(&frequencies));
+
+        assert_eq!(
+            table.len(),
+            frequencies.iter().filter(|freq| **freq != 0).count()
+        );
+
+        let Some(max_length) = table.iter().map(|(_, code)| code.get_length()).max() else {
+            return;
+        };
+        assert!(max_length < 128, "code too long: {max_length}");
+
+        let sum: u128 = table
+            .iter()
+            .map(|(_, code)| 1 << (max_length - code.get_length()))
+            .sum();
+
+        assert_eq!(sum, 1 << max_length, "table: {table:?}");
+    }
+
+    fn check(name: &str, bytes: &[u8]) {
+        assert_kraft_equality(bytes);
+        assert!(
+            round_trip(name, bytes) == bytes,
+            "round trip failed: {bytes:?}"
+        );
+    }
+
+    #[test]
+    fn random_bytes() {
+        let mut rng = Rng::new();
+
+        for _ in 0..CASES {
+            let len = rng.below(4096);
+            check("random", &rng.bytes(len));
+        }
+    }
+
+    #[test]
+    fn skewed_bytes() {
+        let mut rng = Rng::new();
+
+        for _ in 0..CASES {
+            let len = 1 + rng.below(4096);
+            check("skewed", &rng.skewed(len));
+        }
+    }
+
+    #[test]
+    fn all_symbols() {
+        let mut rng = Rng::new();
+
+        for _ in 0..CASES {
+            let mut bytes: Vec<u8> = (0..=255).collect();
+            let len = rng.below(2048);
+            bytes.extend(rng.skewed(len));
+            check("all_symbols", &bytes);
+        }
+    }
+
+    #[test]
+    fn single_symbol() {
+        let mut rng = Rng::new();
+
+        for _ in 0..CASES {
+            let len = 1 + rng.below(4096);
+            check("single", &vec![rng.next() as u8; len]);
+        }
+    }
+
+    #[test]
+    fn tree_modes() {
+        let mut rng = Rng::new();
+
+        for _ in 0..CASES {
+            let len = 1 + rng.below(4096);
+            let bytes = rng.skewed(len);
+            //the standard tree has the shortest longest code of all optimal codes
+            let minimum = Stats::from_bytes_with(&bytes, TreeMode::Standard);
+            let legacy = Stats::from_bytes_with(&bytes, TreeMode::Legacy);
+
+            assert_eq!(legacy.payload_size, minimum.payload_size);
+            assert!(legacy.max_code_length >= minimum.max_code_length);
+
+            for mode in [TreeMode::Standard, TreeMode::Legacy] {
+                let options = CompressOptions::new().tree_mode(mode);
+                let compressed = compress_to_vec_with_options(&bytes, &options);
+
+                assert!(decompress_to_vec(&compressed).unwrap() == bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn coders() {
+        let mut rng = Rng::new();
+
+        for _ in 0..CASES {
+            let len = rng.below(4096);
+            let bytes = if rng.below(2) == 0 {
+                rng.bytes(len)
+            } else {
+                rng.skewed(len)
+            };
+
+            for coder in [Coder::RANGE, Coder::ANS, Coder::HUFFMAN4] {
+                let options = CompressOptions::new().coder(coder);
+                let compressed = compress_to_vec_with_options(&bytes, &options);
+                assert!(
+                    decompress_to_vec(&compressed).unwrap() == bytes,
+                    "{coder:?} round trip failed: {bytes:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bwt() {
+        let mut rng = Rng::new();
+
+        for _ in 0..CASES {
+            let len = rng.below(4096);
+            let bytes = if rng.below(2) == 0 {
+                rng.bytes(len)
+            } else {
+                rng.skewed(len)
+            };
+
+            //the empty suffix stands in for the sentinel
+            let mut naive: Vec<usize> = (0..=bytes.len()).collect();
+            naive.sort_by_key(|i| &bytes[*i..]);
+            assert_eq!(suffix_array(&bytes), naive, "wrong suffix array: {bytes:?}");
+
+            for coder in [Coder::HUFFMAN, Coder::ANS] {
+                let options = CompressOptions::new().coder(coder).bwt(true);
+                let compressed = compress_to_vec_with_options(&bytes, &options);
+                assert!(
+                    decompress_to_vec(&compressed).unwrap() == bytes,
+                    "{coder:?} bwt round trip failed: {bytes:?}"
+                );
+            }
+
+            //must fail cleanly on anything
+            let _ = bwt_untransform(&bytes);
+        }
+    }
+
+    #[test]
+    fn hpack() {
+        let mut rng = Rng::new();
+
+        for _ in 0..CASES {
+            let len = rng.below(256);
+            let bytes = if rng.below(2) == 0 {
+                rng.bytes(len)
+            } else {
+                rng.skewed(len)
+            };
+
+            let mut encoded = Vec::new();
+            hpack_encode(&bytes, &mut encoded);
+            assert_eq!(encoded.len(), hpack_encoded_len(&bytes));
+            assert!(
+                hpack_decode(&encoded).unwrap() == bytes,
+                "hpack round trip failed: {bytes:?}"
+            );
+
+            //must fail cleanly on anything
+            let _ = hpack_decode(&bytes);
+        }
+    }
+
+    ///how `encode_with_table` wrote codes a bit at a time before it had an accumulator
+    fn encode_bit_by_bit(bytes: &[u8], table: &CodeTable) -> (Vec<u8>, u8) {
+        let mut output = Vec::new();
+        let (mut current, mut used) = (0u8, 0);
+
+        for byte in bytes {
+            let code = &table[*byte];
+            for i in 0..code.get_length() {
+                current |= (((code.get_code() >> i) & 1) as u8) << used;
+                used += 1;
+
+                if used == 8 {
+                    output.push(current);
+                    (current, used) = (0, 0);
+                }
+            }
+        }
+        if used != 0 {
+            output.push(current);
+        }
+
+        (output, used)
+    }
+
+    #[test]
+    fn encode_bit_exact() {
+        let mut rng = Rng::new();
+
+        for _ in 0..CASES {
+            //any lengths up to 128 bits, with bits above them that have to be ignored
+            let size = 1 + rng.below(12);
+            let alphabet = rng.bytes(size);
+            let max_length = [8, 64, 128][rng.below(3)];
+            let table: CodeTable = alphabet
+                .iter()
+                .map(|byte| {
+                    let length = rng.below(max_length + 1) as u16;
+                    let code = (rng.next() as u128) << 64 | rng.next() as u128;
+                    (*byte, HuffCode::new(0, length, code))
+                })
+                .collect();
+
+            let len = rng.below(2048);
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| alphabet[rng.below(alphabet.len())])
+                .collect();
+
+            let mut output = vec![0xaa];
+            let last_byte_size = encode_with_table(&bytes, &table, &mut output).unwrap();
+            let (expected, expected_last) = encode_bit_by_bit(&bytes, &table);
+            assert!(output[1..] == expected, "table: {table:?}");
+            assert_eq!(last_byte_size, expected_last);
+
+            let missing = (0..=255).find(|byte| table.get(*byte).is_none()).unwrap();
+            assert!(encode_with_table(&[alphabet[0], missing], &table, &mut output).is_err());
+            assert!(output[1..] == expected);
+        }
+    }
+
+    #[test]
+    fn interleaved_streams() {
+        let mut rng = Rng::new();
+
+        for _ in 0..CASES {
+            let len = rng.below(4096);
+            let bytes = rng.skewed(len);
+
+            for mode in [TreeMode::Standard, TreeMode::Legacy] {
+                let single = CompressOptions::new().tree_mode(mode);
+                let interleaved = single.coder(Coder::HUFFMAN4);
+                let single = compress_to_vec_with_options(&bytes, &single);
+                let compressed = compress_to_vec_with_options(&bytes, &interleaved);
+
+                assert!(
+                    decompress_to_vec(&compressed).unwrap() == bytes,
+                    "{mode:?} round trip failed: {bytes:?}"
+                );
+                //the same codes, with a jump table and every stream padded to a whole byte
+                assert!(compressed.len() <= single.len() + 3 * 8 + 3);
+            }
+        }
+    }
+
+    #[test]
+    fn two_symbols_whole_bytes() {
+        //8 bits of payload exactly, the last byte is full
+        check("whole_bytes", b"ABABABAB");
+    }
+
+    #[test]
+    fn empty() {
+        check("empty", &[]);
+    }
+}
@@ -0,0 +1,29 @@
+#!/bin/sh
+#builds the ffi crate for wasm32-unknown-unknown, with the library without the fs feature, and runs
+#tests/wasm/test.mjs on it in node, skipped if the target or node isn't installed
+set -eu
+
+cd "$(dirname "$0")/../.."
+
+if ! rustup target list --installed 2>/dev/null | grep -q wasm32-unknown-unknown; then
+    echo "skipped, install the target with: rustup target add wasm32-unknown-unknown"
+    exit 0
+fi
+if ! command -v node >/dev/null; then
+    echo "skipped, node isn't installed"
+    exit 0
+fi
+
+cargo build --release -p learning_huffman-ffi --target wasm32-unknown-unknown
+wasm=target/wasm32-unknown-unknown/release/learning_huffman_ffi.wasm
+
+#a file from the command line as well, if it is built
+cli=""
+if [ -x target/release/learning_huffman ]; then
+    work=$(mktemp -d)
+    trap 'rm -rf "$work"' EXIT
+    cli="$work/cli.huf"
+    target/release/learning_huffman -c README.md --coder ans --bwt "$cli"
+fi
+
+node tests/wasm/test.mjs "$wasm" README.md "$cli"
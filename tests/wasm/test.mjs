@@ -0,0 +1,31 @@
+//round trips through the wasm build in node, run by tests/wasm/run.sh
+//
+//usage: node tests/wasm/test.mjs <module.wasm> <input> <compressed by the command line or "">
+
+import { readFileSync } from "node:fs";
+import assert from "node:assert/strict";
+
+import { load } from "../../wasm/learning_huffman.mjs";
+
+const [wasm, inputPath, cliPath] = process.argv.slice(2);
+const huffman = await load(readFileSync(wasm));
+const input = new Uint8Array(readFileSync(inputPath));
+
+for (const coder of ["huffman", "range", "ans", "huffman4"]) {
+  for (const bwt of [false, true]) {
+    const compressed = huffman.compress(input, { coder, bwt });
+    assert.deepEqual(huffman.decompress(compressed), input);
+  }
+}
+
+const compressed = huffman.compress(input, { treeMode: "legacy" });
+assert.throws(() => huffman.decompress(compressed, { maxOutputSize: 10 }), /10/);
+assert.throws(() => huffman.decompress(input), Error);
+assert.throws(() => huffman.compress(input, { coder: "zip" }), /unknown coder/);
+assert.deepEqual(huffman.decompress(huffman.compress(new Uint8Array())), new Uint8Array());
+
+if (cliPath) {
+  assert.deepEqual(huffman.decompress(new Uint8Array(readFileSync(cliPath))), input);
+}
+
+console.log("wasm test passed");
@@ -0,0 +1,71 @@
+//JS API of the wasm build, see ffi/src/wasm.rs
+//
+//  const huffman = await load(fetch("learning_huffman_ffi.wasm"));
+//  const bytes = huffman.decompress(new Uint8Array(await file.arrayBuffer()));
+
+//ids of the coders, the same as in the files
+export const CODERS = { huffman: 0, range: 1, ans: 2, huffman4: 3 };
+
+//the same flags as include/learning_huffman.h
+const FLAG_BWT = 1;
+const TREE_MODES = { standard: 0, legacy: 4 };
+
+//load the module from a `Response` or its bytes
+export async function load(source) {
+  const resolved = await source;
+  const { instance } =
+    resolved instanceof Response
+      ? await WebAssembly.instantiateStreaming(resolved)
+      : await WebAssembly.instantiate(resolved);
+
+  return wrap(instance.exports);
+}
+
+//functions taking and returning `Uint8Array`s for the exports of an instance,
+//they throw an `Error` with the message of the library when it fails
+export function wrap(exports) {
+  const call = (bytes, run) => {
+    const input = exports.lh_wasm_alloc(bytes.length);
+    new Uint8Array(exports.memory.buffer, input, bytes.length).set(bytes);
+
+    let status;
+    try {
+      status = run(input, bytes.length);
+    } finally {
+      exports.lh_wasm_free(input, bytes.length);
+    }
+
+    //copied as memory can grow and move on the next call
+    const output = new Uint8Array(
+      exports.memory.buffer,
+      exports.lh_wasm_output(),
+      exports.lh_wasm_output_len(),
+    ).slice();
+
+    if (status !== 0) {
+      throw new Error(new TextDecoder().decode(output));
+    }
+    return output;
+  };
+
+  return {
+    //`coder` is one of `CODERS` by name, `treeMode` standard or legacy
+    compress(bytes, { coder = "huffman", bwt = false, treeMode = "standard" } = {}) {
+      if (!(coder in CODERS) || !(treeMode in TREE_MODES)) {
+        throw new Error(`unknown coder ${coder} or tree mode ${treeMode}`);
+      }
+      const flags = (bwt ? FLAG_BWT : 0) | TREE_MODES[treeMode];
+
+      return call(bytes, (input, length) =>
+        exports.lh_wasm_compress(input, length, CODERS[coder], flags),
+      );
+    },
+
+    //`.huf` and `.bz2` files, `maxOutputSize` 0 for no limit
+    decompress(bytes, { maxOutputSize = 0 } = {}) {
+      return call(bytes, (input, length) =>
+        exports.lh_wasm_decompress(input, length, maxOutputSize),
+      );
+    },
+  };
+}